@@ -1,25 +0,0 @@
-/* Link Layer command interface */
-#include "bsp.h"
-#include "common_types.h"
-#include "dtm.h"
-#include "event_manager.h"
-#include "evnt_schdlr_gnrc_if.h"
-#include "hci.h"
-#include "ll_error.h"
-#include "ll_intf.h"
-#include "ll_intf_cmn.h"
-#include "mac_host_intf.h"
-#include "mem_intf.h"
-#include "os_wrapper.h"
-#include "platform.h"
-#include "power_table.h"
-#include "pta.h"
-#include "ral.h"
-#include "rfd_dev_config.h"
-#include "linklayer_plat.h"
-#include "ll_sys_sequencer.h"
-#include "ll_sys_startup.h"
-#include "ll_sys.h"
-#include "ll_version.h"
-#include "ll_intf_cmn.h"
-#include "power_table.h"
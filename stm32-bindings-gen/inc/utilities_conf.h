@@ -0,0 +1,154 @@
+/* Default configuration of the ST utilities (sequencer, timer server, ...).
+ *
+ * Used while generating bindings and copied into the generated crate, where
+ * it is compiled with the utility sources. Point STM32_BINDINGS_CONF_DIR at a
+ * directory with your own utilities_conf.h to override it.
+ */
+
+#ifndef STM32_BINDINGS_GEN_UTILITIES_CONF_H
+#define STM32_BINDINGS_GEN_UTILITIES_CONF_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#include <stdarg.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <string.h>
+
+static inline uint32_t utilities_conf_get_primask(void)
+{
+  uint32_t primask;
+  __asm volatile("MRS %0, primask" : "=r"(primask));
+  return primask;
+}
+
+static inline void utilities_conf_set_primask(uint32_t primask)
+{
+  __asm volatile("MSR primask, %0" : : "r"(primask) : "memory");
+}
+
+#ifndef UTILS_ENTER_CRITICAL_SECTION
+#define UTILS_ENTER_CRITICAL_SECTION()                          \
+  uint32_t primask_bit = utilities_conf_get_primask();          \
+  __asm volatile("cpsid i" : : : "memory")
+#endif
+
+#ifndef UTILS_EXIT_CRITICAL_SECTION
+#define UTILS_EXIT_CRITICAL_SECTION() utilities_conf_set_primask(primask_bit)
+#endif
+
+#ifndef UTILS_MEMSET8
+#define UTILS_MEMSET8(dest, value, size) memset((dest), (value), (size))
+#endif
+
+/* Sequencer */
+
+#ifndef UTIL_SEQ_CONF_TASK_NBR
+#define UTIL_SEQ_CONF_TASK_NBR (32U)
+#endif
+
+#ifndef UTIL_SEQ_CONF_PRIO_NBR
+#define UTIL_SEQ_CONF_PRIO_NBR (2U)
+#endif
+
+#ifndef UTIL_SEQ_INIT_CRITICAL_SECTION
+#define UTIL_SEQ_INIT_CRITICAL_SECTION()
+#endif
+
+#ifndef UTIL_SEQ_ENTER_CRITICAL_SECTION
+#define UTIL_SEQ_ENTER_CRITICAL_SECTION() UTILS_ENTER_CRITICAL_SECTION()
+#endif
+
+#ifndef UTIL_SEQ_EXIT_CRITICAL_SECTION
+#define UTIL_SEQ_EXIT_CRITICAL_SECTION() UTILS_EXIT_CRITICAL_SECTION()
+#endif
+
+#ifndef UTIL_SEQ_ENTER_CRITICAL_SECTION_IDLE
+#define UTIL_SEQ_ENTER_CRITICAL_SECTION_IDLE() UTILS_ENTER_CRITICAL_SECTION()
+#endif
+
+#ifndef UTIL_SEQ_EXIT_CRITICAL_SECTION_IDLE
+#define UTIL_SEQ_EXIT_CRITICAL_SECTION_IDLE() UTILS_EXIT_CRITICAL_SECTION()
+#endif
+
+#ifndef UTIL_SEQ_MEMSET8
+#define UTIL_SEQ_MEMSET8(dest, value, size) UTILS_MEMSET8((dest), (value), (size))
+#endif
+
+/* Timer server */
+
+#ifndef UTIL_TIMER_INIT_CRITICAL_SECTION
+#define UTIL_TIMER_INIT_CRITICAL_SECTION()
+#endif
+
+#ifndef UTIL_TIMER_ENTER_CRITICAL_SECTION
+#define UTIL_TIMER_ENTER_CRITICAL_SECTION() UTILS_ENTER_CRITICAL_SECTION()
+#endif
+
+#ifndef UTIL_TIMER_EXIT_CRITICAL_SECTION
+#define UTIL_TIMER_EXIT_CRITICAL_SECTION() UTILS_EXIT_CRITICAL_SECTION()
+#endif
+
+/* Low-power manager */
+
+#ifndef UTIL_LPM_INIT_CRITICAL_SECTION
+#define UTIL_LPM_INIT_CRITICAL_SECTION()
+#endif
+
+#ifndef UTIL_LPM_ENTER_CRITICAL_SECTION
+#define UTIL_LPM_ENTER_CRITICAL_SECTION() UTILS_ENTER_CRITICAL_SECTION()
+#endif
+
+#ifndef UTIL_LPM_EXIT_CRITICAL_SECTION
+#define UTIL_LPM_EXIT_CRITICAL_SECTION() UTILS_EXIT_CRITICAL_SECTION()
+#endif
+
+/* Advanced trace */
+
+#ifndef UTIL_ADV_TRACE_CONDITIONNAL
+#define UTIL_ADV_TRACE_CONDITIONNAL
+#endif
+
+#ifndef UTIL_ADV_TRACE_OVERRUN
+#define UTIL_ADV_TRACE_OVERRUN
+#endif
+
+#ifndef UTIL_ADV_TRACE_INIT_CRITICAL_SECTION
+#define UTIL_ADV_TRACE_INIT_CRITICAL_SECTION()
+#endif
+
+#ifndef UTIL_ADV_TRACE_ENTER_CRITICAL_SECTION
+#define UTIL_ADV_TRACE_ENTER_CRITICAL_SECTION() UTILS_ENTER_CRITICAL_SECTION()
+#endif
+
+#ifndef UTIL_ADV_TRACE_EXIT_CRITICAL_SECTION
+#define UTIL_ADV_TRACE_EXIT_CRITICAL_SECTION() UTILS_EXIT_CRITICAL_SECTION()
+#endif
+
+#ifndef UTIL_ADV_TRACE_TMP_BUF_SIZE
+#define UTIL_ADV_TRACE_TMP_BUF_SIZE (256U)
+#endif
+
+#ifndef UTIL_ADV_TRACE_TMP_MAX_TIMESTMAP_SIZE
+#define UTIL_ADV_TRACE_TMP_MAX_TIMESTMAP_SIZE (15U)
+#endif
+
+#ifndef UTIL_ADV_TRACE_FIFO_SIZE
+#define UTIL_ADV_TRACE_FIFO_SIZE (1024U)
+#endif
+
+#ifndef UTIL_ADV_TRACE_MEMSET8
+#define UTIL_ADV_TRACE_MEMSET8(dest, value, size) UTILS_MEMSET8((dest), (value), (size))
+#endif
+
+#ifndef UTIL_ADV_TRACE_VSNPRINTF
+#define UTIL_ADV_TRACE_VSNPRINTF(...) vsnprintf(__VA_ARGS__)
+#endif
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* STM32_BINDINGS_GEN_UTILITIES_CONF_H */
@@ -0,0 +1,42 @@
+/* AES, PKA and RNG drivers of the WPAN hardware interface.
+ *
+ * Declarations from Projects/Common/WPAN/Interfaces/hw.h, repeated here so
+ * the device headers hw.h pulls in are not needed. */
+#ifndef STM32_BINDINGS_GEN_WBA_HW_IF_H
+#define STM32_BINDINGS_GEN_WBA_HW_IF_H
+
+#include <stdint.h>
+
+/* Mode flags of HW_AES_SetKey() */
+#define HW_AES_DEC  0
+#define HW_AES_ENC  1
+#define HW_AES_REV  2
+#define HW_AES_SWAP 4
+
+extern void HW_AES_Enable(void);
+extern void HW_AES_SetKey(uint32_t mode, const uint8_t *key);
+extern void HW_AES_Crypt(const uint32_t *input, uint32_t *output);
+extern void HW_AES_Crypt8(const uint8_t *input, uint8_t *output);
+extern void HW_AES_Disable(void);
+
+extern int HW_PKA_Enable(void);
+extern int HW_PKA_EndOfOperation(void);
+extern void HW_PKA_Disable(void);
+extern void HW_PKA_P256_StartRangeCheck(const uint32_t *coord);
+extern uint32_t HW_PKA_P256_IsRangeCheckOk(void);
+extern void HW_PKA_P256_StartPointCheck(const uint32_t *x, const uint32_t *y);
+extern uint32_t HW_PKA_P256_IsPointCheckOk(void);
+extern void HW_PKA_P256_StartEccScalarMul(const uint32_t *k, const uint32_t *p_x,
+                                          const uint32_t *p_y);
+extern void HW_PKA_P256_ReadEccScalarMul(uint32_t *p_x, uint32_t *p_y);
+
+extern void HW_RNG_Init(void);
+extern void HW_RNG_Start(void);
+extern void HW_RNG_Disable(void);
+extern void HW_RNG_Get(uint8_t n, uint32_t *val);
+extern int HW_RNG_Process(void);
+extern void HW_RNG_SetPoolThreshold(uint8_t threshold);
+extern void HW_RNG_EnableClock(uint8_t user_mask);
+extern void HW_RNG_DisableClock(uint8_t user_mask);
+
+#endif /* STM32_BINDINGS_GEN_WBA_HW_IF_H */
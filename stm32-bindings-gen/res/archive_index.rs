@@ -0,0 +1,80 @@
+use std::collections::BTreeMap;
+
+/// Reads the global symbol index of a GNU-style `ar` archive.
+///
+/// Both the 32-bit (`/`) and 64-bit (`/SYM64/`) indexes are read. Returns an
+/// empty list for archives without an index or in another format.
+pub fn archive_symbols(data: &[u8]) -> Vec<String> {
+    let Some(data) = data.strip_prefix(b"!<arch>\n") else {
+        return Vec::new();
+    };
+    if data.len() < 60 {
+        return Vec::new();
+    }
+
+    let (header, rest) = data.split_at(60);
+    let word = match String::from_utf8_lossy(&header[..16]).trim_end() {
+        "/" => 4,
+        "/SYM64/" => 8,
+        _ => return Vec::new(),
+    };
+    let Ok(size) = String::from_utf8_lossy(&header[48..58])
+        .trim()
+        .parse::<usize>()
+    else {
+        return Vec::new();
+    };
+    let Some(index) = rest.get(..size) else {
+        return Vec::new();
+    };
+    let Some(count) = index.get(..word) else {
+        return Vec::new();
+    };
+    // Big-endian symbol count, then one member offset per symbol.
+    let count = count
+        .iter()
+        .fold(0usize, |acc, byte| (acc << 8) | usize::from(*byte));
+    let Some(names) = count
+        .checked_add(1)
+        .and_then(|words| words.checked_mul(word))
+        .and_then(|offsets| index.get(offsets..))
+    else {
+        return Vec::new();
+    };
+
+    names
+        .split(|byte| *byte == 0)
+        .take(count)
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+        .collect()
+}
+
+/// Groups the global symbols defined by more than one of `libraries`, given
+/// as names and symbol lists, by the first two libraries defining them.
+pub fn duplicate_symbols(
+    libraries: &[(String, Vec<String>)],
+) -> BTreeMap<(String, String), Vec<String>> {
+    let mut owners: BTreeMap<&str, &str> = BTreeMap::new();
+    let mut conflicts: BTreeMap<(String, String), Vec<String>> = BTreeMap::new();
+
+    for (lib, symbols) in libraries {
+        let mut symbols = symbols.iter().collect::<Vec<_>>();
+        symbols.sort();
+        symbols.dedup();
+        for symbol in symbols {
+            match owners.get(symbol.as_str()) {
+                Some(owner) if owner != lib => {
+                    conflicts
+                        .entry((owner.to_string(), lib.clone()))
+                        .or_default()
+                        .push(symbol.clone());
+                }
+                Some(_) => {}
+                None => {
+                    owners.insert(symbol, lib);
+                }
+            }
+        }
+    }
+    conflicts
+}
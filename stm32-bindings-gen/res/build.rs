@@ -1,27 +1,243 @@
 use std::path::{Path, PathBuf};
-use std::{env, fs, io};
+use std::{env, fs};
 
-fn add_dir(src: &Path) -> io::Result<()> {
-    println!("cargo:rustc-link-search=native={}", src.to_str().unwrap());
+mod archive_index;
 
-    for entry in fs::read_dir(src)? {
-        let entry = entry?;
-        let path = entry.path();
-        if path.is_dir() {
-            add_dir(&path)?;
+include!("link_dirs.rs");
+include!("c_sources.rs");
+
+/// Target features showing that the core has an FPU.
+const FPU_FEATURES: &[&str] = &["fpregs", "vfp2", "vfp3", "vfp4", "fp-armv8"];
+
+/// Float ABI the prebuilt archives must be compiled for: `hard` for
+/// `eabihf` targets, `softfp` for `eabi` targets with an FPU, e.g. with
+/// `-C target-feature=+fp-armv8`, and `soft` otherwise.
+fn target_float_abi() -> String {
+    if let Ok(abi) = env::var("STM32_BINDINGS_FLOAT_ABI") {
+        return abi.trim().to_ascii_lowercase();
+    }
+
+    let target_abi = env::var("CARGO_CFG_TARGET_ABI").unwrap_or_default();
+    let target = env::var("TARGET").unwrap_or_default();
+    if target_abi == "eabihf" || target.ends_with("eabihf") {
+        return "hard".to_string();
+    }
+    let features = env::var("CARGO_CFG_TARGET_FEATURE").unwrap_or_default();
+    if features
+        .split(',')
+        .any(|feature| FPU_FEATURES.contains(&feature))
+    {
+        "softfp".to_string()
+    } else {
+        "soft".to_string()
+    }
+}
+
+/// Link-search priority of archives built for `abi` on a target needing
+/// `target_abi`: `Some(0)` for an exact match, `Some(1)` for soft-float
+/// archives on a `softfp` target, and `None` if they cannot be linked.
+fn link_priority(abi: Option<&str>, target_abi: &str) -> Option<u8> {
+    match abi {
+        None => Some(0),
+        Some(abi) if abi == target_abi => Some(0),
+        Some("soft") if target_abi == "softfp" => Some(1),
+        Some(_) => None,
+    }
+}
+
+/// Warns (or fails with `STM32_BINDINGS_DUPLICATE_SYMBOLS=deny`) when two
+/// selected libraries define the same global symbol.
+fn check_duplicate_symbols(search_dirs: &[PathBuf], libs: &[String]) {
+    let libraries = libs
+        .iter()
+        .filter_map(|lib| {
+            let file_name = format!("lib{lib}.a");
+            let archive = search_dirs
+                .iter()
+                .map(|dir| dir.join(&file_name))
+                .find(|path| path.is_file())?;
+            let symbols = fs::read(&archive)
+                .map(|data| archive_index::archive_symbols(&data))
+                .unwrap_or_default();
+            Some((lib.clone(), symbols))
+        })
+        .collect::<Vec<_>>();
+    let conflicts = archive_index::duplicate_symbols(&libraries);
+
+    if conflicts.is_empty() {
+        return;
+    }
+
+    let deny = env::var("STM32_BINDINGS_DUPLICATE_SYMBOLS").is_ok_and(|value| value == "deny");
+    for ((first, second), symbols) in &conflicts {
+        let mut listed = symbols
+            .iter()
+            .take(5)
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(", ");
+        if symbols.len() > 5 {
+            listed.push_str(&format!(", ... ({} total)", symbols.len()));
+        }
+        let message = format!(
+            "features `lib_{first}` and `lib_{second}` both define {listed}; enable only one of them"
+        );
+        if deny {
+            panic!("{message}");
+        }
+        println!("cargo:warning={message}");
+    }
+}
+
+/// `platform_*` features exporting hooks that forward to a registered value,
+/// with the name of the registration macro.
+const PLATFORM_HOOKS: &[(&str, &str)] = &[
+    ("rng", "register_rng_source"),
+    ("temperature", "register_temperature_sensor"),
+    ("clock", "register_clock_control"),
+];
+
+/// Fails the link with a message naming the registration macro when a
+/// `platform_*` feature is enabled without registering its value, rather
+/// than with an undefined `__stm32_bindings_platform_*` symbol.
+///
+/// The check is a linker script, installed as a library so the linker also
+/// reads it when linking the crates depending on this one.
+fn check_platform_hooks(out_dir: &Path) {
+    let mut script = String::new();
+    for (hook, register) in PLATFORM_HOOKS {
+        let feature = format!("CARGO_FEATURE_PLATFORM_{}", hook.to_ascii_uppercase());
+        if env::var_os(feature).is_none() {
+            continue;
+        }
+        let symbol = format!("__stm32_bindings_platform_{hook}");
+        script.push_str(&format!(
+            "PROVIDE({symbol} = 0);\nASSERT({symbol} != 0, \"stm32-bindings: feature `platform_{hook}` is enabled but `{register}!` is never called\");\n"
+        ));
+    }
+    if script.is_empty() {
+        return;
+    }
+
+    fs::write(out_dir.join("libstm32_bindings_platform.a"), script).unwrap();
+    println!("cargo:rustc-link-search=native={}", out_dir.display());
+    println!("cargo:rustc-link-lib=static:-bundle=stm32_bindings_platform");
+}
+
+/// Lists the `.c` files of `dir` in a stable order.
+fn c_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut sources = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "c"))
+        .collect::<Vec<_>>();
+    sources.sort();
+    sources
+}
+
+/// Compiles the preprocessed `static inline` wrappers emitted by the
+/// generator, if any. Crates generated for several targets have one
+/// directory of wrappers per target.
+fn compile_shims(dir: &Path) {
+    let target_dir = dir.join(env::var("TARGET").unwrap_or_default());
+    let dir = if target_dir.is_dir() {
+        &target_dir
+    } else {
+        dir
+    };
+    let sources = c_files(dir);
+    if sources.is_empty() {
+        return;
+    }
+
+    for source in &sources {
+        println!("cargo:rerun-if-changed={}", source.display());
+    }
+    cc::Build::new()
+        .files(&sources)
+        .warnings(false)
+        .compile("stm32_bindings_shims");
+}
+
+/// Compiles the vendor C sources of the enabled modules.
+///
+/// `STM32_BINDINGS_CONF_DIR` is searched before the bundled default
+/// configuration headers, so applications can supply their own
+/// `utilities_conf.h`.
+fn compile_c_sources(crate_dir: &Path) {
+    println!("cargo:rerun-if-env-changed=STM32_BINDINGS_CONF_DIR");
+    let conf_dir = env::var_os("STM32_BINDINGS_CONF_DIR").map(PathBuf::from);
+
+    for (dir, feature) in C_SOURCES {
+        if let Some(feature) = feature {
+            let var = format!("CARGO_FEATURE_{}", feature.to_ascii_uppercase());
+            if env::var_os(var).is_none() {
+                continue;
+            }
         }
+
+        let dir = crate_dir.join(dir);
+        let sources = c_files(&dir);
+        if sources.is_empty() {
+            continue;
+        }
+        for source in &sources {
+            println!("cargo:rerun-if-changed={}", source.display());
+        }
+
+        let mut build = cc::Build::new();
+        if let Some(conf_dir) = &conf_dir {
+            build.include(conf_dir);
+        }
+        let name = dir.file_name().unwrap().to_string_lossy();
+        build
+            .include(crate_dir.join("src").join("csrc").join("include"))
+            .include(&dir)
+            .files(&sources)
+            .warnings(false)
+            .compile(&format!("stm32_bindings_{name}"));
     }
-    Ok(())
 }
 
 fn main() {
     let crate_dir = PathBuf::from(env::var_os("CARGO_MANIFEST_DIR").unwrap());
-    let lib_dir = crate_dir.join("src").join("lib");
+    let float_abi = target_float_abi();
 
-    add_dir(&lib_dir).unwrap();
+    println!("cargo:rerun-if-env-changed=STM32_BINDINGS_FLOAT_ABI");
+    println!("cargo:rustc-check-cfg=cfg(stm32_bindings_target, values(any()))");
+    if let Ok(target) = env::var("TARGET") {
+        println!("cargo:rustc-cfg=stm32_bindings_target=\"{target}\"");
+    }
+    println!("cargo:rerun-if-env-changed=STM32_BINDINGS_DUPLICATE_SYMBOLS");
 
-    env::vars()
+    // Search the exact matches first, so a library built for the FPU of the
+    // target wins over its soft-float variant.
+    let mut link_dirs = LINK_DIRS
+        .iter()
+        .filter_map(|(dir, abi)| Some((link_priority(*abi, &float_abi)?, dir)))
+        .collect::<Vec<_>>();
+    link_dirs.sort_by_key(|(priority, _)| *priority);
+    let mut search_dirs = Vec::new();
+    for (_, dir) in link_dirs {
+        let dir = crate_dir.join(dir);
+        println!("cargo:rustc-link-search=native={}", dir.display());
+        search_dirs.push(dir);
+    }
+
+    let libs = env::vars()
         .filter_map(|(a, _)| a.strip_prefix("CARGO_FEATURE_LIB_").map(|a| a.to_string()))
         .map(|a| a.to_ascii_lowercase())
+        .collect::<Vec<_>>();
+
+    check_duplicate_symbols(&search_dirs, &libs);
+    check_platform_hooks(&PathBuf::from(env::var_os("OUT_DIR").unwrap()));
+
+    compile_shims(&crate_dir.join("src").join("shims"));
+    compile_c_sources(&crate_dir);
+
+    libs.iter()
         .for_each(|a| println!("cargo:rustc-link-lib=static={}", a));
 }
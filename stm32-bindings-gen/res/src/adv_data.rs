@@ -0,0 +1,284 @@
+//! BLE advertising and scan response data.
+//!
+//! The data is a sequence of AD structures, each a length byte, an AD type
+//! and its value (Core specification, Vol 3, Part C, 11). [`AdvData`] packs
+//! them into a fixed-capacity buffer that refuses structures past the
+//! capacity: 31 bytes for legacy advertising with [`LegacyAdvData`], 251
+//! bytes per `HCI_LE_Set_Extended_Advertising_Data` command with
+//! [`ExtendedAdvData`]. [`parse`] walks the structures of received data,
+//! e.g. from an advertising report, borrowing their values from it.
+
+use heapless::Vec;
+
+/// The structure does not fit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TooLong;
+
+/// A structure runs past the end of the data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Malformed;
+
+/// Type of an AD structure, from the Assigned Numbers document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AdType(pub u8);
+
+impl AdType {
+    pub const FLAGS: AdType = AdType(0x01);
+    pub const INCOMPLETE_SERVICE_UUIDS_16: AdType = AdType(0x02);
+    pub const COMPLETE_SERVICE_UUIDS_16: AdType = AdType(0x03);
+    pub const INCOMPLETE_SERVICE_UUIDS_128: AdType = AdType(0x06);
+    pub const COMPLETE_SERVICE_UUIDS_128: AdType = AdType(0x07);
+    pub const SHORTENED_LOCAL_NAME: AdType = AdType(0x08);
+    pub const COMPLETE_LOCAL_NAME: AdType = AdType(0x09);
+    pub const TX_POWER_LEVEL: AdType = AdType(0x0A);
+    pub const SERVICE_DATA_16: AdType = AdType(0x16);
+    pub const APPEARANCE: AdType = AdType(0x19);
+    pub const MANUFACTURER_SPECIFIC_DATA: AdType = AdType(0xFF);
+}
+
+/// Value of the flags AD structure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Flags(pub u8);
+
+impl Flags {
+    pub const LE_LIMITED_DISCOVERABLE: Flags = Flags(0x01);
+    pub const LE_GENERAL_DISCOVERABLE: Flags = Flags(0x02);
+    pub const BR_EDR_NOT_SUPPORTED: Flags = Flags(0x04);
+
+    /// Flags of a discoverable LE-only device.
+    pub const LE_ONLY_GENERAL_DISCOVERABLE: Flags =
+        Flags(Self::LE_GENERAL_DISCOVERABLE.0 | Self::BR_EDR_NOT_SUPPORTED.0);
+
+    pub const fn contains(self, other: Flags) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl core::ops::BitOr for Flags {
+    type Output = Flags;
+
+    fn bitor(self, rhs: Flags) -> Flags {
+        Flags(self.0 | rhs.0)
+    }
+}
+
+/// Advertising data of up to `N` bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdvData<const N: usize> {
+    bytes: Vec<u8, N>,
+}
+
+/// Data of a legacy advertising or scan response PDU.
+pub type LegacyAdvData = AdvData<31>;
+
+/// Data of one `HCI_LE_Set_Extended_Advertising_Data` command.
+pub type ExtendedAdvData = AdvData<251>;
+
+impl<const N: usize> AdvData<N> {
+    pub const fn new() -> Self {
+        Self { bytes: Vec::new() }
+    }
+
+    /// Appends a structure of type `ad_type` whose value is the
+    /// concatenation of `parts`. Nothing is appended if it does not fit.
+    pub fn push(&mut self, ad_type: AdType, parts: &[&[u8]]) -> Result<(), TooLong> {
+        let value_len: usize = parts.iter().map(|part| part.len()).sum();
+        // The length byte counts the AD type and the value.
+        let len = u8::try_from(1 + value_len).map_err(|_| TooLong)?;
+        if self.bytes.len() + 1 + len as usize > N {
+            return Err(TooLong);
+        }
+        // Cannot fail: the room was checked above.
+        let _ = self.bytes.extend_from_slice(&[len, ad_type.0]);
+        for part in parts {
+            let _ = self.bytes.extend_from_slice(part);
+        }
+        Ok(())
+    }
+
+    pub fn flags(&mut self, flags: Flags) -> Result<(), TooLong> {
+        self.push(AdType::FLAGS, &[&[flags.0]])
+    }
+
+    pub fn complete_local_name(&mut self, name: &str) -> Result<(), TooLong> {
+        self.push(AdType::COMPLETE_LOCAL_NAME, &[name.as_bytes()])
+    }
+
+    pub fn shortened_local_name(&mut self, name: &str) -> Result<(), TooLong> {
+        self.push(AdType::SHORTENED_LOCAL_NAME, &[name.as_bytes()])
+    }
+
+    /// Appends 16-bit service UUIDs, the complete list of the device if
+    /// `complete`.
+    pub fn service_uuids_16(&mut self, uuids: &[u16], complete: bool) -> Result<(), TooLong> {
+        let ad_type = if complete {
+            AdType::COMPLETE_SERVICE_UUIDS_16
+        } else {
+            AdType::INCOMPLETE_SERVICE_UUIDS_16
+        };
+        let mut value = Vec::<u8, 254>::new();
+        for uuid in uuids {
+            value
+                .extend_from_slice(&uuid.to_le_bytes())
+                .map_err(|_| TooLong)?;
+        }
+        self.push(ad_type, &[&value])
+    }
+
+    /// Appends 128-bit service UUIDs, each little-endian as sent over the
+    /// air, the complete list of the device if `complete`.
+    pub fn service_uuids_128(&mut self, uuids: &[[u8; 16]], complete: bool) -> Result<(), TooLong> {
+        let ad_type = if complete {
+            AdType::COMPLETE_SERVICE_UUIDS_128
+        } else {
+            AdType::INCOMPLETE_SERVICE_UUIDS_128
+        };
+        self.push(ad_type, &[uuids.as_flattened()])
+    }
+
+    pub fn service_data_16(&mut self, uuid: u16, data: &[u8]) -> Result<(), TooLong> {
+        self.push(AdType::SERVICE_DATA_16, &[&uuid.to_le_bytes(), data])
+    }
+
+    /// Appends manufacturer data of the company with the `company`
+    /// identifier.
+    pub fn manufacturer_data(&mut self, company: u16, data: &[u8]) -> Result<(), TooLong> {
+        self.push(
+            AdType::MANUFACTURER_SPECIFIC_DATA,
+            &[&company.to_le_bytes(), data],
+        )
+    }
+
+    /// Appends the transmit power, in dBm.
+    pub fn tx_power_level(&mut self, dbm: i8) -> Result<(), TooLong> {
+        self.push(AdType::TX_POWER_LEVEL, &[&dbm.to_le_bytes()])
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl<const N: usize> Default for AdvData<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An AD structure, borrowed from the data it was parsed from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AdStructure<'a> {
+    pub ad_type: AdType,
+    pub value: &'a [u8],
+}
+
+impl<'a> AdStructure<'a> {
+    pub fn flags(&self) -> Option<Flags> {
+        match (self.ad_type, self.value) {
+            (AdType::FLAGS, [flags, ..]) => Some(Flags(*flags)),
+            _ => None,
+        }
+    }
+
+    /// The complete or shortened local name, if valid UTF-8.
+    pub fn local_name(&self) -> Option<&'a str> {
+        match self.ad_type {
+            AdType::COMPLETE_LOCAL_NAME | AdType::SHORTENED_LOCAL_NAME => {
+                core::str::from_utf8(self.value).ok()
+            }
+            _ => None,
+        }
+    }
+
+    /// The complete or incomplete list of 16-bit service UUIDs.
+    pub fn service_uuids_16(&self) -> Option<impl Iterator<Item = u16> + 'a> {
+        match self.ad_type {
+            AdType::COMPLETE_SERVICE_UUIDS_16 | AdType::INCOMPLETE_SERVICE_UUIDS_16 => Some(
+                self.value
+                    .chunks_exact(2)
+                    .map(|uuid| u16::from_le_bytes([uuid[0], uuid[1]])),
+            ),
+            _ => None,
+        }
+    }
+
+    /// The complete or incomplete list of 128-bit service UUIDs,
+    /// little-endian.
+    pub fn service_uuids_128(&self) -> Option<impl Iterator<Item = [u8; 16]> + 'a> {
+        match self.ad_type {
+            AdType::COMPLETE_SERVICE_UUIDS_128 | AdType::INCOMPLETE_SERVICE_UUIDS_128 => Some(
+                self.value
+                    .chunks_exact(16)
+                    .map(|uuid| uuid.try_into().unwrap()),
+            ),
+            _ => None,
+        }
+    }
+
+    /// Service UUID and data.
+    pub fn service_data_16(&self) -> Option<(u16, &'a [u8])> {
+        match (self.ad_type, self.value) {
+            (AdType::SERVICE_DATA_16, [lo, hi, data @ ..]) => {
+                Some((u16::from_le_bytes([*lo, *hi]), data))
+            }
+            _ => None,
+        }
+    }
+
+    /// Company identifier and data.
+    pub fn manufacturer_data(&self) -> Option<(u16, &'a [u8])> {
+        match (self.ad_type, self.value) {
+            (AdType::MANUFACTURER_SPECIFIC_DATA, [lo, hi, data @ ..]) => {
+                Some((u16::from_le_bytes([*lo, *hi]), data))
+            }
+            _ => None,
+        }
+    }
+
+    /// Transmit power, in dBm.
+    pub fn tx_power_level(&self) -> Option<i8> {
+        match (self.ad_type, self.value) {
+            (AdType::TX_POWER_LEVEL, [dbm]) => Some(*dbm as i8),
+            _ => None,
+        }
+    }
+}
+
+/// Iterator over the AD structures of advertising data; see [`parse`].
+#[derive(Debug, Clone)]
+pub struct AdStructures<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Iterator for AdStructures<'a> {
+    type Item = Result<AdStructure<'a>, Malformed>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&len, rest) = self.bytes.split_first()?;
+        // A zero length ends the significant part; zeros pad the rest.
+        if len == 0 {
+            self.bytes = &[];
+            return None;
+        }
+        let Some((structure, rest)) = rest.split_at_checked(len as usize) else {
+            self.bytes = &[];
+            return Some(Err(Malformed));
+        };
+        self.bytes = rest;
+        Some(Ok(AdStructure {
+            ad_type: AdType(structure[0]),
+            value: &structure[1..],
+        }))
+    }
+}
+
+/// Walks the AD structures of `data`. A structure running past the end of
+/// the data is reported as [`Malformed`] and ends the walk.
+pub fn parse(data: &[u8]) -> AdStructures<'_> {
+    AdStructures { bytes: data }
+}
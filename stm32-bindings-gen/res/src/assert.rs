@@ -0,0 +1,102 @@
+//! What happens when the link layer's assertions fail.
+//!
+//! With the `platform_assert` feature, the crate exports
+//! `LINKLAYER_PLAT_Assert` and handles a failed assertion according to the
+//! [`AssertPolicy`] set with [`set_assert_policy`], panicking by default.
+//! Production builds can instead record the failure and let the link layer
+//! carry on, or route it into their own fault reporting:
+//!
+//! ```ignore
+//! fn report(failure: &AssertFailure) {
+//!     FAULT_LOG.record(failure.location);
+//! }
+//!
+//! stm32_bindings::assert::set_assert_policy(AssertPolicy::Hook(report));
+//! ```
+//!
+//! [`platform_assert`] applies the same policy to assertions made from Rust.
+
+use core::cell::UnsafeCell;
+use core::panic::Location;
+
+/// Handling of a failed link-layer assertion.
+#[derive(Debug, Clone, Copy)]
+pub enum AssertPolicy {
+    /// Panics, like the vendor's reference implementation halts.
+    Panic,
+    /// Logs the failure with the `log` or `defmt` feature and returns to
+    /// the link layer.
+    LogAndContinue,
+    /// Calls the hook, then returns to the link layer. The hook may panic or
+    /// reset the device itself.
+    Hook(fn(&AssertFailure)),
+}
+
+/// A failed assertion, as passed to [`AssertPolicy::Hook`].
+#[derive(Debug, Clone, Copy)]
+pub struct AssertFailure {
+    /// Where [`platform_assert`] was called, or `None` for assertions of the
+    /// vendor libraries, whose location is not known.
+    pub location: Option<&'static Location<'static>>,
+}
+
+struct Policy(UnsafeCell<AssertPolicy>);
+
+// Only accessed inside critical sections.
+unsafe impl Sync for Policy {}
+
+static POLICY: Policy = Policy(UnsafeCell::new(AssertPolicy::Panic));
+
+/// Sets how failed assertions are handled from now on.
+pub fn set_assert_policy(policy: AssertPolicy) {
+    cortex_m::interrupt::free(|_| unsafe { *POLICY.0.get() = policy });
+}
+
+/// Returns the policy in effect.
+pub fn assert_policy() -> AssertPolicy {
+    cortex_m::interrupt::free(|_| unsafe { *POLICY.0.get() })
+}
+
+/// Asserts `condition` under the current [`AssertPolicy`], reporting the
+/// caller's location when it does not hold.
+#[track_caller]
+pub fn platform_assert(condition: bool) {
+    if !condition {
+        fail(AssertFailure {
+            location: Some(Location::caller()),
+        });
+    }
+}
+
+fn fail(failure: AssertFailure) {
+    match assert_policy() {
+        AssertPolicy::Panic => match failure.location {
+            Some(location) => panic!("link-layer assertion failed at {location}"),
+            None => panic!("link-layer assertion failed"),
+        },
+        AssertPolicy::LogAndContinue => {
+            #[cfg(feature = "log")]
+            match failure.location {
+                Some(location) => log::error!("link-layer assertion failed at {}", location),
+                None => log::error!("link-layer assertion failed"),
+            }
+            #[cfg(feature = "defmt")]
+            match failure.location {
+                Some(location) => defmt::error!(
+                    "link-layer assertion failed at {=str}:{=u32}",
+                    location.file(),
+                    location.line()
+                ),
+                None => defmt::error!("link-layer assertion failed"),
+            }
+        }
+        AssertPolicy::Hook(hook) => hook(&failure),
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn LINKLAYER_PLAT_Assert(condition: u8) {
+    if condition == 0 {
+        fail(AssertFailure { location: None });
+    }
+}
@@ -0,0 +1,213 @@
+//! Bitfield storage of the bindings generated with `--audit`.
+//!
+//! Same interface as the one bindgen emits, without its debug assertions
+//! and indexing: bits outside the storage read as zero and are not written,
+//! so the accessors of the generated types cannot panic.
+
+#![deny(
+    clippy::panic,
+    clippy::unwrap_used,
+    clippy::expect_used,
+    clippy::indexing_slicing,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_possible_wrap
+)]
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct __BindgenBitfieldUnit<Storage> {
+    storage: Storage,
+}
+
+impl<Storage> __BindgenBitfieldUnit<Storage> {
+    #[inline]
+    pub const fn new(storage: Storage) -> Self {
+        Self { storage }
+    }
+}
+
+impl<Storage> __BindgenBitfieldUnit<Storage>
+where
+    Storage: AsRef<[u8]> + AsMut<[u8]>,
+{
+    #[inline]
+    fn mask(index: usize) -> u8 {
+        let bit_index = if cfg!(target_endian = "big") {
+            7 - (index % 8)
+        } else {
+            index % 8
+        };
+        1 << bit_index
+    }
+
+    #[inline]
+    fn change_bit(byte: u8, index: usize, val: bool) -> u8 {
+        if val {
+            byte | Self::mask(index)
+        } else {
+            byte & !Self::mask(index)
+        }
+    }
+
+    /// Width of a field, at most 64 bits.
+    #[inline]
+    fn width(bit_width: u8) -> usize {
+        usize::from(bit_width.min(64))
+    }
+
+    /// Position in the field's value of its bit `i`.
+    #[inline]
+    fn value_bit(i: usize, width: usize) -> usize {
+        if cfg!(target_endian = "big") {
+            width - 1 - i
+        } else {
+            i
+        }
+    }
+
+    #[inline]
+    pub fn get_bit(&self, index: usize) -> bool {
+        self.storage
+            .as_ref()
+            .get(index / 8)
+            .is_some_and(|byte| byte & Self::mask(index) != 0)
+    }
+
+    /// # Safety
+    ///
+    /// `this` must be valid for reads.
+    #[inline]
+    pub unsafe fn raw_get_bit(this: *const Self, index: usize) -> bool {
+        let byte_index = index / 8;
+        if byte_index >= core::mem::size_of::<Storage>() {
+            return false;
+        }
+        let byte = unsafe {
+            *core::ptr::addr_of!((*this).storage)
+                .cast::<u8>()
+                .add(byte_index)
+        };
+        byte & Self::mask(index) != 0
+    }
+
+    #[inline]
+    pub fn set_bit(&mut self, index: usize, val: bool) {
+        if let Some(byte) = self.storage.as_mut().get_mut(index / 8) {
+            *byte = Self::change_bit(*byte, index, val);
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `this` must be valid for reads and writes.
+    #[inline]
+    pub unsafe fn raw_set_bit(this: *mut Self, index: usize, val: bool) {
+        let byte_index = index / 8;
+        if byte_index >= core::mem::size_of::<Storage>() {
+            return;
+        }
+        unsafe {
+            let byte = core::ptr::addr_of_mut!((*this).storage)
+                .cast::<u8>()
+                .add(byte_index);
+            *byte = Self::change_bit(*byte, index, val);
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, bit_offset: usize, bit_width: u8) -> u64 {
+        let width = Self::width(bit_width);
+        let mut val = 0;
+        for i in 0..width {
+            if self.get_bit(bit_offset.saturating_add(i)) {
+                val |= 1 << Self::value_bit(i, width);
+            }
+        }
+        val
+    }
+
+    /// # Safety
+    ///
+    /// `this` must be valid for reads.
+    #[inline]
+    pub unsafe fn raw_get(this: *const Self, bit_offset: usize, bit_width: u8) -> u64 {
+        let width = Self::width(bit_width);
+        let mut val = 0;
+        for i in 0..width {
+            if unsafe { Self::raw_get_bit(this, bit_offset.saturating_add(i)) } {
+                val |= 1 << Self::value_bit(i, width);
+            }
+        }
+        val
+    }
+
+    #[inline]
+    pub fn set(&mut self, bit_offset: usize, bit_width: u8, val: u64) {
+        let width = Self::width(bit_width);
+        for i in 0..width {
+            let bit = val & (1 << i) != 0;
+            self.set_bit(bit_offset.saturating_add(Self::value_bit(i, width)), bit);
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `this` must be valid for reads and writes.
+    #[inline]
+    pub unsafe fn raw_set(this: *mut Self, bit_offset: usize, bit_width: u8, val: u64) {
+        let width = Self::width(bit_width);
+        for i in 0..width {
+            let bit = val & (1 << i) != 0;
+            let index = bit_offset.saturating_add(Self::value_bit(i, width));
+            unsafe { Self::raw_set_bit(this, index, bit) };
+        }
+    }
+}
+
+/// Unsigned integers bindgen reads bitfield values as, before transmuting
+/// them to the field type.
+pub trait FromLowBits {
+    /// The low bits of `val`, as many as `Self` holds. A field never has
+    /// more bits than its type, so nothing is lost, and a signed field of
+    /// full width keeps its sign bit.
+    fn from_low_bits(val: u64) -> Self;
+}
+
+impl FromLowBits for u8 {
+    #[inline]
+    fn from_low_bits(val: u64) -> Self {
+        let [b0, ..] = val.to_le_bytes();
+        b0
+    }
+}
+
+impl FromLowBits for u16 {
+    #[inline]
+    fn from_low_bits(val: u64) -> Self {
+        let [b0, b1, ..] = val.to_le_bytes();
+        u16::from_le_bytes([b0, b1])
+    }
+}
+
+impl FromLowBits for u32 {
+    #[inline]
+    fn from_low_bits(val: u64) -> Self {
+        let [b0, b1, b2, b3, ..] = val.to_le_bytes();
+        u32::from_le_bytes([b0, b1, b2, b3])
+    }
+}
+
+impl FromLowBits for u64 {
+    #[inline]
+    fn from_low_bits(val: u64) -> Self {
+        val
+    }
+}
+
+impl FromLowBits for u128 {
+    #[inline]
+    fn from_low_bits(val: u64) -> Self {
+        u128::from(val)
+    }
+}
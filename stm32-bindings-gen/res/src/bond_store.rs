@@ -0,0 +1,593 @@
+//! Persistent store for BLE bonding data on any `embedded-storage` flash.
+//!
+//! Records are appended to a log spread over the erase pages of a flash
+//! range. Each carries a CRC-32, so records torn by a reset or corrupted
+//! later are skipped and the previous version is read instead. Full pages are
+//! reclaimed oldest first, after their live records were copied forward, so
+//! erases are spread over the whole range. One page is kept erased for that
+//! copy, so the range needs at least two pages. The copy is marked complete
+//! before the old page is retired and erased, so a reset during a reclaim
+//! loses nothing: an unfinished copy is dropped and redone, an unfinished
+//! erase is finished.
+
+use core::ops::Range;
+
+use embedded_storage::nor_flash::NorFlash;
+
+/// Identifies a record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Key(u16);
+
+impl Key {
+    /// Security database blob of the BLE stack.
+    pub const SECURITY_DB: Key = Key(0);
+    /// Local identity address and IRK.
+    pub const IDENTITY: Key = Key(1);
+
+    /// IRK and LTK records of bonded peer `index`.
+    pub const fn peer(index: u8) -> Key {
+        Key(0x100 + index as u16)
+    }
+
+    /// Application-defined key. Keys below `0x1000` are reserved for the
+    /// constructors above; returns `None` for those and for `0xFFFF`.
+    pub const fn new(raw: u16) -> Option<Key> {
+        if raw >= 0x1000 && raw != ERASED_KEY {
+            Some(Key(raw))
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    Flash(E),
+    /// The flash range or geometry is not supported; see [`BondStore::mount`].
+    Unsupported,
+    /// The live records leave no room for the new one.
+    Full,
+    /// The record does not fit in a page.
+    TooLarge,
+    /// The buffer passed to [`BondStore::read`] is shorter than the record.
+    BufferTooSmall,
+}
+
+/// "BOND", little-endian.
+const PAGE_MAGIC: u32 = 0x444E_4F42;
+const ERASED_KEY: u16 = 0xFFFF;
+/// Size of the page and record headers before padding to `WRITE_SIZE`.
+const HEADER_LEN: u32 = 8;
+/// Copy buffer size, and the largest supported `WRITE_SIZE`.
+const CHUNK: usize = 32;
+/// Slots of the marks following the page header, in header lengths.
+const RETIRED: u32 = 1;
+const COPIED: u32 = 2;
+
+/// Record header as found in flash.
+#[derive(Clone, Copy)]
+struct Record {
+    key: u16,
+    len: u16,
+    crc: u32,
+    /// Address of the data.
+    addr: u32,
+}
+
+enum Source<'a> {
+    Ram(&'a [u8]),
+    Flash(u32),
+}
+
+struct Crc32(u32);
+
+impl Crc32 {
+    const fn new() -> Self {
+        Self(!0)
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u32;
+            for _ in 0..8 {
+                let mask = (self.0 & 1).wrapping_neg();
+                self.0 = (self.0 >> 1) ^ (0xEDB8_8320 & mask);
+            }
+        }
+    }
+
+    fn finish(&self) -> u32 {
+        !self.0
+    }
+}
+
+fn record_crc(key: u16, data: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(&key.to_le_bytes());
+    crc.update(&(data.len() as u16).to_le_bytes());
+    crc.update(data);
+    crc.finish()
+}
+
+/// Bonding record store on the erase pages of a flash range.
+pub struct BondStore<F> {
+    flash: F,
+    start: u32,
+    pages: u32,
+    /// Page records are appended to, and its sequence number.
+    active: u32,
+    seq: u32,
+    /// Offset of the first free byte in the active page.
+    free: u32,
+}
+
+impl<F: NorFlash> BondStore<F> {
+    const PAGE_SIZE: u32 = F::ERASE_SIZE as u32;
+
+    fn align(len: u32) -> u32 {
+        len.next_multiple_of(F::WRITE_SIZE as u32)
+    }
+
+    fn header_len() -> u32 {
+        Self::align(HEADER_LEN)
+    }
+
+    /// Offset of the first record in a page, after the page header and the
+    /// retired and copied marks.
+    fn records_start() -> u32 {
+        3 * Self::header_len()
+    }
+
+    /// Space a record with `len` bytes of data takes in a page.
+    fn record_len(len: u32) -> u32 {
+        Self::header_len() + Self::align(len)
+    }
+
+    /// Opens the store on `range`, formatting it if it holds no store yet
+    /// and finishing a page reclaim interrupted by a reset.
+    ///
+    /// `range`, in offsets of `flash`, must be aligned to `ERASE_SIZE` and
+    /// cover at least two pages.
+    /// The flash must read with byte granularity and have a power-of-two
+    /// `WRITE_SIZE` of at most 32 bytes.
+    pub fn mount(flash: F, range: Range<u32>) -> Result<Self, Error<F::Error>> {
+        let page_size = Self::PAGE_SIZE;
+        let write_size = F::WRITE_SIZE;
+        if F::READ_SIZE != 1
+            || !write_size.is_power_of_two()
+            || write_size > CHUNK
+            || !page_size.is_multiple_of(write_size as u32)
+            || page_size < 4 * Self::header_len()
+            || !range.start.is_multiple_of(page_size)
+            || range.end < range.start
+            || !(range.end - range.start).is_multiple_of(page_size)
+            || (range.end - range.start) / page_size < 2
+        {
+            return Err(Error::Unsupported);
+        }
+
+        let mut store = Self {
+            flash,
+            start: range.start,
+            pages: (range.end - range.start) / page_size,
+            active: 0,
+            seq: 0,
+            free: 0,
+        };
+
+        for page in 0..store.pages {
+            let stale = match store.page_seq(page)? {
+                Some(_) => store.is_marked(page, RETIRED)?,
+                None => !store.is_blank(page)?,
+            };
+            if stale {
+                store.erase(page)?;
+            }
+        }
+
+        // Without a spare page, a reclaim into the newest page was
+        // interrupted. Until the copy is marked complete, the oldest page is
+        // untouched and still holds every record. Once it is, the erase of
+        // the oldest page may have been cut short after clearing its retired
+        // mark, and the newest page holds the only intact copy.
+        if store.blank_pages()? == 0
+            && let Some((newest, _)) = store.newest()?
+            && let Some((oldest, _)) = store.next_oldest(None)?
+        {
+            if store.is_marked(newest, COPIED)? {
+                store.erase(oldest)?;
+            } else {
+                store.erase(newest)?;
+            }
+        }
+
+        match store.newest()? {
+            Some((page, seq)) => {
+                store.active = page;
+                store.seq = seq;
+                store.free = store.records_end(page)?;
+            }
+            None => store.open(0, 1)?,
+        }
+        Ok(store)
+    }
+
+    pub fn into_inner(self) -> F {
+        self.flash
+    }
+
+    /// Copies the latest record of `key` into `buf` and returns its length,
+    /// or `None` if there is no such record.
+    pub fn read(&mut self, key: Key, buf: &mut [u8]) -> Result<Option<usize>, Error<F::Error>> {
+        let Some(record) = self.find(key.0)?.filter(|record| record.len > 0) else {
+            return Ok(None);
+        };
+        let len = record.len as usize;
+        let buf = buf.get_mut(..len).ok_or(Error::BufferTooSmall)?;
+        self.read_raw(record.addr, buf)?;
+        Ok(Some(len))
+    }
+
+    /// Stores `data` as the latest record of `key`. Writing the data already
+    /// stored does not touch the flash, and empty data removes the record.
+    pub fn write(&mut self, key: Key, data: &[u8]) -> Result<(), Error<F::Error>> {
+        let max = Self::PAGE_SIZE - Self::records_start() - Self::header_len();
+        if data.len() > max.min(u16::MAX as u32) as usize {
+            return Err(Error::TooLarge);
+        }
+
+        let crc = record_crc(key.0, data);
+        let current = self.find(key.0)?;
+        let unchanged = match current {
+            Some(record) if record.len as usize == data.len() && record.crc == crc => {
+                self.same_data(record.addr, data)?
+            }
+            None => data.is_empty(),
+            _ => false,
+        };
+        if unchanged {
+            return Ok(());
+        }
+
+        // A removal may drop the record while reclaiming.
+        self.make_room(data.len() as u32, data.is_empty().then_some(key.0))?;
+        self.append(key.0, data.len() as u16, crc, Source::Ram(data))
+    }
+
+    /// Removes the record of `key`.
+    pub fn remove(&mut self, key: Key) -> Result<(), Error<F::Error>> {
+        self.write(key, &[])
+    }
+
+    fn page_addr(&self, page: u32) -> u32 {
+        self.start + page * Self::PAGE_SIZE
+    }
+
+    fn read_raw(&mut self, addr: u32, buf: &mut [u8]) -> Result<(), Error<F::Error>> {
+        self.flash.read(addr, buf).map_err(Error::Flash)
+    }
+
+    fn write_raw(&mut self, addr: u32, buf: &[u8]) -> Result<(), Error<F::Error>> {
+        self.flash.write(addr, buf).map_err(Error::Flash)
+    }
+
+    fn erase(&mut self, page: u32) -> Result<(), Error<F::Error>> {
+        let addr = self.page_addr(page);
+        self.flash
+            .erase(addr, addr + Self::PAGE_SIZE)
+            .map_err(Error::Flash)
+    }
+
+    /// Sequence number of `page`, or `None` if it has no valid page header.
+    fn page_seq(&mut self, page: u32) -> Result<Option<u32>, Error<F::Error>> {
+        let mut header = [0; HEADER_LEN as usize];
+        self.read_raw(self.page_addr(page), &mut header)?;
+        let magic = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        let seq = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        Ok((magic == PAGE_MAGIC && seq != !0).then_some(seq))
+    }
+
+    fn is_erased(&mut self, addr: u32, len: u32) -> Result<bool, Error<F::Error>> {
+        let mut buf = [0; CHUNK];
+        for offset in (0..len).step_by(CHUNK) {
+            let chunk = CHUNK.min((len - offset) as usize);
+            self.read_raw(addr + offset, &mut buf[..chunk])?;
+            if buf[..chunk].iter().any(|&byte| byte != 0xFF) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn is_marked(&mut self, page: u32, slot: u32) -> Result<bool, Error<F::Error>> {
+        let addr = self.page_addr(page) + slot * Self::header_len();
+        Ok(!self.is_erased(addr, HEADER_LEN)?)
+    }
+
+    fn mark(&mut self, page: u32, slot: u32) -> Result<(), Error<F::Error>> {
+        let zeros = [0; CHUNK];
+        let addr = self.page_addr(page) + slot * Self::header_len();
+        self.write_raw(addr, &zeros[..Self::header_len() as usize])
+    }
+
+    fn is_blank(&mut self, page: u32) -> Result<bool, Error<F::Error>> {
+        self.is_erased(self.page_addr(page), Self::PAGE_SIZE)
+    }
+
+    fn blank_pages(&mut self) -> Result<u32, Error<F::Error>> {
+        let mut count = 0;
+        for page in 0..self.pages {
+            if self.page_seq(page)?.is_none() {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Page with the smallest sequence number above `after`.
+    fn next_oldest(&mut self, after: Option<u32>) -> Result<Option<(u32, u32)>, Error<F::Error>> {
+        let mut oldest: Option<(u32, u32)> = None;
+        for page in 0..self.pages {
+            let Some(seq) = self.page_seq(page)? else {
+                continue;
+            };
+            if after.is_some_and(|after| seq <= after) {
+                continue;
+            }
+            if oldest.is_none_or(|(_, oldest)| seq < oldest) {
+                oldest = Some((page, seq));
+            }
+        }
+        Ok(oldest)
+    }
+
+    fn newest(&mut self) -> Result<Option<(u32, u32)>, Error<F::Error>> {
+        let mut newest: Option<(u32, u32)> = None;
+        for page in 0..self.pages {
+            if let Some(seq) = self.page_seq(page)?
+                && newest.is_none_or(|(_, newest)| seq > newest)
+            {
+                newest = Some((page, seq));
+            }
+        }
+        Ok(newest)
+    }
+
+    /// Reads the record header at `offset` in `page`, returning it with the
+    /// offset of the next one, or `None` past the last record.
+    fn record_at(
+        &mut self,
+        page: u32,
+        offset: u32,
+    ) -> Result<Option<(Record, u32)>, Error<F::Error>> {
+        if offset + Self::header_len() > Self::PAGE_SIZE {
+            return Ok(None);
+        }
+        let addr = self.page_addr(page) + offset;
+        let mut header = [0; HEADER_LEN as usize];
+        self.read_raw(addr, &mut header)?;
+        if header.iter().all(|&byte| byte == 0xFF) {
+            return Ok(None);
+        }
+
+        let record = Record {
+            key: u16::from_le_bytes([header[0], header[1]]),
+            len: u16::from_le_bytes([header[2], header[3]]),
+            crc: u32::from_le_bytes([header[4], header[5], header[6], header[7]]),
+            addr: addr + Self::header_len(),
+        };
+        let next = offset + Self::record_len(record.len as u32);
+        // A corrupted length makes the rest of the page unusable.
+        Ok((next <= Self::PAGE_SIZE).then_some((record, next)))
+    }
+
+    fn records_end(&mut self, page: u32) -> Result<u32, Error<F::Error>> {
+        let mut offset = Self::records_start();
+        while let Some((_, next)) = self.record_at(page, offset)? {
+            offset = next;
+        }
+        // Nothing can be appended after a corrupted length.
+        if offset + Self::header_len() <= Self::PAGE_SIZE
+            && !self.is_erased(self.page_addr(page) + offset, HEADER_LEN)?
+        {
+            return Ok(Self::PAGE_SIZE);
+        }
+        Ok(offset)
+    }
+
+    fn is_valid(&mut self, record: &Record) -> Result<bool, Error<F::Error>> {
+        let mut crc = Crc32::new();
+        crc.update(&record.key.to_le_bytes());
+        crc.update(&record.len.to_le_bytes());
+        let mut buf = [0; CHUNK];
+        let mut done = 0;
+        while done < record.len as usize {
+            let len = CHUNK.min(record.len as usize - done);
+            self.read_raw(record.addr + done as u32, &mut buf[..len])?;
+            crc.update(&buf[..len]);
+            done += len;
+        }
+        Ok(crc.finish() == record.crc)
+    }
+
+    fn same_data(&mut self, addr: u32, data: &[u8]) -> Result<bool, Error<F::Error>> {
+        let mut buf = [0; CHUNK];
+        for (index, chunk) in data.chunks(CHUNK).enumerate() {
+            let stored = &mut buf[..chunk.len()];
+            self.read_raw(addr + (index * CHUNK) as u32, stored)?;
+            if stored != chunk {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Latest valid record of `key`; a removed record has no data.
+    fn find(&mut self, key: u16) -> Result<Option<Record>, Error<F::Error>> {
+        let mut latest = None;
+        let mut after = None;
+        while let Some((page, seq)) = self.next_oldest(after)? {
+            let mut offset = Self::records_start();
+            while let Some((record, next)) = self.record_at(page, offset)? {
+                if record.key == key && self.is_valid(&record)? {
+                    latest = Some(record);
+                }
+                offset = next;
+            }
+            after = Some(seq);
+        }
+        Ok(latest)
+    }
+
+    /// Whether `record` is the latest version of its key, with data.
+    fn is_live(&mut self, record: &Record) -> Result<bool, Error<F::Error>> {
+        if record.len == 0 || !self.is_valid(record)? {
+            return Ok(false);
+        }
+        Ok(self
+            .find(record.key)?
+            .is_some_and(|latest| latest.addr == record.addr))
+    }
+
+    /// Space taken by the live records of every key but `except`.
+    fn live_len(&mut self, except: Option<u16>) -> Result<u32, Error<F::Error>> {
+        let mut total = 0;
+        let mut after = None;
+        while let Some((page, seq)) = self.next_oldest(after)? {
+            let mut offset = Self::records_start();
+            while let Some((record, next)) = self.record_at(page, offset)? {
+                offset = next;
+                if Some(record.key) != except && self.is_live(&record)? {
+                    total += Self::record_len(record.len as u32);
+                }
+            }
+            after = Some(seq);
+        }
+        Ok(total)
+    }
+
+    fn has_room(&self, len: u32) -> bool {
+        self.free + Self::record_len(len) <= Self::PAGE_SIZE
+    }
+
+    /// Starts appending to `page`, erasing it first if needed.
+    fn open(&mut self, page: u32, seq: u32) -> Result<(), Error<F::Error>> {
+        if !self.is_blank(page)? {
+            self.erase(page)?;
+        }
+        let mut header = [0xFF; CHUNK];
+        header[..4].copy_from_slice(&PAGE_MAGIC.to_le_bytes());
+        header[4..8].copy_from_slice(&seq.to_le_bytes());
+        self.write_raw(self.page_addr(page), &header[..Self::header_len() as usize])?;
+        self.active = page;
+        self.seq = seq;
+        self.free = Self::records_start();
+        Ok(())
+    }
+
+    /// First page without a page header after the active one.
+    fn next_blank(&mut self) -> Result<Option<u32>, Error<F::Error>> {
+        for step in 1..=self.pages {
+            let page = (self.active + step) % self.pages;
+            if self.page_seq(page)?.is_none() {
+                return Ok(Some(page));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Makes room for a record of `len` bytes, reclaiming pages if needed.
+    /// The records of `drop` are not copied forward.
+    fn make_room(&mut self, len: u32, drop: Option<u16>) -> Result<(), Error<F::Error>> {
+        if self.has_room(len) {
+            return Ok(());
+        }
+        // Reclaims cannot free more than the dead records, so do not wear
+        // the flash out erasing pages when the live ones fill the store.
+        let capacity = (self.pages - 1) * (Self::PAGE_SIZE - Self::records_start());
+        if self.live_len(drop)? + Self::record_len(len) > capacity {
+            return Err(Error::Full);
+        }
+
+        for _ in 0..self.pages {
+            if self.has_room(len) {
+                return Ok(());
+            }
+            if self.blank_pages()? > 1 {
+                let page = self.next_blank()?.ok_or(Error::Full)?;
+                self.open(page, self.seq + 1)?;
+            } else {
+                self.reclaim(drop)?;
+            }
+        }
+        if self.has_room(len) {
+            Ok(())
+        } else {
+            Err(Error::Full)
+        }
+    }
+
+    /// Copies the live records of the oldest page but those of `drop` to the
+    /// spare page, which becomes the active one, marks the copy complete,
+    /// then retires and erases the oldest page.
+    fn reclaim(&mut self, drop: Option<u16>) -> Result<(), Error<F::Error>> {
+        let (oldest, _) = self.next_oldest(None)?.ok_or(Error::Full)?;
+        let spare = self.next_blank()?.ok_or(Error::Full)?;
+        self.open(spare, self.seq + 1)?;
+
+        let mut offset = Self::records_start();
+        while let Some((record, next)) = self.record_at(oldest, offset)? {
+            offset = next;
+            if Some(record.key) == drop || !self.is_live(&record)? {
+                continue;
+            }
+            if !self.has_room(record.len as u32) {
+                return Err(Error::Full);
+            }
+            self.append(
+                record.key,
+                record.len,
+                record.crc,
+                Source::Flash(record.addr),
+            )?;
+        }
+
+        self.mark(self.active, COPIED)?;
+        self.mark(oldest, RETIRED)?;
+        self.erase(oldest)
+    }
+
+    fn append(
+        &mut self,
+        key: u16,
+        len: u16,
+        crc: u32,
+        source: Source<'_>,
+    ) -> Result<(), Error<F::Error>> {
+        let addr = self.page_addr(self.active) + self.free;
+        let mut buf = [0xFF; CHUNK];
+        buf[..2].copy_from_slice(&key.to_le_bytes());
+        buf[2..4].copy_from_slice(&len.to_le_bytes());
+        buf[4..8].copy_from_slice(&crc.to_le_bytes());
+        self.write_raw(addr, &buf[..Self::header_len() as usize])?;
+
+        let data = addr + Self::header_len();
+        let mut done = 0;
+        while done < len as usize {
+            let chunk = CHUNK.min(len as usize - done);
+            buf.fill(0xFF);
+            match source {
+                Source::Ram(bytes) => buf[..chunk].copy_from_slice(&bytes[done..done + chunk]),
+                Source::Flash(from) => self.read_raw(from + done as u32, &mut buf[..chunk])?,
+            }
+            let padded = Self::align(chunk as u32) as usize;
+            self.write_raw(data + done as u32, &buf[..padded])?;
+            done += chunk;
+        }
+
+        self.free += Self::record_len(len as u32);
+        Ok(())
+    }
+}
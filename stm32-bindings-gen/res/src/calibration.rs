@@ -0,0 +1,198 @@
+//! Radio calibration notifications, and the policy deciding when the link
+//! layer sees a new die temperature.
+//!
+//! The link layer calibrates the RCO sleep clock and the radio PHY on its
+//! own, and reports the start and end of each calibration through the
+//! `LINKLAYER_PLAT_RCOStartClbr`, `LINKLAYER_PLAT_RCOStopClbr`,
+//! `LINKLAYER_PLAT_PhyStartClbr` and `LINKLAYER_PLAT_PhyStopClbr` hooks. Here
+//! those hooks call the [`EventHandler`] given to
+//! [`Calibration::set_event_handler`]. The device must not enter Stop mode
+//! while the RCO is calibrated; with `stm32_lpm`, the handler can set a
+//! `power::PowerManager` limit on [`CalibrationEvent::RcoStarted`] and lift
+//! it on [`CalibrationEvent::RcoDone`].
+//!
+//! The PHY is recalibrated when the die temperature drifts. Measurements
+//! handed to [`Calibration::report_temperature`] reach the link layer as the
+//! [`CalibrationPolicy`] decides.
+
+use core::cell::UnsafeCell;
+
+use crate::bindings::wba_link_layer::*;
+use crate::os_timer::PeriodicTimer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// [`Calibration::init`] was already called.
+    AlreadyInitialized,
+    /// The sleep timer of [`CalibrationPolicy::Periodic`] could not be
+    /// created or started.
+    Timer,
+}
+
+/// Start or end of a calibration run by the link layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CalibrationEvent {
+    RcoStarted,
+    RcoDone,
+    PhyStarted,
+    PhyDone,
+}
+
+/// Told about calibration events. Called from the link-layer context.
+pub type EventHandler = fn(CalibrationEvent);
+
+/// When measured temperatures are handed to the link layer.
+#[derive(Debug, Clone, Copy)]
+pub enum CalibrationPolicy {
+    /// Every reported temperature; the application decides when to measure.
+    Manual,
+    /// Every reported temperature, with `measure` called every `period_ms`
+    /// milliseconds from the link-layer sleep timer to start a measurement.
+    Periodic { period_ms: u32, measure: fn() },
+    /// A reported temperature differing by at least `celsius` degrees from
+    /// the last one handed over.
+    TemperatureDelta { celsius: u16 },
+}
+
+struct State {
+    initialized: bool,
+    policy: CalibrationPolicy,
+    handler: Option<EventHandler>,
+    rco_calibrating: bool,
+    /// Last temperature handed to the link layer.
+    last: Option<i16>,
+}
+
+struct Global(UnsafeCell<State>);
+
+// Only accessed inside critical sections.
+unsafe impl Sync for Global {}
+
+static STATE: Global = Global(UnsafeCell::new(State {
+    initialized: false,
+    policy: CalibrationPolicy::Manual,
+    handler: None,
+    rco_calibrating: false,
+    last: None,
+}));
+
+fn state<R>(f: impl FnOnce(&mut State) -> R) -> R {
+    cortex_m::interrupt::free(|_| f(unsafe { &mut *STATE.0.get() }))
+}
+
+/// Whether the link layer is calibrating the RCO.
+pub fn rco_calibrating() -> bool {
+    state(|state| state.rco_calibrating)
+}
+
+fn notify(event: CalibrationEvent) {
+    let handler = state(|state| {
+        match event {
+            CalibrationEvent::RcoStarted => state.rco_calibrating = true,
+            CalibrationEvent::RcoDone => state.rco_calibrating = false,
+            _ => {}
+        }
+        state.handler
+    });
+    if let Some(handler) = handler {
+        handler(event);
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn LINKLAYER_PLAT_RCOStartClbr() {
+    notify(CalibrationEvent::RcoStarted);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn LINKLAYER_PLAT_RCOStopClbr() {
+    notify(CalibrationEvent::RcoDone);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn LINKLAYER_PLAT_PhyStartClbr() {
+    notify(CalibrationEvent::PhyStarted);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn LINKLAYER_PLAT_PhyStopClbr() {
+    notify(CalibrationEvent::PhyDone);
+}
+
+unsafe extern "C" fn refresh(_arg: *const core::ffi::c_void) {
+    if let CalibrationPolicy::Periodic { measure, .. } = state(|state| state.policy) {
+        measure();
+    }
+}
+
+static REFRESH: PeriodicTimer = PeriodicTimer::new(refresh);
+
+/// Token proving calibration management is set up.
+#[derive(Debug, Clone, Copy)]
+pub struct Calibration {
+    _private: (),
+}
+
+impl Calibration {
+    /// Sets up calibration management following `policy`.
+    ///
+    /// Call once the link layer is initialized if `policy` is
+    /// [`CalibrationPolicy::Periodic`], as it runs on its sleep timer.
+    pub fn init(policy: CalibrationPolicy) -> Result<Self, Error> {
+        state(|state| {
+            if state.initialized {
+                return Err(Error::AlreadyInitialized);
+            }
+            state.initialized = true;
+            Ok(())
+        })?;
+        let calibration = Self { _private: () };
+        calibration.set_policy(policy)?;
+        Ok(calibration)
+    }
+
+    /// Has calibration events reported to `handler`.
+    pub fn set_event_handler(&self, handler: EventHandler) {
+        state(|state| state.handler = Some(handler));
+    }
+
+    /// Follows `policy` from now on.
+    pub fn set_policy(&self, policy: CalibrationPolicy) -> Result<(), Error> {
+        let period_ms = match policy {
+            CalibrationPolicy::Periodic { period_ms, .. } => Some(period_ms),
+            _ => None,
+        };
+        state(|state| state.policy = policy);
+        REFRESH.set_period(period_ms).map_err(|_| Error::Timer)
+    }
+
+    /// Hands a measured die temperature, in degrees Celsius, to the link
+    /// layer if the policy says so. Callable from thread or interrupt
+    /// context.
+    pub fn report_temperature(&self, celsius: i16) {
+        let forward = state(|state| {
+            let forward = match state.policy {
+                CalibrationPolicy::TemperatureDelta { celsius: delta } => state
+                    .last
+                    .is_none_or(|last| celsius.abs_diff(last) >= delta),
+                _ => true,
+            };
+            if forward {
+                state.last = Some(celsius);
+            }
+            forward
+        });
+        if forward {
+            self.calibrate(celsius);
+        }
+    }
+
+    /// Hands a measured die temperature to the link layer whatever the
+    /// policy, which recalibrates the PHY if it changed enough.
+    pub fn calibrate(&self, celsius: i16) {
+        state(|state| state.last = Some(celsius));
+        unsafe { cmn_set_temperature_value(celsius as i32 as u32) };
+    }
+}
@@ -0,0 +1,214 @@
+//! Packet capture in the pcapng format, for viewing HCI traffic and
+//! 802.15.4 frames in Wireshark.
+//!
+//! [`PcapngWriter`] streams blocks to a [`Sink`], e.g. an RTT channel or a
+//! UART: a section header when created, an interface description per
+//! [`PcapngWriter::add_interface`], then an enhanced packet block per
+//! packet. The application calls [`PcapngWriter::write_hci`] from its HCI
+//! transport and [`PcapngWriter::write_802154`] from its MAC data callbacks
+//! for the packets to capture. Blocks are written little-endian, with
+//! microsecond timestamps.
+
+/// Destination of the capture.
+pub trait Sink {
+    type Error;
+
+    /// Writes all of `bytes`.
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error>;
+}
+
+/// Link-layer header type of an interface, from the tcpdump.org list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u16)]
+pub enum LinkType {
+    /// `LINKTYPE_BLUETOOTH_HCI_H4`: HCI packets preceded by their H4 packet
+    /// type.
+    BluetoothHciH4 = 187,
+    /// `LINKTYPE_IEEE802_15_4_TAP`: 802.15.4 frames preceded by a TAP header.
+    Ieee802154Tap = 283,
+}
+
+/// Interface added to a [`PcapngWriter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct InterfaceId(u32);
+
+/// Whether a packet was received or sent by the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Direction {
+    /// Received by the device: events and data from the controller, or
+    /// frames from the air.
+    Inbound,
+    /// Sent by the device: commands and data to the controller, or frames
+    /// to the air.
+    Outbound,
+}
+
+/// H4 packet type of an HCI packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum HciPacketType {
+    Command = 0x01,
+    Acl = 0x02,
+    Sco = 0x03,
+    Event = 0x04,
+    Iso = 0x05,
+}
+
+/// Frame check sequence ending an 802.15.4 frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum Fcs {
+    None = 0,
+    Crc16 = 1,
+    Crc32 = 2,
+}
+
+const SECTION_HEADER: u32 = 0x0A0D_0D0A;
+const INTERFACE_DESCRIPTION: u32 = 0x0000_0001;
+const ENHANCED_PACKET: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+
+const OPT_ENDOFOPT: u16 = 0;
+const OPT_EPB_FLAGS: u16 = 2;
+
+/// Options of an enhanced packet block: the flags with the direction, and
+/// the end of options.
+const EPB_OPTIONS_LEN: usize = 8 + 4;
+
+const TAP_FCS_TYPE: u16 = 0;
+const TAP_CHANNEL_ASSIGNMENT: u16 = 3;
+
+/// Streams pcapng blocks to a [`Sink`].
+pub struct PcapngWriter<S: Sink> {
+    sink: S,
+    interfaces: u32,
+}
+
+impl<S: Sink> PcapngWriter<S> {
+    /// Starts a capture section on `sink`.
+    pub fn new(mut sink: S) -> Result<Self, S::Error> {
+        let len = 28u32.to_le_bytes();
+        sink.write(&SECTION_HEADER.to_le_bytes())?;
+        sink.write(&len)?;
+        sink.write(&BYTE_ORDER_MAGIC.to_le_bytes())?;
+        // Version 1.0, and a section length left unspecified.
+        sink.write(&1u16.to_le_bytes())?;
+        sink.write(&0u16.to_le_bytes())?;
+        sink.write(&(-1i64).to_le_bytes())?;
+        sink.write(&len)?;
+        Ok(Self {
+            sink,
+            interfaces: 0,
+        })
+    }
+
+    /// Describes a new interface carrying packets of `link_type`.
+    pub fn add_interface(&mut self, link_type: LinkType) -> Result<InterfaceId, S::Error> {
+        let len = 20u32.to_le_bytes();
+        self.sink.write(&INTERFACE_DESCRIPTION.to_le_bytes())?;
+        self.sink.write(&len)?;
+        self.sink.write(&(link_type as u16).to_le_bytes())?;
+        self.sink.write(&0u16.to_le_bytes())?;
+        // No snapshot length limit.
+        self.sink.write(&0u32.to_le_bytes())?;
+        self.sink.write(&len)?;
+        let id = InterfaceId(self.interfaces);
+        self.interfaces += 1;
+        Ok(id)
+    }
+
+    /// Writes an HCI packet to a [`LinkType::BluetoothHciH4`] interface,
+    /// `timestamp_us` microseconds after an origin of the application's
+    /// choice.
+    pub fn write_hci(
+        &mut self,
+        interface: InterfaceId,
+        timestamp_us: u64,
+        direction: Direction,
+        packet_type: HciPacketType,
+        packet: &[u8],
+    ) -> Result<(), S::Error> {
+        self.write_packet(
+            interface,
+            timestamp_us,
+            direction,
+            &[&[packet_type as u8], packet],
+        )
+    }
+
+    /// Writes an 802.15.4 frame, ending with the `fcs`, to a
+    /// [`LinkType::Ieee802154Tap`] interface, with the channel it was sent
+    /// or received on if known.
+    pub fn write_802154(
+        &mut self,
+        interface: InterfaceId,
+        timestamp_us: u64,
+        direction: Direction,
+        fcs: Fcs,
+        channel: Option<u8>,
+        frame: &[u8],
+    ) -> Result<(), S::Error> {
+        // TLVs of 4 bytes of type and length, and a value padded to 4 bytes.
+        let mut tap = [0; 4 + 8 + 8];
+        let len = if channel.is_some() { 20 } else { 12 };
+        tap[2..4].copy_from_slice(&(len as u16).to_le_bytes());
+        tap[4..6].copy_from_slice(&TAP_FCS_TYPE.to_le_bytes());
+        tap[6..8].copy_from_slice(&1u16.to_le_bytes());
+        tap[8] = fcs as u8;
+        if let Some(channel) = channel {
+            tap[12..14].copy_from_slice(&TAP_CHANNEL_ASSIGNMENT.to_le_bytes());
+            tap[14..16].copy_from_slice(&3u16.to_le_bytes());
+            // Channel number, then channel page 0.
+            tap[16..18].copy_from_slice(&(channel as u16).to_le_bytes());
+        }
+        self.write_packet(interface, timestamp_us, direction, &[&tap[..len], frame])
+    }
+
+    /// Writes an enhanced packet block with the concatenation of `parts` as
+    /// packet data.
+    fn write_packet(
+        &mut self,
+        interface: InterfaceId,
+        timestamp_us: u64,
+        direction: Direction,
+        parts: &[&[u8]],
+    ) -> Result<(), S::Error> {
+        let data_len: usize = parts.iter().map(|part| part.len()).sum();
+        let padding = data_len.next_multiple_of(4) - data_len;
+        let len = ((28 + data_len + padding + EPB_OPTIONS_LEN + 4) as u32).to_le_bytes();
+        let flags: u32 = match direction {
+            Direction::Inbound => 0b01,
+            Direction::Outbound => 0b10,
+        };
+
+        self.sink.write(&ENHANCED_PACKET.to_le_bytes())?;
+        self.sink.write(&len)?;
+        self.sink.write(&interface.0.to_le_bytes())?;
+        self.sink
+            .write(&((timestamp_us >> 32) as u32).to_le_bytes())?;
+        self.sink.write(&(timestamp_us as u32).to_le_bytes())?;
+        // Captured and original lengths: packets are never truncated.
+        self.sink.write(&(data_len as u32).to_le_bytes())?;
+        self.sink.write(&(data_len as u32).to_le_bytes())?;
+        for part in parts {
+            self.sink.write(part)?;
+        }
+        self.sink.write(&[0; 3][..padding])?;
+        self.sink.write(&OPT_EPB_FLAGS.to_le_bytes())?;
+        self.sink.write(&4u16.to_le_bytes())?;
+        self.sink.write(&flags.to_le_bytes())?;
+        self.sink.write(&OPT_ENDOFOPT.to_le_bytes())?;
+        self.sink.write(&0u16.to_le_bytes())?;
+        self.sink.write(&len)
+    }
+
+    /// Ends the capture and returns the sink.
+    pub fn into_inner(self) -> S {
+        self.sink
+    }
+}
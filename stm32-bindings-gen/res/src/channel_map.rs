@@ -0,0 +1,210 @@
+//! BLE data channel maps, and the HCI commands reading and classifying them.
+//!
+//! A [`ChannelMap`] holds one bit per data channel, 0 to 36, as the
+//! controller sends and takes it: 5 bytes, little-endian, with the top 3
+//! bits reserved. [`set_host_channel_classification`] tells the controller
+//! which channels the host found busy, e.g. with
+//! [`ChannelMap::without_wifi_channel`] for a nearby Wi-Fi network, and
+//! [`read_channel_map`] returns the channels a connection uses.
+
+use crate::bindings::wba_ble_stack::*;
+
+/// Number of BLE data channels.
+pub const DATA_CHANNELS: u8 = 37;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// A channel is not a data channel, or reserved bits are set.
+    Invalid,
+    /// Fewer than two channels are used, the minimum of the link layer.
+    TooFewChannels,
+    /// The controller refused the command, with this HCI status code.
+    Rejected(u8),
+}
+
+/// Set of data channels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ChannelMap([u8; 5]);
+
+const ALL_BITS: u64 = (1 << DATA_CHANNELS) - 1;
+
+impl ChannelMap {
+    /// Every data channel.
+    pub const ALL: ChannelMap = ChannelMap::from_bits(ALL_BITS);
+
+    pub const fn empty() -> Self {
+        Self([0; 5])
+    }
+
+    /// Map of the 5 bytes sent by the controller, if no reserved bit is
+    /// set.
+    pub const fn from_bytes(bytes: [u8; 5]) -> Option<Self> {
+        let map = Self(bytes);
+        if map.bits() & !ALL_BITS == 0 {
+            Some(map)
+        } else {
+            None
+        }
+    }
+
+    pub const fn into_bytes(self) -> [u8; 5] {
+        self.0
+    }
+
+    const fn from_bits(bits: u64) -> Self {
+        let bytes = bits.to_le_bytes();
+        Self([bytes[0], bytes[1], bytes[2], bytes[3], bytes[4]])
+    }
+
+    const fn bits(self) -> u64 {
+        let [b0, b1, b2, b3, b4] = self.0;
+        u64::from_le_bytes([b0, b1, b2, b3, b4, 0, 0, 0])
+    }
+
+    pub const fn contains(self, channel: u8) -> bool {
+        channel < DATA_CHANNELS && self.bits() & 1 << channel != 0
+    }
+
+    pub fn insert(&mut self, channel: u8) -> Result<(), Error> {
+        if channel >= DATA_CHANNELS {
+            return Err(Error::Invalid);
+        }
+        self.0[channel as usize / 8] |= 1 << (channel % 8);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, channel: u8) -> Result<(), Error> {
+        if channel >= DATA_CHANNELS {
+            return Err(Error::Invalid);
+        }
+        self.0[channel as usize / 8] &= !(1 << (channel % 8));
+        Ok(())
+    }
+
+    /// Number of channels in the map.
+    pub const fn len(self) -> u8 {
+        self.bits().count_ones() as u8
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.bits() == 0
+    }
+
+    /// Whether a connection may use the map: it has at least two channels.
+    pub const fn is_usable(self) -> bool {
+        self.len() >= 2
+    }
+
+    /// The channels in the map, in increasing order.
+    pub const fn iter(self) -> Channels {
+        Channels { bits: self.bits() }
+    }
+
+    /// Centre frequency of data `channel`, in MHz.
+    pub const fn frequency_mhz(channel: u8) -> Option<u16> {
+        match channel {
+            0..=10 => Some(2404 + 2 * channel as u16),
+            11..=36 => Some(2428 + 2 * (channel as u16 - 11)),
+            _ => None,
+        }
+    }
+
+    /// The map without the channels overlapping the 22 MHz band of 2.4 GHz
+    /// Wi-Fi channel `wifi_channel`, 1 to 13. Returns the map unchanged
+    /// for other channels.
+    pub const fn without_wifi_channel(self, wifi_channel: u8) -> Self {
+        if wifi_channel < 1 || wifi_channel > 13 {
+            return self;
+        }
+        let centre = 2407 + 5 * wifi_channel as u16;
+        let mut bits = self.bits();
+        let mut channel = 0;
+        while channel < DATA_CHANNELS {
+            if let Some(frequency) = Self::frequency_mhz(channel) {
+                // Each BLE channel is 2 MHz wide.
+                if frequency + 1 > centre - 11 && frequency - 1 < centre + 11 {
+                    bits &= !(1 << channel);
+                }
+            }
+            channel += 1;
+        }
+        Self::from_bits(bits)
+    }
+}
+
+impl Default for ChannelMap {
+    /// Every data channel, as the controller starts with.
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl IntoIterator for ChannelMap {
+    type Item = u8;
+    type IntoIter = Channels;
+
+    fn into_iter(self) -> Channels {
+        self.iter()
+    }
+}
+
+impl FromIterator<u8> for ChannelMap {
+    /// Map of the given data channels; others are ignored.
+    fn from_iter<I: IntoIterator<Item = u8>>(channels: I) -> Self {
+        let mut map = Self::empty();
+        for channel in channels {
+            let _ = map.insert(channel);
+        }
+        map
+    }
+}
+
+/// Iterator over the channels of a [`ChannelMap`].
+#[derive(Debug, Clone)]
+pub struct Channels {
+    bits: u64,
+}
+
+impl Iterator for Channels {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        if self.bits == 0 {
+            return None;
+        }
+        let channel = self.bits.trailing_zeros() as u8;
+        self.bits &= self.bits - 1;
+        Some(channel)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.bits.count_ones() as usize;
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for Channels {}
+
+/// Tells the controller the host may use the channels of `map` and finds
+/// the others busy. Connections then stop using the busy channels.
+pub fn set_host_channel_classification(map: ChannelMap) -> Result<(), Error> {
+    if !map.is_usable() {
+        return Err(Error::TooFewChannels);
+    }
+    let bytes = map.into_bytes();
+    match unsafe { hci_le_set_host_channel_classification(bytes.as_ptr()) } {
+        0 => Ok(()),
+        status => Err(Error::Rejected(status)),
+    }
+}
+
+/// Channels the connection with handle `connection_handle` uses.
+pub fn read_channel_map(connection_handle: u16) -> Result<ChannelMap, Error> {
+    let mut bytes = [0; 5];
+    match unsafe { hci_le_read_channel_map(connection_handle, bytes.as_mut_ptr()) } {
+        0 => ChannelMap::from_bytes(bytes).ok_or(Error::Invalid),
+        status => Err(Error::Rejected(status)),
+    }
+}
@@ -0,0 +1,232 @@
+//! Typed masks of the HCI events the controller reports.
+//!
+//! `hci_set_event_mask`, `hci_set_event_mask_page_2` and
+//! `hci_le_set_event_mask` take their mask as 8 raw bytes. [`EventMask`],
+//! [`EventMaskPage2`] and [`LeEventMask`] name the bits as the Core
+//! specification does (Vol 4, Part E, 7.3.1, 7.3.69 and 7.8.1), default to
+//! the masks the controller starts with, and [`set_event_mask`],
+//! [`set_event_mask_page_2`] and [`set_le_event_mask`] take them by value.
+//!
+//! Bits of BR/EDR-only events are left out, as the STM32WBA controller is
+//! LE-only.
+
+use core::ops::{BitAnd, BitOr, BitOrAssign, Not, Sub};
+
+use crate::bindings::wba_ble_stack::*;
+
+/// The controller refused the mask, with this HCI status code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Rejected(pub u8);
+
+macro_rules! event_mask {
+    (
+        $(#[$meta:meta])*
+        $name:ident, default = $default:expr;
+        $($(#[$bit_meta:meta])* $bit:ident = $position:expr;)*
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+        pub struct $name(u64);
+
+        impl $name {
+            $($(#[$bit_meta])* pub const $bit: Self = Self(1 << $position);)*
+
+            /// Every event with a constant of its own.
+            pub const ALL: Self = Self(0 $(| 1 << $position)*);
+
+            /// No events.
+            pub const fn empty() -> Self {
+                Self(0)
+            }
+
+            /// Mask of the raw `bits`, including reserved ones.
+            pub const fn from_bits(bits: u64) -> Self {
+                Self(bits)
+            }
+
+            pub const fn bits(self) -> u64 {
+                self.0
+            }
+
+            pub const fn contains(self, other: Self) -> bool {
+                self.0 & other.0 == other.0
+            }
+
+            /// Mask with the events of `other` reported too.
+            pub const fn union(self, other: Self) -> Self {
+                Self(self.0 | other.0)
+            }
+
+            /// Mask with the events of `other` masked.
+            pub const fn without(self, other: Self) -> Self {
+                Self(self.0 & !other.0)
+            }
+
+            /// The mask as the HCI command takes it, little-endian.
+            pub const fn into_bytes(self) -> [u8; 8] {
+                self.0.to_le_bytes()
+            }
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                Self($default)
+            }
+        }
+
+        impl BitOr for $name {
+            type Output = Self;
+
+            fn bitor(self, rhs: Self) -> Self {
+                self.union(rhs)
+            }
+        }
+
+        impl BitOrAssign for $name {
+            fn bitor_assign(&mut self, rhs: Self) {
+                *self = self.union(rhs);
+            }
+        }
+
+        impl BitAnd for $name {
+            type Output = Self;
+
+            fn bitand(self, rhs: Self) -> Self {
+                Self(self.0 & rhs.0)
+            }
+        }
+
+        impl Sub for $name {
+            type Output = Self;
+
+            fn sub(self, rhs: Self) -> Self {
+                self.without(rhs)
+            }
+        }
+
+        impl Not for $name {
+            type Output = Self;
+
+            fn not(self) -> Self {
+                Self(!self.0)
+            }
+        }
+    };
+}
+
+event_mask! {
+    /// Events of `HCI_Set_Event_Mask`. Defaults to `0x0000_1FFF_FFFF_FFFF`,
+    /// which masks [`EventMask::LE_META`] and so every LE event.
+    EventMask, default = 0x0000_1FFF_FFFF_FFFF;
+    DISCONNECTION_COMPLETE = 4;
+    ENCRYPTION_CHANGE = 7;
+    READ_REMOTE_VERSION_INFORMATION_COMPLETE = 11;
+    HARDWARE_ERROR = 15;
+    DATA_BUFFER_OVERFLOW = 25;
+    ENCRYPTION_KEY_REFRESH_COMPLETE = 47;
+    /// All the events of [`LeEventMask`].
+    LE_META = 61;
+}
+
+event_mask! {
+    /// Events of `HCI_Set_Event_Mask_Page_2`. Defaults to no events.
+    EventMaskPage2, default = 0;
+    AUTHENTICATED_PAYLOAD_TIMEOUT_EXPIRED = 23;
+    /// Encryption change with the key size, replacing
+    /// [`EventMask::ENCRYPTION_CHANGE`] once set.
+    ENCRYPTION_CHANGE_V2 = 25;
+}
+
+event_mask! {
+    /// Events of `HCI_LE_Set_Event_Mask`, reported if [`EventMask::LE_META`]
+    /// is set too. Defaults to `0x1F`, the events of Bluetooth 4.0 up to
+    /// [`LeEventMask::LONG_TERM_KEY_REQUEST`].
+    LeEventMask, default = 0x1F;
+    CONNECTION_COMPLETE = 0;
+    ADVERTISING_REPORT = 1;
+    CONNECTION_UPDATE_COMPLETE = 2;
+    READ_REMOTE_FEATURES_COMPLETE = 3;
+    LONG_TERM_KEY_REQUEST = 4;
+    REMOTE_CONNECTION_PARAMETER_REQUEST = 5;
+    DATA_LENGTH_CHANGE = 6;
+    READ_LOCAL_P256_PUBLIC_KEY_COMPLETE = 7;
+    GENERATE_DHKEY_COMPLETE = 8;
+    ENHANCED_CONNECTION_COMPLETE = 9;
+    DIRECTED_ADVERTISING_REPORT = 10;
+    PHY_UPDATE_COMPLETE = 11;
+    EXTENDED_ADVERTISING_REPORT = 12;
+    PERIODIC_ADVERTISING_SYNC_ESTABLISHED = 13;
+    PERIODIC_ADVERTISING_REPORT = 14;
+    PERIODIC_ADVERTISING_SYNC_LOST = 15;
+    SCAN_TIMEOUT = 16;
+    ADVERTISING_SET_TERMINATED = 17;
+    SCAN_REQUEST_RECEIVED = 18;
+    CHANNEL_SELECTION_ALGORITHM = 19;
+    CONNECTIONLESS_IQ_REPORT = 20;
+    CONNECTION_IQ_REPORT = 21;
+    CTE_REQUEST_FAILED = 22;
+    PERIODIC_ADVERTISING_SYNC_TRANSFER_RECEIVED = 23;
+    CIS_ESTABLISHED = 24;
+    CIS_REQUEST = 25;
+    CREATE_BIG_COMPLETE = 26;
+    TERMINATE_BIG_COMPLETE = 27;
+    BIG_SYNC_ESTABLISHED = 28;
+    BIG_SYNC_LOST = 29;
+    REQUEST_PEER_SCA_COMPLETE = 30;
+    PATH_LOSS_THRESHOLD = 31;
+    TRANSMIT_POWER_REPORTING = 32;
+    BIGINFO_ADVERTISING_REPORT = 33;
+    SUBRATE_CHANGE = 34;
+    PERIODIC_ADVERTISING_SYNC_ESTABLISHED_V2 = 35;
+    PERIODIC_ADVERTISING_REPORT_V2 = 36;
+    PERIODIC_ADVERTISING_SYNC_TRANSFER_RECEIVED_V2 = 37;
+    PERIODIC_ADVERTISING_SUBEVENT_DATA_REQUEST = 38;
+    PERIODIC_ADVERTISING_RESPONSE_REPORT = 39;
+    ENHANCED_CONNECTION_COMPLETE_V2 = 40;
+    CIS_ESTABLISHED_V2 = 41;
+}
+
+impl LeEventMask {
+    /// Connection events of a central or peripheral using legacy
+    /// advertising, with LE Secure Connections pairing.
+    pub const CONNECTIONS: Self = Self::CONNECTION_COMPLETE
+        .union(Self::ADVERTISING_REPORT)
+        .union(Self::CONNECTION_UPDATE_COMPLETE)
+        .union(Self::READ_REMOTE_FEATURES_COMPLETE)
+        .union(Self::LONG_TERM_KEY_REQUEST)
+        .union(Self::REMOTE_CONNECTION_PARAMETER_REQUEST)
+        .union(Self::DATA_LENGTH_CHANGE)
+        .union(Self::READ_LOCAL_P256_PUBLIC_KEY_COMPLETE)
+        .union(Self::GENERATE_DHKEY_COMPLETE)
+        .union(Self::ENHANCED_CONNECTION_COMPLETE)
+        .union(Self::PHY_UPDATE_COMPLETE)
+        .union(Self::CHANNEL_SELECTION_ALGORITHM);
+}
+
+fn check(status: u8) -> Result<(), Rejected> {
+    if status == 0 {
+        Ok(())
+    } else {
+        Err(Rejected(status))
+    }
+}
+
+/// Sets the events the controller reports.
+pub fn set_event_mask(mask: EventMask) -> Result<(), Rejected> {
+    let bytes = mask.into_bytes();
+    check(unsafe { hci_set_event_mask(bytes.as_ptr()) })
+}
+
+/// Sets the events of the second page the controller reports.
+pub fn set_event_mask_page_2(mask: EventMaskPage2) -> Result<(), Rejected> {
+    let bytes = mask.into_bytes();
+    check(unsafe { hci_set_event_mask_page_2(bytes.as_ptr()) })
+}
+
+/// Sets the LE events the controller reports.
+pub fn set_le_event_mask(mask: LeEventMask) -> Result<(), Rejected> {
+    let bytes = mask.into_bytes();
+    check(unsafe { hci_le_set_event_mask(bytes.as_ptr()) })
+}
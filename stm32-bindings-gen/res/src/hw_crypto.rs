@@ -0,0 +1,387 @@
+//! AES, PKA and RNG drivers of the WPAN hardware interface, in Rust.
+//!
+//! The BLE platform layer (`bleplat.c`) and the link layer call the
+//! `HW_AES_*`, `HW_PKA_*` and `HW_RNG_*` functions of ST's `hw_aes.c`,
+//! `hw_pka.c` and `hw_rng.c`. With the `hw_crypto_aes`, `hw_crypto_pka` and
+//! `hw_crypto_rng` features, those functions are exported from here instead
+//! and forward to the [`Aes`], [`Pka`] and [`Rng`] implementation set at
+//! start-up, typically a thin wrapper over a HAL driver.
+//!
+//! Only the functions used outside the ST drivers themselves are provided;
+//! the register-level `HW_PKA_WriteOperand` family is not.
+//!
+//! The drivers must be set before the BLE stack and the link layer start.
+//! The stack takes keys and nonces from these functions, so a call that
+//! cannot be served panics rather than returning made-up results. AES and
+//! RNG calls are short and run with interrupts masked, so the link layer
+//! may also call them from its interrupts. PKA operations are long and run
+//! with interrupts enabled; callers already exclude each other through
+//! `HW_PKA_Enable`, and a PKA call while the driver is in use panics too.
+
+use core::cell::UnsafeCell;
+
+use crate::bindings::wba_hw_if::{HW_AES_ENC, HW_AES_REV, HW_AES_SWAP};
+
+/// AES-128 block cipher. Called with interrupts masked, so it must poll
+/// the peripheral rather than wait for its interrupt.
+pub trait Aes: Send {
+    fn set_key(&mut self, key: &[u8; 16]);
+    fn encrypt(&mut self, block: &mut [u8; 16]);
+    fn decrypt(&mut self, block: &mut [u8; 16]);
+}
+
+/// NIST P-256 operations. Big numbers are eight 32-bit words, least
+/// significant word first, as the ST drivers use them.
+pub trait Pka: Send {
+    /// Returns whether `coord` is below the field modulus.
+    fn p256_range_check(&mut self, coord: &[u32; 8]) -> bool;
+
+    /// Returns whether (`x`, `y`) is on the curve.
+    fn p256_point_check(&mut self, x: &[u32; 8], y: &[u32; 8]) -> bool;
+
+    /// Returns `k` times the point (`x`, `y`).
+    fn p256_scalar_mul(&mut self, k: &[u32; 8], x: &[u32; 8], y: &[u32; 8])
+    -> ([u32; 8], [u32; 8]);
+}
+
+/// True random number generator. Called with interrupts masked, like
+/// [`Aes`].
+pub trait Rng: Send {
+    fn fill(&mut self, words: &mut [u32]);
+}
+
+/// A driver set once at start-up and used from the exported functions.
+struct Driver<T: ?Sized + 'static>(UnsafeCell<Option<&'static mut T>>);
+
+// Only accessed inside critical sections.
+unsafe impl<T: ?Sized + Send> Sync for Driver<T> {}
+
+impl<T: ?Sized> Driver<T> {
+    const fn new() -> Self {
+        Self(UnsafeCell::new(None))
+    }
+
+    fn set(&self, driver: &'static mut T) {
+        cortex_m::interrupt::free(|_| unsafe { *self.0.get() = Some(driver) });
+    }
+
+    /// Runs `f` on the driver, which is taken out while `f` runs so
+    /// interrupts stay enabled during long operations.
+    ///
+    /// Panics if the driver is not set or already in use.
+    #[cfg_attr(not(feature = "hw_crypto_pka"), allow(dead_code))]
+    fn with<R>(&self, name: &str, f: impl FnOnce(&mut T) -> R) -> R {
+        let driver = cortex_m::interrupt::free(|_| unsafe { (*self.0.get()).take() })
+            .unwrap_or_else(|| unavailable(name));
+        let result = f(driver);
+        cortex_m::interrupt::free(|_| unsafe { *self.0.get() = Some(driver) });
+        result
+    }
+
+    /// Runs `f` on the driver with interrupts masked, so it cannot be in
+    /// use already.
+    ///
+    /// Panics if the driver is not set.
+    #[cfg_attr(
+        not(any(feature = "hw_crypto_aes", feature = "hw_crypto_rng")),
+        allow(dead_code)
+    )]
+    fn with_masked<R>(&self, name: &str, f: impl FnOnce(&mut T) -> R) -> R {
+        cortex_m::interrupt::free(|_| match unsafe { (*self.0.get()).as_deref_mut() } {
+            Some(driver) => f(driver),
+            None => unavailable(name),
+        })
+    }
+}
+
+#[cold]
+fn unavailable(name: &str) -> ! {
+    panic!("hw_crypto: {name} driver not set or in use")
+}
+
+/// Key in the byte order of the standard: `HW_AES_REV` gives it reversed.
+#[cfg_attr(not(feature = "hw_crypto_aes"), allow(dead_code))]
+pub(crate) fn aes_key(mode: u32, mut key: [u8; 16]) -> [u8; 16] {
+    if mode & HW_AES_REV != 0 {
+        key.reverse();
+    }
+    key
+}
+
+/// Block held in the data words of `HW_AES_Crypt`. Like the peripheral's
+/// data registers, the first word holds the first four bytes with the first
+/// one most significant, unless `HW_AES_SWAP` selects their memory order.
+#[cfg_attr(not(feature = "hw_crypto_aes"), allow(dead_code))]
+fn aes_block(mode: u32, words: [u32; 4]) -> [u8; 16] {
+    let mut block = [0; 16];
+    for (bytes, word) in block.chunks_exact_mut(4).zip(words) {
+        let word = if mode & HW_AES_SWAP != 0 {
+            word.to_le_bytes()
+        } else {
+            word.to_be_bytes()
+        };
+        bytes.copy_from_slice(&word);
+    }
+    block
+}
+
+/// Inverse of [`aes_block`].
+#[cfg_attr(not(feature = "hw_crypto_aes"), allow(dead_code))]
+fn aes_words(mode: u32, block: [u8; 16]) -> [u32; 4] {
+    let mut words = [0; 4];
+    for (word, bytes) in words.iter_mut().zip(block.chunks_exact(4)) {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        *word = if mode & HW_AES_SWAP != 0 {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        };
+    }
+    words
+}
+
+/// Encrypts or decrypts the data words as `mode` says.
+#[cfg_attr(not(feature = "hw_crypto_aes"), allow(dead_code))]
+pub(crate) fn aes_crypt(aes: &mut dyn Aes, mode: u32, words: [u32; 4]) -> [u32; 4] {
+    let mut block = aes_block(mode, words);
+    if mode & HW_AES_ENC != 0 {
+        aes.encrypt(&mut block);
+    } else {
+        aes.decrypt(&mut block);
+    }
+    aes_words(mode, block)
+}
+
+/// `HW_AES_Crypt` on bytes copied to and from the data words in memory
+/// order, as ST's `HW_AES_Crypt8` does.
+#[cfg_attr(not(feature = "hw_crypto_aes"), allow(dead_code))]
+pub(crate) fn aes_crypt8(aes: &mut dyn Aes, mode: u32, bytes: [u8; 16]) -> [u8; 16] {
+    let mut words = [0; 4];
+    for (word, bytes) in words.iter_mut().zip(bytes.chunks_exact(4)) {
+        *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+    let mut output = [0; 16];
+    for (bytes, word) in output.chunks_exact_mut(4).zip(aes_crypt(aes, mode, words)) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+    output
+}
+
+#[cfg(feature = "hw_crypto_aes")]
+pub use aes::set_aes;
+
+#[cfg(feature = "hw_crypto_aes")]
+mod aes {
+    use core::cell::UnsafeCell;
+
+    use super::{Aes, Driver, aes_crypt, aes_crypt8, aes_key};
+    use crate::bindings::wba_hw_if::HW_AES_ENC;
+
+    static AES: Driver<dyn Aes> = Driver::new();
+
+    struct Mode(UnsafeCell<u32>);
+
+    // Only accessed inside critical sections.
+    unsafe impl Sync for Mode {}
+
+    /// `HW_AES_*` mode flags of the last key set.
+    static MODE: Mode = Mode(UnsafeCell::new(HW_AES_ENC));
+
+    /// Sets the driver used by the `HW_AES_*` functions. Call before
+    /// starting the BLE stack and the link layer.
+    pub fn set_aes(aes: &'static mut dyn Aes) {
+        AES.set(aes);
+    }
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn HW_AES_Enable() {}
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn HW_AES_Disable() {}
+
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn HW_AES_SetKey(mode: u32, key: *const u8) {
+        let key = unsafe { core::ptr::read_unaligned(key as *const [u8; 16]) };
+        AES.with_masked("AES", |aes| {
+            aes.set_key(&aes_key(mode, key));
+            // Still masked, so no block is processed with the new mode and
+            // the old key.
+            unsafe { *MODE.0.get() = mode };
+        });
+    }
+
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn HW_AES_Crypt(input: *const u32, output: *mut u32) {
+        let words = unsafe { core::ptr::read_unaligned(input as *const [u32; 4]) };
+        let words = AES.with_masked("AES", |aes| aes_crypt(aes, unsafe { *MODE.0.get() }, words));
+        unsafe { core::ptr::write_unaligned(output as *mut [u32; 4], words) };
+    }
+
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn HW_AES_Crypt8(input: *const u8, output: *mut u8) {
+        let bytes = unsafe { core::ptr::read_unaligned(input as *const [u8; 16]) };
+        let bytes = AES.with_masked("AES", |aes| {
+            aes_crypt8(aes, unsafe { *MODE.0.get() }, bytes)
+        });
+        unsafe { core::ptr::write_unaligned(output as *mut [u8; 16], bytes) };
+    }
+}
+
+#[cfg(feature = "hw_crypto_pka")]
+pub use pka::set_pka;
+
+#[cfg(feature = "hw_crypto_pka")]
+mod pka {
+    use core::cell::UnsafeCell;
+
+    use super::{Driver, Pka};
+
+    static PKA: Driver<dyn Pka> = Driver::new();
+
+    /// Results of the last operation, read back by the `Is*Ok` and `Read*`
+    /// functions. The operations complete when started.
+    struct Results {
+        enabled: bool,
+        check_ok: bool,
+        x: [u32; 8],
+        y: [u32; 8],
+    }
+
+    struct Shared(UnsafeCell<Results>);
+
+    // Only accessed inside critical sections.
+    unsafe impl Sync for Shared {}
+
+    static RESULTS: Shared = Shared(UnsafeCell::new(Results {
+        enabled: false,
+        check_ok: false,
+        x: [0; 8],
+        y: [0; 8],
+    }));
+
+    fn results<R>(f: impl FnOnce(&mut Results) -> R) -> R {
+        cortex_m::interrupt::free(|_| f(unsafe { &mut *RESULTS.0.get() }))
+    }
+
+    unsafe fn operand(words: *const u32) -> [u32; 8] {
+        unsafe { core::ptr::read_unaligned(words as *const [u32; 8]) }
+    }
+
+    /// Sets the driver used by the `HW_PKA_*` functions. Call before
+    /// starting the BLE stack and the link layer.
+    pub fn set_pka(pka: &'static mut dyn Pka) {
+        PKA.set(pka);
+    }
+
+    /// Returns 0 while a previous user has not disabled the PKA.
+    #[unsafe(no_mangle)]
+    pub extern "C" fn HW_PKA_Enable() -> i32 {
+        results(|results| !core::mem::replace(&mut results.enabled, true) as i32)
+    }
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn HW_PKA_Disable() {
+        results(|results| results.enabled = false);
+    }
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn HW_PKA_EndOfOperation() -> i32 {
+        1
+    }
+
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn HW_PKA_P256_StartRangeCheck(coord: *const u32) {
+        let coord = unsafe { operand(coord) };
+        let ok = PKA.with("PKA", |pka| pka.p256_range_check(&coord));
+        results(|results| results.check_ok = ok);
+    }
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn HW_PKA_P256_IsRangeCheckOk() -> u32 {
+        results(|results| results.check_ok as u32)
+    }
+
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn HW_PKA_P256_StartPointCheck(x: *const u32, y: *const u32) {
+        let (x, y) = unsafe { (operand(x), operand(y)) };
+        let ok = PKA.with("PKA", |pka| pka.p256_point_check(&x, &y));
+        results(|results| results.check_ok = ok);
+    }
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn HW_PKA_P256_IsPointCheckOk() -> u32 {
+        results(|results| results.check_ok as u32)
+    }
+
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn HW_PKA_P256_StartEccScalarMul(
+        k: *const u32,
+        p_x: *const u32,
+        p_y: *const u32,
+    ) {
+        let (k, x, y) = unsafe { (operand(k), operand(p_x), operand(p_y)) };
+        let (x, y) = PKA.with("PKA", |pka| pka.p256_scalar_mul(&k, &x, &y));
+        results(|results| {
+            results.x = x;
+            results.y = y;
+        });
+    }
+
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn HW_PKA_P256_ReadEccScalarMul(p_x: *mut u32, p_y: *mut u32) {
+        let (x, y) = results(|results| (results.x, results.y));
+        unsafe {
+            core::ptr::write_unaligned(p_x as *mut [u32; 8], x);
+            core::ptr::write_unaligned(p_y as *mut [u32; 8], y);
+        }
+    }
+}
+
+#[cfg(feature = "hw_crypto_rng")]
+pub use rng::set_rng;
+
+#[cfg(feature = "hw_crypto_rng")]
+mod rng {
+    use super::{Driver, Rng};
+
+    static RNG: Driver<dyn Rng> = Driver::new();
+
+    /// Sets the driver used by the `HW_RNG_*` functions. Call before
+    /// starting the BLE stack and the link layer.
+    pub fn set_rng(rng: &'static mut dyn Rng) {
+        RNG.set(rng);
+    }
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn HW_RNG_Init() {}
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn HW_RNG_Start() {}
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn HW_RNG_Disable() {}
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn HW_RNG_SetPoolThreshold(_threshold: u8) {}
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn HW_RNG_EnableClock(_user_mask: u8) {}
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn HW_RNG_DisableClock(_user_mask: u8) {}
+
+    /// Numbers are drawn on demand, so there is never pool refilling to do.
+    #[unsafe(no_mangle)]
+    pub extern "C" fn HW_RNG_Process() -> i32 {
+        0
+    }
+
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn HW_RNG_Get(n: u8, val: *mut u32) {
+        if n == 0 {
+            return;
+        }
+        let words = unsafe { core::slice::from_raw_parts_mut(val, n as usize) };
+        RNG.with_masked("RNG", |rng| rng.fill(words));
+    }
+}
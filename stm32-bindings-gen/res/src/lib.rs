@@ -4,5 +4,46 @@
 #![allow(non_camel_case_types)]
 #![doc(html_no_source)]
 
+#[cfg(feature = "adv_data")]
+pub mod adv_data;
+#[cfg(feature = "platform_assert")]
+pub mod assert;
 pub mod bindings;
 pub use bindings::*;
+#[cfg(feature = "bond_store")]
+pub mod bond_store;
+#[cfg(feature = "calibration")]
+pub mod calibration;
+#[cfg(feature = "capture")]
+pub mod capture;
+#[cfg(feature = "channel_map")]
+pub mod channel_map;
+#[cfg(feature = "event_mask")]
+pub mod event_mask;
+#[cfg(feature = "wba_hw_if")]
+pub mod hw_crypto;
+#[cfg(feature = "mac_frame")]
+pub mod mac_frame;
+pub mod markers;
+#[cfg(feature = "wba_nvm")]
+pub mod nvm;
+#[cfg(any(feature = "temperature_compensation", feature = "calibration"))]
+mod os_timer;
+pub mod platform;
+#[cfg(feature = "stm32_lpm")]
+pub mod power;
+#[cfg(feature = "scheduler_timing")]
+pub mod scheduler_timing;
+#[cfg(feature = "stm32_seq")]
+pub mod sequencer;
+#[cfg(feature = "sleep_clock")]
+pub mod sleep_clock;
+#[cfg(feature = "sleep_timer")]
+pub mod sleep_timer;
+pub mod stubs;
+#[cfg(feature = "temperature_compensation")]
+pub mod temperature;
+#[cfg(feature = "stm32_timer")]
+pub mod timer_server;
+#[cfg(feature = "stm32_adv_trace")]
+pub mod trace;
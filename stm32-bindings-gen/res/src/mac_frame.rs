@@ -0,0 +1,519 @@
+//! IEEE 802.15.4 MAC frame headers, for applications using the raw data path
+//! of the MAC.
+//!
+//! [`Frame::parse`] splits a received frame into its [`Header`] and a
+//! payload borrowed from the frame; [`Frame::encode`] writes one into a
+//! buffer. Frames are handled without their FCS, which the radio computes
+//! and checks. Beacon, data, acknowledgment and MAC command frames of the
+//! 2003, 2006 and 2015 editions are supported, with their addressing fields
+//! and auxiliary security header. The payload of a secured frame is left
+//! encrypted, and header and payload IEs are left at the start of
+//! [`Frame::payload`].
+//!
+//! The PAN ID Compression bit is not part of [`Header`]: it follows from
+//! which PAN IDs are present, and [`Error::Inconsistent`] is returned for
+//! combinations the frame version cannot express.
+
+/// A frame could not be parsed or encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The frame ends inside its header.
+    Truncated,
+    /// The buffer passed to [`Frame::encode`] is too small for the frame.
+    BufferTooSmall,
+    /// The frame uses a reserved frame type, frame version or addressing
+    /// mode.
+    Reserved,
+    /// The frame type is not supported: multipurpose, fragment or extended.
+    Unsupported,
+    /// The header asks for fields its frame version cannot express.
+    Inconsistent,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FrameType {
+    Beacon = 0,
+    Data = 1,
+    Ack = 2,
+    MacCommand = 3,
+}
+
+/// Edition of the standard the frame follows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FrameVersion {
+    Ieee2003 = 0,
+    Ieee2006 = 1,
+    Ieee2015 = 2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Address {
+    Short(u16),
+    Extended(u64),
+}
+
+impl Address {
+    /// Broadcast short address.
+    pub const BROADCAST: Address = Address::Short(0xFFFF);
+
+    const fn mode(address: Option<Address>) -> u16 {
+        match address {
+            None => 0,
+            Some(Address::Short(_)) => 2,
+            Some(Address::Extended(_)) => 3,
+        }
+    }
+
+    const fn len(address: Option<Address>) -> usize {
+        match address {
+            None => 0,
+            Some(Address::Short(_)) => 2,
+            Some(Address::Extended(_)) => 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SecurityLevel {
+    None = 0,
+    Mic32 = 1,
+    Mic64 = 2,
+    Mic128 = 3,
+    /// Encryption without authentication, reserved since the 2015 edition.
+    Enc = 4,
+    EncMic32 = 5,
+    EncMic64 = 6,
+    EncMic128 = 7,
+}
+
+impl SecurityLevel {
+    const LEVELS: [SecurityLevel; 8] = [
+        Self::None,
+        Self::Mic32,
+        Self::Mic64,
+        Self::Mic128,
+        Self::Enc,
+        Self::EncMic32,
+        Self::EncMic64,
+        Self::EncMic128,
+    ];
+
+    /// Length of the message integrity code ending the payload, in bytes.
+    pub const fn mic_len(self) -> usize {
+        match self as u8 & 0b11 {
+            0 => 0,
+            level => 2 << level,
+        }
+    }
+
+    pub const fn encrypted(self) -> bool {
+        self as u8 & 0b100 != 0
+    }
+}
+
+/// Key the frame is secured with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum KeyIdentifier {
+    /// Known to both ends from the addresses.
+    Implicit,
+    /// Key `index` of the default key source.
+    Index(u8),
+    /// Key `index` of a 4-byte key source.
+    Source4 { source: u32, index: u8 },
+    /// Key `index` of an 8-byte key source.
+    Source8 { source: u64, index: u8 },
+}
+
+impl KeyIdentifier {
+    const fn mode(self) -> u8 {
+        match self {
+            Self::Implicit => 0,
+            Self::Index(_) => 1,
+            Self::Source4 { .. } => 2,
+            Self::Source8 { .. } => 3,
+        }
+    }
+
+    const fn len(self) -> usize {
+        match self {
+            Self::Implicit => 0,
+            Self::Index(_) => 1,
+            Self::Source4 { .. } => 5,
+            Self::Source8 { .. } => 9,
+        }
+    }
+}
+
+/// Auxiliary security header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SecurityHeader {
+    pub level: SecurityLevel,
+    pub key_id: KeyIdentifier,
+    /// `None` if suppressed, as only 2015 frames may do.
+    pub frame_counter: Option<u32>,
+    /// The nonce uses the TSCH absolute slot number instead of the frame
+    /// counter. 2015 frames only.
+    pub asn_in_nonce: bool,
+}
+
+impl SecurityHeader {
+    const fn len(&self) -> usize {
+        let counter = if self.frame_counter.is_some() { 4 } else { 0 };
+        1 + counter + self.key_id.len()
+    }
+}
+
+/// MAC header of a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Header {
+    pub frame_type: FrameType,
+    pub version: FrameVersion,
+    pub frame_pending: bool,
+    pub ack_request: bool,
+    /// Header or payload IEs start the payload. 2015 frames only.
+    pub ie_present: bool,
+    /// `None` if suppressed, as only 2015 frames may do.
+    pub sequence_number: Option<u8>,
+    pub dst_pan_id: Option<u16>,
+    pub dst_address: Option<Address>,
+    /// `None` if not sent; with both addresses present, the source is then
+    /// in the destination PAN.
+    pub src_pan_id: Option<u16>,
+    pub src_address: Option<Address>,
+    pub security: Option<SecurityHeader>,
+}
+
+const FRAME_TYPE: u16 = 0b111;
+const SECURITY_ENABLED: u16 = 1 << 3;
+const FRAME_PENDING: u16 = 1 << 4;
+const ACK_REQUEST: u16 = 1 << 5;
+const PAN_ID_COMPRESSION: u16 = 1 << 6;
+const SEQUENCE_NUMBER_SUPPRESSION: u16 = 1 << 8;
+const IE_PRESENT: u16 = 1 << 9;
+const DST_MODE_SHIFT: u16 = 10;
+const VERSION_SHIFT: u16 = 12;
+const SRC_MODE_SHIFT: u16 = 14;
+
+const KEY_ID_MODE_SHIFT: u8 = 3;
+const FRAME_COUNTER_SUPPRESSION: u8 = 1 << 5;
+const ASN_IN_NONCE: u8 = 1 << 6;
+
+/// Whether the destination and source PAN IDs are present for the given
+/// addresses and PAN ID Compression bit.
+fn pan_ids_present(
+    version: FrameVersion,
+    dst: Option<Address>,
+    src: Option<Address>,
+    compression: bool,
+) -> (bool, bool) {
+    if version < FrameVersion::Ieee2015 {
+        return (
+            dst.is_some(),
+            src.is_some() && !(compression && dst.is_some()),
+        );
+    }
+    // IEEE 802.15.4-2015, table 7-2.
+    match (dst, src) {
+        (None, None) => (compression, false),
+        (Some(_), None) => (!compression, false),
+        (None, Some(_)) => (false, !compression),
+        (Some(Address::Extended(_)), Some(Address::Extended(_))) => (!compression, false),
+        (Some(_), Some(_)) => (true, !compression),
+    }
+}
+
+/// The PAN ID Compression bit giving the PAN IDs of `header`, if any does.
+fn pan_id_compression(header: &Header) -> Result<bool, Error> {
+    let present = (header.dst_pan_id.is_some(), header.src_pan_id.is_some());
+    [false, true]
+        .into_iter()
+        .find(|&compression| {
+            pan_ids_present(
+                header.version,
+                header.dst_address,
+                header.src_address,
+                compression,
+            ) == present
+        })
+        .ok_or(Error::Inconsistent)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let (head, rest) = self.bytes.split_first_chunk().ok_or(Error::Truncated)?;
+        self.bytes = rest;
+        Ok(*head)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.take()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+
+    fn address(&mut self, mode: u16) -> Result<Option<Address>, Error> {
+        Ok(match mode {
+            0 => None,
+            2 => Some(Address::Short(self.u16()?)),
+            3 => Some(Address::Extended(self.u64()?)),
+            _ => return Err(Error::Reserved),
+        })
+    }
+
+    fn security(&mut self) -> Result<SecurityHeader, Error> {
+        let control = self.u8()?;
+        let frame_counter = if control & FRAME_COUNTER_SUPPRESSION == 0 {
+            Some(self.u32()?)
+        } else {
+            None
+        };
+        let key_id = match (control >> KEY_ID_MODE_SHIFT) & 0b11 {
+            0 => KeyIdentifier::Implicit,
+            1 => KeyIdentifier::Index(self.u8()?),
+            2 => KeyIdentifier::Source4 {
+                source: self.u32()?,
+                index: self.u8()?,
+            },
+            _ => KeyIdentifier::Source8 {
+                source: self.u64()?,
+                index: self.u8()?,
+            },
+        };
+        Ok(SecurityHeader {
+            level: SecurityLevel::LEVELS[(control & 0b111) as usize],
+            key_id,
+            frame_counter,
+            asn_in_nonce: control & ASN_IN_NONCE != 0,
+        })
+    }
+}
+
+struct Writer<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl Writer<'_> {
+    fn put(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let end = self.len + bytes.len();
+        self.buf
+            .get_mut(self.len..end)
+            .ok_or(Error::BufferTooSmall)?
+            .copy_from_slice(bytes);
+        self.len = end;
+        Ok(())
+    }
+
+    fn address(&mut self, address: Option<Address>) -> Result<(), Error> {
+        match address {
+            None => Ok(()),
+            Some(Address::Short(address)) => self.put(&address.to_le_bytes()),
+            Some(Address::Extended(address)) => self.put(&address.to_le_bytes()),
+        }
+    }
+
+    fn security(&mut self, security: &SecurityHeader) -> Result<(), Error> {
+        let mut control = security.level as u8 | security.key_id.mode() << KEY_ID_MODE_SHIFT;
+        if security.frame_counter.is_none() {
+            control |= FRAME_COUNTER_SUPPRESSION;
+        }
+        if security.asn_in_nonce {
+            control |= ASN_IN_NONCE;
+        }
+        self.put(&[control])?;
+        if let Some(counter) = security.frame_counter {
+            self.put(&counter.to_le_bytes())?;
+        }
+        match security.key_id {
+            KeyIdentifier::Implicit => Ok(()),
+            KeyIdentifier::Index(index) => self.put(&[index]),
+            KeyIdentifier::Source4 { source, index } => {
+                self.put(&source.to_le_bytes())?;
+                self.put(&[index])
+            }
+            KeyIdentifier::Source8 { source, index } => {
+                self.put(&source.to_le_bytes())?;
+                self.put(&[index])
+            }
+        }
+    }
+}
+
+impl Header {
+    /// Length of the encoded header, in bytes.
+    pub const fn encoded_len(&self) -> usize {
+        let sequence = if self.sequence_number.is_some() { 1 } else { 0 };
+        let dst_pan = if self.dst_pan_id.is_some() { 2 } else { 0 };
+        let src_pan = if self.src_pan_id.is_some() { 2 } else { 0 };
+        let security = match &self.security {
+            Some(security) => security.len(),
+            None => 0,
+        };
+        2 + sequence
+            + dst_pan
+            + Address::len(self.dst_address)
+            + src_pan
+            + Address::len(self.src_address)
+            + security
+    }
+
+    fn frame_control(&self) -> Result<u16, Error> {
+        let legacy = self.version < FrameVersion::Ieee2015;
+        let security_legacy = self
+            .security
+            .is_some_and(|security| security.frame_counter.is_none() || security.asn_in_nonce);
+        if legacy && (self.sequence_number.is_none() || self.ie_present || security_legacy) {
+            return Err(Error::Inconsistent);
+        }
+
+        let mut fcf = self.frame_type as u16
+            | Address::mode(self.dst_address) << DST_MODE_SHIFT
+            | (self.version as u16) << VERSION_SHIFT
+            | Address::mode(self.src_address) << SRC_MODE_SHIFT;
+        for (set, bit) in [
+            (self.security.is_some(), SECURITY_ENABLED),
+            (self.frame_pending, FRAME_PENDING),
+            (self.ack_request, ACK_REQUEST),
+            (pan_id_compression(self)?, PAN_ID_COMPRESSION),
+            (self.sequence_number.is_none(), SEQUENCE_NUMBER_SUPPRESSION),
+            (self.ie_present, IE_PRESENT),
+        ] {
+            if set {
+                fcf |= bit;
+            }
+        }
+        Ok(fcf)
+    }
+}
+
+/// A frame without its FCS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Frame<'a> {
+    pub header: Header,
+    /// Everything after the header: IEs if [`Header::ie_present`], then the
+    /// MAC payload, encrypted and followed by its MIC if the frame is
+    /// secured.
+    pub payload: &'a [u8],
+}
+
+impl<'a> Frame<'a> {
+    /// Parses a frame received without its FCS.
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, Error> {
+        let mut reader = Reader { bytes };
+        let fcf = reader.u16()?;
+        let frame_type = match fcf & FRAME_TYPE {
+            0 => FrameType::Beacon,
+            1 => FrameType::Data,
+            2 => FrameType::Ack,
+            3 => FrameType::MacCommand,
+            4 => return Err(Error::Reserved),
+            _ => return Err(Error::Unsupported),
+        };
+        let version = match (fcf >> VERSION_SHIFT) & 0b11 {
+            0 => FrameVersion::Ieee2003,
+            1 => FrameVersion::Ieee2006,
+            2 => FrameVersion::Ieee2015,
+            _ => return Err(Error::Reserved),
+        };
+        let dst_mode = (fcf >> DST_MODE_SHIFT) & 0b11;
+        let src_mode = (fcf >> SRC_MODE_SHIFT) & 0b11;
+        if dst_mode == 1 || src_mode == 1 {
+            return Err(Error::Reserved);
+        }
+        // Modes only, to know which PAN IDs follow.
+        let mode_only = |mode| match mode {
+            0 => None,
+            2 => Some(Address::Short(0)),
+            _ => Some(Address::Extended(0)),
+        };
+        let (dst_pan, src_pan) = pan_ids_present(
+            version,
+            mode_only(dst_mode),
+            mode_only(src_mode),
+            fcf & PAN_ID_COMPRESSION != 0,
+        );
+
+        let sequence_number = if fcf & SEQUENCE_NUMBER_SUPPRESSION == 0 {
+            Some(reader.u8()?)
+        } else {
+            None
+        };
+        let dst_pan_id = if dst_pan { Some(reader.u16()?) } else { None };
+        let dst_address = reader.address(dst_mode)?;
+        let src_pan_id = if src_pan { Some(reader.u16()?) } else { None };
+        let src_address = reader.address(src_mode)?;
+        let security = if fcf & SECURITY_ENABLED != 0 {
+            Some(reader.security()?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            header: Header {
+                frame_type,
+                version,
+                frame_pending: fcf & FRAME_PENDING != 0,
+                ack_request: fcf & ACK_REQUEST != 0,
+                ie_present: fcf & IE_PRESENT != 0,
+                sequence_number,
+                dst_pan_id,
+                dst_address,
+                src_pan_id,
+                src_address,
+                security,
+            },
+            payload: reader.bytes,
+        })
+    }
+
+    /// Length of the encoded frame, in bytes.
+    pub const fn encoded_len(&self) -> usize {
+        self.header.encoded_len() + self.payload.len()
+    }
+
+    /// Writes the frame into `buf`, without FCS, and returns its length.
+    pub fn encode(&self, buf: &mut [u8]) -> Result<usize, Error> {
+        let header = &self.header;
+        let mut writer = Writer { buf, len: 0 };
+        writer.put(&header.frame_control()?.to_le_bytes())?;
+        if let Some(sequence_number) = header.sequence_number {
+            writer.put(&[sequence_number])?;
+        }
+        if let Some(pan_id) = header.dst_pan_id {
+            writer.put(&pan_id.to_le_bytes())?;
+        }
+        writer.address(header.dst_address)?;
+        if let Some(pan_id) = header.src_pan_id {
+            writer.put(&pan_id.to_le_bytes())?;
+        }
+        writer.address(header.src_address)?;
+        if let Some(security) = &header.security {
+            writer.security(security)?;
+        }
+        writer.put(self.payload)?;
+        Ok(writer.len)
+    }
+}
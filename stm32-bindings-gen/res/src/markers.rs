@@ -0,0 +1,116 @@
+//! Marker wrappers for the thread-safety audit of the generated types.
+//!
+//! Types audited as `Send` or `Sync` get the matching `unsafe impl` in their
+//! bindings module. Types that must stay on the context that created them
+//! say so in their documentation and can be wrapped in [`Local`].
+//!
+//! [`ThreadToken`] and [`IsrToken`] prove the execution context: functions
+//! that would lock up when called from the wrong one take the matching
+//! token. Neither is `Send`, so a token cannot leave the context it was
+//! obtained in:
+//!
+//! ```ignore
+//! let thread = ThreadToken::new().unwrap();
+//! loop {
+//!     stm32_bindings::sequencer::run(&thread, sequencer::ALL);
+//! }
+//! ```
+
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+
+use cortex_m::peripheral::SCB;
+use cortex_m::peripheral::scb::VectActive;
+
+/// Keeps a vendor value on the task or interrupt context that created it.
+///
+/// `Local<T>` is neither `Send` nor `Sync`, whatever `T` is, and has the
+/// layout of `T`.
+#[repr(transparent)]
+pub struct Local<T> {
+    value: T,
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl<T> Local<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            value,
+            _not_send: PhantomData,
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+
+    /// Pointer to the wrapped value, for passing it to the vendor API.
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        &mut self.value
+    }
+}
+
+impl<T> Deref for Local<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for Local<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+/// Proof of running in thread mode, outside any exception or interrupt
+/// handler.
+#[derive(Debug, Clone, Copy)]
+pub struct ThreadToken {
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl ThreadToken {
+    /// Returns `None` when called from a handler.
+    pub fn new() -> Option<Self> {
+        match SCB::vect_active() {
+            VectActive::ThreadMode => Some(unsafe { Self::new_unchecked() }),
+            _ => None,
+        }
+    }
+
+    /// # Safety
+    ///
+    /// Must be called in thread mode.
+    pub const unsafe fn new_unchecked() -> Self {
+        Self {
+            _not_send: PhantomData,
+        }
+    }
+}
+
+/// Proof of running in an exception or interrupt handler.
+#[derive(Debug, Clone, Copy)]
+pub struct IsrToken {
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl IsrToken {
+    /// Returns `None` when called in thread mode.
+    pub fn new() -> Option<Self> {
+        match SCB::vect_active() {
+            VectActive::ThreadMode => None,
+            _ => Some(unsafe { Self::new_unchecked() }),
+        }
+    }
+
+    /// # Safety
+    ///
+    /// Must be called from an exception or interrupt handler.
+    pub const unsafe fn new_unchecked() -> Self {
+        Self {
+            _not_send: PhantomData,
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! Non-volatile storage through the ST SNVMA arbiter and flash manager.
+//!
+//! SNVMA keeps RAM buffers registered by the BLE stack and the application
+//! (bonding data, for example) in flash, and schedules its flash operations
+//! through the flash manager so they do not disturb the radio. The C modules
+//! are built by the application, which also implements `FM_ProcessRequest`
+//! to have [`background_process`] called from its idle loop or sequencer.
+//!
+//! With the `nvm_flash_driver` feature, the `FD_*` flash driver used by the
+//! flash manager is implemented here on top of a [`StorageBackend`], in place
+//! of ST's `flash_driver.c`.
+
+use core::cell::UnsafeCell;
+
+use crate::bindings::wba_nvm::*;
+
+/// Error status returned by an SNVMA command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Error(pub u32);
+
+fn check(status: SNVMA_Cmd_Status_t) -> Result<(), Error> {
+    match status.0 {
+        0 => Ok(()),
+        code => Err(Error(code)),
+    }
+}
+
+/// A value only written inside critical sections.
+struct Shared<T>(UnsafeCell<T>);
+
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+impl<T: Copy> Shared<T> {
+    const fn new(value: T) -> Self {
+        Self(UnsafeCell::new(value))
+    }
+
+    fn get(&self) -> T {
+        cortex_m::interrupt::free(|_| unsafe { *self.0.get() })
+    }
+
+    fn set(&self, value: T) {
+        cortex_m::interrupt::free(|_| unsafe { *self.0.get() = value });
+    }
+}
+
+/// Identifier of an SNVMA buffer, as configured for the application.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BufferId(pub SNVMA_BufferId_t);
+
+/// Called when a write started by [`write`] has completed; the argument
+/// tells whether the data reached flash.
+pub type WriteHandler = fn(bool);
+
+static WRITE_HANDLER: Shared<Option<WriteHandler>> = Shared::new(None);
+
+unsafe extern "C" fn write_trampoline(status: SNVMA_Callback_Status_t) {
+    if let Some(handler) = WRITE_HANDLER.get() {
+        handler(status.0 == 0);
+    }
+}
+
+/// Initializes SNVMA on the NVM area starting at `nvm_start`.
+///
+/// # Safety
+///
+/// `nvm_start` must be the start of flash pages reserved for SNVMA.
+pub unsafe fn init(nvm_start: *const u32) -> Result<(), Error> {
+    check(unsafe { SNVMA_Init(nvm_start) })
+}
+
+/// Registers `buffer` as the RAM copy of `id`.
+pub fn register(id: BufferId, buffer: &'static mut [u32]) -> Result<(), Error> {
+    check(unsafe { SNVMA_Register(id.0, buffer.as_mut_ptr(), buffer.len() as u32) })
+}
+
+/// Loads the last data written for `id` from flash into its buffer.
+pub fn restore(id: BufferId) -> Result<(), Error> {
+    check(unsafe { SNVMA_Restore(id.0) })
+}
+
+/// Queues the content of the buffer of `id` for writing to flash.
+///
+/// Completion is reported to the handler set with [`set_write_handler`].
+pub fn write(id: BufferId) -> Result<(), Error> {
+    check(unsafe { SNVMA_Write(id.0, Some(write_trampoline)) })
+}
+
+/// Sets the function told about completed writes.
+pub fn set_write_handler(handler: WriteHandler) {
+    WRITE_HANDLER.set(Some(handler));
+}
+
+/// Runs pending flash manager operations. Call after the flash manager
+/// requested it through `FM_ProcessRequest`.
+pub fn background_process() {
+    unsafe { FM_BackgroundProcess() }
+}
+
+/// The backend failed to program or erase flash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FlashError;
+
+/// Flash access used by the flash manager, with the `nvm_flash_driver`
+/// feature.
+pub trait StorageBackend: Send {
+    /// Programs the 16 bytes of `data` at the absolute flash `address`.
+    fn write(&mut self, address: u32, data: &[u8; 16]) -> Result<(), FlashError>;
+
+    /// Erases flash page `sector`, counted from the start of flash.
+    fn erase_sector(&mut self, sector: u32) -> Result<(), FlashError>;
+}
+
+/// [`StorageBackend`] over an `embedded-storage` flash driver.
+#[cfg(feature = "embedded-storage")]
+pub struct NorFlashBackend<F> {
+    flash: F,
+    base: u32,
+}
+
+#[cfg(feature = "embedded-storage")]
+impl<F: embedded_storage::nor_flash::NorFlash> NorFlashBackend<F> {
+    /// `base` is the address of offset 0 of `flash`, which must be the start
+    /// of flash as pages are numbered from there.
+    pub const fn new(flash: F, base: u32) -> Self {
+        Self { flash, base }
+    }
+
+    pub fn into_inner(self) -> F {
+        self.flash
+    }
+}
+
+#[cfg(feature = "embedded-storage")]
+impl<F: embedded_storage::nor_flash::NorFlash + Send> StorageBackend for NorFlashBackend<F> {
+    fn write(&mut self, address: u32, data: &[u8; 16]) -> Result<(), FlashError> {
+        let offset = address.checked_sub(self.base).ok_or(FlashError)?;
+        self.flash.write(offset, data).map_err(|_| FlashError)
+    }
+
+    fn erase_sector(&mut self, sector: u32) -> Result<(), FlashError> {
+        let size = F::ERASE_SIZE as u32;
+        let from = sector.checked_mul(size).ok_or(FlashError)?;
+        let to = from.checked_add(size).ok_or(FlashError)?;
+        self.flash.erase(from, to).map_err(|_| FlashError)
+    }
+}
+
+#[cfg(feature = "nvm_flash_driver")]
+pub use flash_driver::set_storage_backend;
+
+#[cfg(feature = "nvm_flash_driver")]
+mod flash_driver {
+    use core::cell::UnsafeCell;
+
+    use super::StorageBackend;
+    use crate::bindings::wba_nvm::*;
+
+    struct State {
+        backend: Option<&'static mut dyn StorageBackend>,
+        /// Bit per `FD_Flash_ctrl_bm_t` flag currently disabling access.
+        disabled: u32,
+        /// Flash access is allowed outside the radio timing windows.
+        bypass: bool,
+    }
+
+    struct Global(UnsafeCell<State>);
+
+    // Only accessed inside critical sections.
+    unsafe impl Sync for Global {}
+
+    static STATE: Global = Global(UnsafeCell::new(State {
+        backend: None,
+        disabled: 0,
+        bypass: false,
+    }));
+
+    /// Sets the backend the `FD_*` functions program and erase flash with.
+    pub fn set_storage_backend(backend: &'static mut dyn StorageBackend) {
+        cortex_m::interrupt::free(|_| unsafe { (*STATE.0.get()).backend = Some(backend) });
+    }
+
+    /// Runs `operation` on the backend with interrupts enabled, as flash
+    /// operations take milliseconds. The backend is taken out of the state
+    /// meanwhile, so a nested call fails instead of aliasing it.
+    fn with_backend(
+        operation: impl FnOnce(&mut dyn StorageBackend) -> Result<(), super::FlashError>,
+    ) -> FD_FlashOp_Status_t {
+        let backend = cortex_m::interrupt::free(|_| {
+            let state = unsafe { &mut *STATE.0.get() };
+            let mut blocked = state.disabled;
+            if state.bypass {
+                blocked &= !(1 << FD_Flash_ctrl_bm_t::FD_FLASHACCESS_RFTS.0);
+            }
+            if blocked == 0 {
+                state.backend.take()
+            } else {
+                None
+            }
+        });
+        let Some(backend) = backend else {
+            return FD_FlashOp_Status_t::FD_FLASHOP_FAILURE;
+        };
+        let done = operation(&mut *backend).is_ok();
+        cortex_m::interrupt::free(|_| {
+            // A backend set meanwhile replaces this one.
+            let state = unsafe { &mut *STATE.0.get() };
+            state.backend.get_or_insert(backend);
+        });
+        if done {
+            FD_FlashOp_Status_t::FD_FLASHOP_SUCCESS
+        } else {
+            FD_FlashOp_Status_t::FD_FLASHOP_FAILURE
+        }
+    }
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn FD_SetStatus(Flags_bm: FD_Flash_ctrl_bm_t, Status: FD_FLASH_Status_t) {
+        let disable = Status == FD_FLASH_Status_t::LL_FLASH_DISABLE;
+        cortex_m::interrupt::free(|_| {
+            let state = unsafe { &mut *STATE.0.get() };
+            if Flags_bm == FD_Flash_ctrl_bm_t::FD_FLASHACCESS_RFTS_BYPASS {
+                state.bypass = !disable;
+            } else if disable {
+                state.disabled |= 1 << Flags_bm.0;
+            } else {
+                state.disabled &= !(1 << Flags_bm.0);
+            }
+        });
+    }
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn FD_WriteData(Dest: u32, Payload: u32) -> FD_FlashOp_Status_t {
+        let data = unsafe { core::ptr::read_unaligned(Payload as *const [u8; 16]) };
+        with_backend(|backend| backend.write(Dest, &data))
+    }
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn FD_EraseSectors(Sect: u32) -> FD_FlashOp_Status_t {
+        with_backend(|backend| backend.erase_sector(Sect))
+    }
+}
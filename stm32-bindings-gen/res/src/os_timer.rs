@@ -0,0 +1,68 @@
+//! Periodic timers of the link-layer OS wrapper (`os_wrapper.h`), which run
+//! on the sleep timer and so keep running in low-power modes.
+
+use core::cell::UnsafeCell;
+
+use crate::bindings::wba_link_layer::*;
+
+/// The timer could not be created or started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TimerError;
+
+/// Callback of a link-layer timer, called from the link-layer context.
+pub(crate) type Callback = unsafe extern "C" fn(*const core::ffi::c_void);
+
+/// A periodic link-layer timer, created on first start.
+pub(crate) struct PeriodicTimer {
+    id: UnsafeCell<os_timer_id>,
+    callback: Callback,
+}
+
+// `id` is only accessed inside critical sections.
+unsafe impl Sync for PeriodicTimer {}
+
+impl PeriodicTimer {
+    pub(crate) const fn new(callback: Callback) -> Self {
+        Self {
+            id: UnsafeCell::new(core::ptr::null_mut()),
+            callback,
+        }
+    }
+
+    /// Calls the callback every `period_ms` milliseconds, or stops doing so
+    /// with `None`. Periods above `u32::MAX` microseconds, about 71 minutes,
+    /// are shortened to it.
+    ///
+    /// Call once the link layer is initialized.
+    pub(crate) fn set_period(&self, period_ms: Option<u32>) -> Result<(), TimerError> {
+        let id = cortex_m::interrupt::free(|_| {
+            let id = unsafe { &mut *self.id.get() };
+            if id.is_null() && period_ms.is_some() {
+                *id = unsafe {
+                    os_timer_create(
+                        Some(self.callback),
+                        os_timer_type::os_timer_periodic,
+                        core::ptr::null_mut(),
+                    )
+                };
+            }
+            *id
+        });
+        if id.is_null() {
+            return match period_ms {
+                Some(_) => Err(TimerError),
+                None => Ok(()),
+            };
+        }
+
+        let status = unsafe {
+            os_timer_stop(id);
+            match period_ms {
+                // `os_timer_start` takes the period in microseconds.
+                Some(period) => os_timer_start(id, period.saturating_mul(1000)),
+                None => 0,
+            }
+        };
+        if status == 0 { Ok(()) } else { Err(TimerError) }
+    }
+}
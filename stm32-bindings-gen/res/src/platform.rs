@@ -0,0 +1,440 @@
+//! Link-layer platform services provided by Rust types.
+//!
+//! Instead of defining `LINKLAYER_PLAT_GetRNG` and the other platform hooks
+//! in C, applications implement [`RngSource`], [`TemperatureSensor`] or
+//! [`ClockControl`] on a `static` and register it with the matching macro.
+//! With the `platform_rng`, `platform_temperature` and `platform_clock`
+//! features, the crate exports the C hooks and forwards them to the
+//! registered value:
+//!
+//! ```ignore
+//! static RNG: MyRng = MyRng::new();
+//! stm32_bindings::register_rng_source!(RNG);
+//! ```
+//!
+//! A feature without registration fails the link with an error naming the
+//! missing macro, e.g. ``feature `platform_rng` is enabled but
+//! `register_rng_source!` is never called``; a registration without its
+//! feature fails to compile.
+//!
+//! With `platform_radio_irq`, [`bind_radio_interrupts!`] defines the radio
+//! and SW-low interrupt handlers and the hooks the link layer uses to set up,
+//! mask and trigger them.
+
+/// Random bytes for the link layer, usually from the RNG peripheral.
+pub trait RngSource: Sync {
+    fn fill_bytes(&self, buf: &mut [u8]);
+}
+
+/// Temperature sensor used by the link layer for radio calibration.
+pub trait TemperatureSensor: Sync {
+    /// Measures the die temperature, in degrees Celsius.
+    fn read_celsius(&self) -> i16;
+}
+
+/// Radio clock control.
+pub trait ClockControl: Sync {
+    /// Sets up the radio clocks when the link layer is initialized.
+    fn init(&self);
+
+    /// Enables or disables the radio baseband (active) clock, waiting for
+    /// HSE32 to be ready when enabling it.
+    fn set_active_clock(&self, enable: bool);
+
+    /// Waits until the AHB clock is ready after a wake-up.
+    fn wait_hclk_ready(&self);
+
+    fn delay_us(&self, us: u32);
+}
+
+/// Registers the `static` [`RngSource`] behind `LINKLAYER_PLAT_GetRNG`.
+#[cfg(feature = "platform_rng")]
+#[macro_export]
+macro_rules! register_rng_source {
+    ($source:path) => {
+        #[unsafe(no_mangle)]
+        fn __stm32_bindings_platform_rng() -> &'static dyn $crate::platform::RngSource {
+            &$source
+        }
+    };
+}
+
+/// Registers the `static` [`RngSource`] behind `LINKLAYER_PLAT_GetRNG`.
+#[cfg(not(feature = "platform_rng"))]
+#[macro_export]
+macro_rules! register_rng_source {
+    ($source:path) => {
+        compile_error!("`register_rng_source!` needs the `platform_rng` feature of stm32-bindings");
+    };
+}
+
+/// Registers the `static` [`TemperatureSensor`] behind
+/// `LINKLAYER_PLAT_RequestTemperature`.
+#[cfg(feature = "platform_temperature")]
+#[macro_export]
+macro_rules! register_temperature_sensor {
+    ($sensor:path) => {
+        #[unsafe(no_mangle)]
+        fn __stm32_bindings_platform_temperature()
+        -> &'static dyn $crate::platform::TemperatureSensor {
+            &$sensor
+        }
+    };
+}
+
+/// Registers the `static` [`TemperatureSensor`] behind
+/// `LINKLAYER_PLAT_RequestTemperature`.
+#[cfg(not(feature = "platform_temperature"))]
+#[macro_export]
+macro_rules! register_temperature_sensor {
+    ($sensor:path) => {
+        compile_error!(
+            "`register_temperature_sensor!` needs the `platform_temperature` feature of stm32-bindings"
+        );
+    };
+}
+
+/// Registers the `static` [`ClockControl`] behind the
+/// `LINKLAYER_PLAT_ClockInit`, `AclkCtrl`, `WaitHclkRdy` and `DelayUs` hooks.
+#[cfg(feature = "platform_clock")]
+#[macro_export]
+macro_rules! register_clock_control {
+    ($clock:path) => {
+        #[unsafe(no_mangle)]
+        fn __stm32_bindings_platform_clock() -> &'static dyn $crate::platform::ClockControl {
+            &$clock
+        }
+    };
+}
+
+/// Registers the `static` [`ClockControl`] behind the
+/// `LINKLAYER_PLAT_ClockInit`, `AclkCtrl`, `WaitHclkRdy` and `DelayUs` hooks.
+#[cfg(not(feature = "platform_clock"))]
+#[macro_export]
+macro_rules! register_clock_control {
+    ($clock:path) => {
+        compile_error!(
+            "`register_clock_control!` needs the `platform_clock` feature of stm32-bindings"
+        );
+    };
+}
+
+#[cfg(feature = "platform_rng")]
+mod rng {
+    unsafe extern "Rust" {
+        fn __stm32_bindings_platform_rng() -> &'static dyn super::RngSource;
+    }
+
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn LINKLAYER_PLAT_GetRNG(ptr_rnd: *mut u8, len: u32) {
+        if len == 0 {
+            return;
+        }
+        let buf = unsafe { core::slice::from_raw_parts_mut(ptr_rnd, len as usize) };
+        unsafe { __stm32_bindings_platform_rng() }.fill_bytes(buf);
+    }
+}
+
+#[cfg(feature = "platform_temperature")]
+mod temperature {
+    use crate::bindings::wba_link_layer::cmn_set_temperature_value;
+
+    unsafe extern "Rust" {
+        fn __stm32_bindings_platform_temperature() -> &'static dyn super::TemperatureSensor;
+    }
+
+    /// Measures the temperature and hands it to the link layer right away.
+    #[unsafe(no_mangle)]
+    pub extern "C" fn LINKLAYER_PLAT_RequestTemperature() {
+        let celsius = unsafe { __stm32_bindings_platform_temperature() }.read_celsius();
+        unsafe { cmn_set_temperature_value(celsius as i32 as u32) };
+    }
+}
+
+#[cfg(feature = "platform_clock")]
+mod clock {
+    unsafe extern "Rust" {
+        fn __stm32_bindings_platform_clock() -> &'static dyn super::ClockControl;
+    }
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn LINKLAYER_PLAT_ClockInit() {
+        unsafe { __stm32_bindings_platform_clock() }.init();
+    }
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn LINKLAYER_PLAT_AclkCtrl(enable: u8) {
+        unsafe { __stm32_bindings_platform_clock() }.set_active_clock(enable != 0);
+    }
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn LINKLAYER_PLAT_WaitHclkRdy() {
+        unsafe { __stm32_bindings_platform_clock() }.wait_hclk_ready();
+    }
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn LINKLAYER_PLAT_DelayUs(delay: u32) {
+        unsafe { __stm32_bindings_platform_clock() }.delay_us(delay);
+    }
+}
+
+#[doc(hidden)]
+pub use cortex_m as __cortex_m;
+
+/// Defines the radio and SW-low interrupt handlers and the link-layer hooks
+/// controlling them.
+///
+/// Takes the PAC's interrupt enum and the two interrupt names:
+///
+/// ```ignore
+/// stm32_bindings::bind_radio_interrupts!(pac::Interrupt {
+///     radio: RADIO,
+///     sw_low: HASH,
+/// });
+/// ```
+///
+/// The NVIC priorities are the ones set with
+/// [`configure_interrupt_priorities`]. The radio interrupt gets its
+/// priority when the link layer enables it. Passing
+/// `sw_low_priority: (high, low)` fixes the priorities of the SW-low
+/// interrupt instead.
+///
+/// The handlers are exported under the interrupt names, as cortex-m-rt
+/// expects, and run the ISRs the link layer registered. Applications with
+/// their own handlers can call [`on_radio_interrupt`] and
+/// [`on_sw_low_interrupt`] instead.
+#[cfg(feature = "platform_radio_irq")]
+#[macro_export]
+macro_rules! bind_radio_interrupts {
+    ($irq:ty {
+        radio: $radio:ident,
+        sw_low: $sw_low:ident
+        $(, sw_low_priority: ($high:expr, $low:expr))? $(,)?
+    }) => {
+        #[unsafe(no_mangle)]
+        extern "C" fn $radio() {
+            $crate::platform::on_radio_interrupt();
+        }
+
+        #[unsafe(no_mangle)]
+        extern "C" fn $sw_low() {
+            $crate::platform::on_sw_low_interrupt();
+        }
+
+        #[unsafe(no_mangle)]
+        extern "C" fn LINKLAYER_PLAT_TriggerSwLowIT(priority: u8) {
+            use $crate::platform::__cortex_m::{Peripherals, peripheral::NVIC};
+
+            let irq = <$irq>::$sw_low;
+            // The priority of a running handler cannot change.
+            if !NVIC::is_active(irq) {
+                let fixed: Option<(u8, u8)> = None $(.or(Some(($high, $low))))?;
+                let level = match fixed {
+                    Some((high, low)) => if priority == 0 { high } else { low },
+                    None => $crate::platform::sw_low_priority(priority == 0),
+                };
+                let mut peripherals = unsafe { Peripherals::steal() };
+                unsafe { peripherals.NVIC.set_priority(irq, level) };
+            }
+            NVIC::pend(irq);
+        }
+
+        #[unsafe(no_mangle)]
+        extern "C" fn LINKLAYER_PLAT_EnableRadioIT() {
+            use $crate::platform::__cortex_m::{Peripherals, peripheral::NVIC};
+
+            let irq = <$irq>::$radio;
+            if !NVIC::is_active(irq) {
+                let level = $crate::platform::interrupt_priorities().radio;
+                let mut peripherals = unsafe { Peripherals::steal() };
+                unsafe { peripherals.NVIC.set_priority(irq, level) };
+            }
+            unsafe { NVIC::unmask(irq) };
+        }
+
+        #[unsafe(no_mangle)]
+        extern "C" fn LINKLAYER_PLAT_DisableRadioIT() {
+            $crate::platform::__cortex_m::peripheral::NVIC::mask(<$irq>::$radio);
+        }
+    };
+}
+
+/// Defines the radio and SW-low interrupt handlers and the link-layer hooks
+/// controlling them.
+#[cfg(not(feature = "platform_radio_irq"))]
+#[macro_export]
+macro_rules! bind_radio_interrupts {
+    ($($tokens:tt)*) => {
+        compile_error!(
+            "`bind_radio_interrupts!` needs the `platform_radio_irq` feature of stm32-bindings"
+        );
+    };
+}
+
+#[cfg(feature = "platform_radio_irq")]
+pub use radio_irq::{
+    InterruptPriorities, PriorityError, configure_interrupt_priorities, interrupt_priorities,
+    on_radio_interrupt, on_sw_low_interrupt, sw_low_priority,
+};
+
+#[cfg(feature = "platform_radio_irq")]
+mod radio_irq {
+    use core::cell::UnsafeCell;
+
+    type Isr = unsafe extern "C" fn();
+
+    /// An ISR registered by the link layer.
+    struct Registered(UnsafeCell<Option<Isr>>);
+
+    // Only accessed inside critical sections.
+    unsafe impl Sync for Registered {}
+
+    impl Registered {
+        fn set(&self, isr: Option<Isr>) {
+            cortex_m::interrupt::free(|_| unsafe { *self.0.get() = isr });
+        }
+
+        fn run(&self) {
+            if let Some(isr) = cortex_m::interrupt::free(|_| unsafe { *self.0.get() }) {
+                unsafe { isr() };
+            }
+        }
+    }
+
+    static RADIO_ISR: Registered = Registered(UnsafeCell::new(None));
+    static SW_LOW_ISR: Registered = Registered(UnsafeCell::new(None));
+
+    /// Priority bits implemented by the STM32WBA NVIC, in the high bits of
+    /// each priority byte.
+    const NVIC_PRIO_BITS: u32 = 4;
+
+    /// NVIC priorities of the link-layer interrupts, as written to the
+    /// priority registers: lower values are more urgent, and only the high
+    /// four bits are implemented.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub struct InterruptPriorities {
+        /// Radio interrupt.
+        pub radio: u8,
+        /// SW-low interrupt, when the link layer triggers it with high
+        /// priority.
+        pub sw_low_high: u8,
+        /// SW-low interrupt, when the link layer triggers it with low
+        /// priority.
+        pub sw_low_low: u8,
+    }
+
+    impl InterruptPriorities {
+        /// The priorities of ST's reference platform layer: 0, 5 and 14 on
+        /// the four implemented bits.
+        pub const DEFAULT: Self = Self {
+            radio: 0x00,
+            sw_low_high: 0x50,
+            sw_low_low: 0xE0,
+        };
+    }
+
+    impl Default for InterruptPriorities {
+        fn default() -> Self {
+            Self::DEFAULT
+        }
+    }
+
+    /// Why [`configure_interrupt_priorities`] rejected priorities.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub enum PriorityError {
+        /// The priority sets bits the NVIC does not implement, so it would
+        /// silently be rounded.
+        UnimplementedBits(u8),
+        /// The radio interrupt is less urgent than the SW-low interrupt, or
+        /// the high-priority SW-low level less urgent than the low one.
+        Order,
+        /// The priority is more urgent than `ceiling`, so critical sections
+        /// masking up to the ceiling would not keep it out.
+        AboveCeiling(u8),
+    }
+
+    struct Priorities(UnsafeCell<InterruptPriorities>);
+
+    // Only accessed inside critical sections.
+    unsafe impl Sync for Priorities {}
+
+    static PRIORITIES: Priorities = Priorities(UnsafeCell::new(InterruptPriorities::DEFAULT));
+
+    /// Sets the NVIC priorities of the link-layer interrupts. Call before
+    /// initializing the link layer, which applies them when it enables the
+    /// interrupts.
+    ///
+    /// `ceiling` is the most urgent priority that the application's
+    /// critical sections mask, e.g. its `BASEPRI` value; the link-layer
+    /// interrupts must not be above it, since they call back into code
+    /// sharing state with those sections. Pass `None` when critical sections
+    /// disable all interrupts.
+    pub fn configure_interrupt_priorities(
+        priorities: InterruptPriorities,
+        ceiling: Option<u8>,
+    ) -> Result<(), PriorityError> {
+        let unimplemented = (1u8 << (8 - NVIC_PRIO_BITS)) - 1;
+        for level in [
+            priorities.radio,
+            priorities.sw_low_high,
+            priorities.sw_low_low,
+        ] {
+            if level & unimplemented != 0 {
+                return Err(PriorityError::UnimplementedBits(level));
+            }
+            if let Some(ceiling) = ceiling
+                && level < ceiling
+            {
+                return Err(PriorityError::AboveCeiling(level));
+            }
+        }
+        if priorities.radio > priorities.sw_low_high
+            || priorities.sw_low_high > priorities.sw_low_low
+        {
+            return Err(PriorityError::Order);
+        }
+
+        cortex_m::interrupt::free(|_| unsafe { *PRIORITIES.0.get() = priorities });
+        Ok(())
+    }
+
+    /// The priorities set with [`configure_interrupt_priorities`].
+    pub fn interrupt_priorities() -> InterruptPriorities {
+        cortex_m::interrupt::free(|_| unsafe { *PRIORITIES.0.get() })
+    }
+
+    /// Priority of the SW-low interrupt when triggered with high or low
+    /// priority.
+    #[doc(hidden)]
+    pub fn sw_low_priority(high: bool) -> u8 {
+        let priorities = interrupt_priorities();
+        if high {
+            priorities.sw_low_high
+        } else {
+            priorities.sw_low_low
+        }
+    }
+
+    /// Runs the link-layer radio ISR. Call from the radio interrupt handler.
+    pub fn on_radio_interrupt() {
+        RADIO_ISR.run();
+    }
+
+    /// Runs the link-layer SW-low ISR. Call from the handler of the
+    /// interrupt used as SW-low.
+    pub fn on_sw_low_interrupt() {
+        SW_LOW_ISR.run();
+    }
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn LINKLAYER_PLAT_SetupRadioIT(intr_cb: Option<Isr>) {
+        RADIO_ISR.set(intr_cb);
+    }
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn LINKLAYER_PLAT_SetupSwLowIT(intr_cb: Option<Isr>) {
+        SW_LOW_ISR.set(intr_cb);
+    }
+}
@@ -0,0 +1,196 @@
+//! Low-power policy on top of the ST `UTIL_LPM` low-power manager.
+//!
+//! Every client that needs the CPU or a peripheral clock running holds a
+//! limit on the deepest mode the device may enter; the device enters the
+//! shallowest of all limits. With the `wba_wpan` feature,
+//! [`PowerManager::idle`] also puts the 2.4 GHz radio into deep sleep around
+//! the low-power period; with `sleep_clock`, only once
+//! `sleep_clock::deep_sleep_allowed` says the sleep clock keeps running.
+//!
+//! The mode transitions themselves are done by `UTIL_PowerDriver`, which the
+//! application still provides. Applications entering low-power modes
+//! themselves use [`PowerManager::enter_low_power`] instead, which also
+//! notifies the link layer around the WFI.
+
+use core::cell::UnsafeCell;
+
+use crate::bindings::stm32_lpm::*;
+use crate::markers::ThreadToken;
+
+/// Deepest low-power mode a client allows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LowPowerMode {
+    Sleep,
+    Stop,
+    Off,
+}
+
+/// One of the 32 low-power manager clients.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ClientId(u8);
+
+impl ClientId {
+    /// Returns `None` if `id` is not below 32.
+    pub const fn new(id: u8) -> Option<Self> {
+        if id < 32 { Some(Self(id)) } else { None }
+    }
+
+    pub const fn mask(self) -> u32 {
+        1 << self.0
+    }
+}
+
+/// [`PowerManager::init`] was already called.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AlreadyInitialized;
+
+struct Initialized(UnsafeCell<bool>);
+
+unsafe impl Sync for Initialized {}
+
+static INITIALIZED: Initialized = Initialized(UnsafeCell::new(false));
+
+/// Token proving the low-power manager is initialized.
+#[derive(Debug, Clone, Copy)]
+pub struct PowerManager {
+    _private: (),
+}
+
+impl PowerManager {
+    /// Initializes the low-power manager. Every client starts allowing
+    /// [`LowPowerMode::Off`].
+    pub fn init() -> Result<Self, AlreadyInitialized> {
+        let first = cortex_m::interrupt::free(|_| unsafe {
+            let initialized = &mut *INITIALIZED.0.get();
+            !core::mem::replace(initialized, true)
+        });
+        if !first {
+            return Err(AlreadyInitialized);
+        }
+
+        unsafe { UTIL_LPM_Init() };
+        Ok(Self { _private: () })
+    }
+
+    /// Sets the deepest mode `client` allows.
+    pub fn set_limit(&self, client: ClientId, mode: LowPowerMode) {
+        let state = |allowed: bool| {
+            if allowed {
+                UTIL_LPM_State_t::UTIL_LPM_ENABLE
+            } else {
+                UTIL_LPM_State_t::UTIL_LPM_DISABLE
+            }
+        };
+        unsafe {
+            UTIL_LPM_SetStopMode(client.mask(), state(mode >= LowPowerMode::Stop));
+            UTIL_LPM_SetOffMode(client.mask(), state(mode >= LowPowerMode::Off));
+        }
+    }
+
+    /// Deepest mode all clients currently allow.
+    pub fn mode(&self) -> LowPowerMode {
+        match unsafe { UTIL_LPM_GetMode() } {
+            UTIL_LPM_Mode_t::UTIL_LPM_OFFMODE => LowPowerMode::Off,
+            UTIL_LPM_Mode_t::UTIL_LPM_STOPMODE => LowPowerMode::Stop,
+            _ => LowPowerMode::Sleep,
+        }
+    }
+
+    /// Enters the deepest allowed mode and returns after wake-up.
+    ///
+    /// Call from the idle loop, in thread mode with interrupts enabled: a
+    /// handler entering low power is only woken by higher priorities. With
+    /// the `wba_wpan` feature, `radio_idle_us` is the time until the next
+    /// radio event; when it is given and stop or off mode is allowed, the
+    /// radio sleeps for that long and is woken up again before returning.
+    pub fn idle(&self, _thread: &ThreadToken, radio_idle_us: Option<u32>) {
+        #[cfg(feature = "wba_wpan")]
+        let radio_asleep = match radio_idle_us {
+            Some(duration) if self.mode() != LowPowerMode::Sleep && radio_may_sleep() => unsafe {
+                crate::bindings::wba_link_layer::ll_sys_dp_slp_enter(duration) == 0
+            },
+            _ => false,
+        };
+        #[cfg(not(feature = "wba_wpan"))]
+        let _ = radio_idle_us;
+
+        unsafe { UTIL_LPM_EnterLowPower() };
+
+        #[cfg(feature = "wba_wpan")]
+        if radio_asleep {
+            unsafe { crate::bindings::wba_link_layer::ll_sys_dp_slp_exit() };
+        }
+    }
+
+    /// Enters low power with `sleep` and returns its result after wake-up.
+    ///
+    /// `sleep` gets the deepest allowed mode and performs the entry itself,
+    /// e.g. `|_| cortex_m::asm::wfi()`, or configures SLEEPDEEP and the PWR
+    /// registers first for stop mode. It runs with interrupts disabled, so
+    /// the interrupt that wakes the CPU is only serviced once the link layer
+    /// has been told about the wake-up: with the `wba_wpan` feature, this
+    /// puts the radio into deep sleep for `radio_idle_us` as in
+    /// [`idle`](Self::idle), calls `LINKLAYER_PLAT_NotifyWFIEnter` before
+    /// `sleep`, and `LINKLAYER_PLAT_NotifyWFIExit` and
+    /// `LINKLAYER_PLAT_WaitHclkRdy` after it.
+    ///
+    /// Interrupts are enabled again on return if they were enabled before.
+    /// As for [`idle`](Self::idle), only thread mode may enter low power.
+    pub fn enter_low_power<R>(
+        &self,
+        _thread: &ThreadToken,
+        radio_idle_us: Option<u32>,
+        sleep: impl FnOnce(LowPowerMode) -> R,
+    ) -> R {
+        let interrupts_enabled = cortex_m::register::primask::read().is_active();
+        cortex_m::interrupt::disable();
+
+        let mode = self.mode();
+
+        #[cfg(feature = "wba_wpan")]
+        let result = {
+            use crate::bindings::wba_link_layer::*;
+
+            let radio_asleep = match radio_idle_us {
+                Some(duration) if mode != LowPowerMode::Sleep && radio_may_sleep() => unsafe {
+                    ll_sys_dp_slp_enter(duration) == 0
+                },
+                _ => false,
+            };
+
+            unsafe { LINKLAYER_PLAT_NotifyWFIEnter() };
+            let result = sleep(mode);
+            unsafe {
+                LINKLAYER_PLAT_NotifyWFIExit();
+                LINKLAYER_PLAT_WaitHclkRdy();
+            }
+
+            if radio_asleep {
+                unsafe { ll_sys_dp_slp_exit() };
+            }
+            result
+        };
+        #[cfg(not(feature = "wba_wpan"))]
+        let result = {
+            let _ = radio_idle_us;
+            sleep(mode)
+        };
+
+        if interrupts_enabled {
+            unsafe { cortex_m::interrupt::enable() };
+        }
+        result
+    }
+}
+
+/// Whether the radio may enter deep sleep.
+#[cfg(feature = "wba_wpan")]
+fn radio_may_sleep() -> bool {
+    #[cfg(feature = "sleep_clock")]
+    return crate::sleep_clock::deep_sleep_allowed();
+    #[cfg(not(feature = "sleep_clock"))]
+    true
+}
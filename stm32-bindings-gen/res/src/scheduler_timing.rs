@@ -0,0 +1,151 @@
+//! Timings the link-layer scheduler plans BLE radio events with.
+//!
+//! Before each radio event, the scheduler wakes up early enough to absorb
+//! the drift of the sleep clock and to prepare the event. Both margins are
+//! counted in sleep-timer ticks of [`TICK_NS`] nanoseconds; [`EventTimings`]
+//! holds them with microsecond accessors and rejects values the link layer
+//! cannot take. [`configure_ble_scheduler_timings`] applies them, and the
+//! handler set with [`set_update_handler`] is told about the timings the
+//! link layer actually uses, through `LINKLAYER_PLAT_SCHLDR_TIMING_UPDATE_NOT`.
+//!
+//! Larger margins are needed with a less accurate sleep clock, e.g. LSI
+//! instead of LSE, and with unoptimized builds, which are slower to prepare
+//! an event.
+
+use core::cell::UnsafeCell;
+
+use crate::bindings::wba_link_layer::*;
+
+/// Length of one sleep-timer tick, in nanoseconds.
+pub const TICK_NS: u32 = 31_250;
+
+/// A timing is zero or longer than the link layer takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct OutOfRange;
+
+const fn ticks_to_us(ticks: u32) -> u32 {
+    (ticks as u64 * TICK_NS as u64 / 1_000) as u32
+}
+
+/// Number of ticks lasting at least `us` microseconds, if it fits a `u8`.
+const fn us_to_ticks(us: u32) -> Option<u8> {
+    let ticks = (us as u64 * 1_000).div_ceil(TICK_NS as u64);
+    if ticks <= u8::MAX as u64 {
+        Some(ticks as u8)
+    } else {
+        None
+    }
+}
+
+/// Drift and execution margins of the BLE scheduler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct EventTimings {
+    drift: u8,
+    exec: u8,
+}
+
+impl EventTimings {
+    /// Margins of `drift` and `exec` ticks, neither of which may be zero.
+    pub const fn from_ticks(drift: u8, exec: u8) -> Result<Self, OutOfRange> {
+        if drift == 0 || exec == 0 {
+            return Err(OutOfRange);
+        }
+        Ok(Self { drift, exec })
+    }
+
+    /// Margins of at least `drift_us` and `exec_us` microseconds, rounded up
+    /// to whole ticks.
+    pub const fn from_us(drift_us: u32, exec_us: u32) -> Result<Self, OutOfRange> {
+        match (us_to_ticks(drift_us), us_to_ticks(exec_us)) {
+            (Some(drift), Some(exec)) => Self::from_ticks(drift, exec),
+            _ => Err(OutOfRange),
+        }
+    }
+
+    /// Time allowed for the sleep clock drift, in ticks.
+    pub const fn drift_ticks(&self) -> u8 {
+        self.drift
+    }
+
+    /// Time allowed to prepare an event, in ticks.
+    pub const fn exec_ticks(&self) -> u8 {
+        self.exec
+    }
+
+    pub const fn drift_us(&self) -> u32 {
+        ticks_to_us(self.drift as u32)
+    }
+
+    pub const fn exec_us(&self) -> u32 {
+        ticks_to_us(self.exec as u32)
+    }
+}
+
+/// Has the BLE scheduler plan events with `timings` from now on.
+pub fn configure_ble_scheduler_timings(timings: EventTimings) {
+    unsafe { ll_sys_config_BLE_schldr_timings(timings.drift, timings.exec) }
+}
+
+/// Timings in effect in the link layer, in ticks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AppliedTimings {
+    pub drift_ticks: u32,
+    pub exec_ticks: u32,
+    /// Time from the scheduling decision to the start of the event.
+    pub scheduling_ticks: u32,
+}
+
+impl AppliedTimings {
+    pub const fn drift_us(&self) -> u32 {
+        ticks_to_us(self.drift_ticks)
+    }
+
+    pub const fn exec_us(&self) -> u32 {
+        ticks_to_us(self.exec_ticks)
+    }
+
+    pub const fn scheduling_us(&self) -> u32 {
+        ticks_to_us(self.scheduling_ticks)
+    }
+}
+
+/// Told about the timings the link layer applies. Called from the link-layer
+/// context.
+pub type UpdateHandler = fn(AppliedTimings);
+
+struct Handler(UnsafeCell<Option<UpdateHandler>>);
+
+// Only accessed inside critical sections.
+unsafe impl Sync for Handler {}
+
+static HANDLER: Handler = Handler(UnsafeCell::new(None));
+
+/// Sets the function told about timing updates.
+pub fn set_update_handler(handler: UpdateHandler) {
+    cortex_m::interrupt::free(|_| unsafe { *HANDLER.0.get() = Some(handler) });
+}
+
+/// Link-layer hook reporting the timings it applies.
+///
+/// # Safety
+///
+/// `p_evnt_timing` must be null or point to a valid `Evnt_timing_t`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn LINKLAYER_PLAT_SCHLDR_TIMING_UPDATE_NOT(
+    p_evnt_timing: *mut Evnt_timing_t,
+) {
+    let Some(timing) = (unsafe { p_evnt_timing.as_ref() }) else {
+        return;
+    };
+    let applied = AppliedTimings {
+        drift_ticks: timing.drift_time,
+        exec_ticks: timing.exec_time,
+        scheduling_ticks: timing.schdling_time,
+    };
+    if let Some(handler) = cortex_m::interrupt::free(|_| unsafe { *HANDLER.0.get() }) {
+        handler(applied);
+    }
+}
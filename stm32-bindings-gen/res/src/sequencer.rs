@@ -0,0 +1,102 @@
+//! Safe interface to the ST `UTIL_SEQ` cooperative sequencer.
+//!
+//! Tasks and events are identified by their bit in the sequencer's 32-bit
+//! masks. The sequencer itself disables interrupts around its bookkeeping, so
+//! tasks may be set, paused and resumed from interrupt handlers.
+
+use crate::bindings::stm32_seq::*;
+use crate::markers::ThreadToken;
+
+/// One of the 32 sequencer task slots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TaskId(u8);
+
+impl TaskId {
+    /// Returns `None` if `id` is not below 32.
+    pub const fn new(id: u8) -> Option<Self> {
+        if id < 32 { Some(Self(id)) } else { None }
+    }
+
+    pub const fn mask(self) -> u32 {
+        1 << self.0
+    }
+}
+
+/// One of the 32 sequencer events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct EventId(u8);
+
+impl EventId {
+    /// Returns `None` if `id` is not below 32.
+    pub const fn new(id: u8) -> Option<Self> {
+        if id < 32 { Some(Self(id)) } else { None }
+    }
+
+    pub const fn mask(self) -> u32 {
+        1 << self.0
+    }
+}
+
+/// Selects every task or event in the masks taken by [`run`].
+pub const ALL: u32 = !0;
+
+/// Initializes the sequencer. Call once before any other function.
+pub fn init() {
+    unsafe { UTIL_SEQ_Init() }
+}
+
+/// Registers `task` as the function run for `id`.
+pub fn register_task(id: TaskId, task: extern "C" fn()) {
+    unsafe { UTIL_SEQ_RegTask(id.mask(), 0, Some(task)) }
+}
+
+/// Requests `id` to run once at `priority`, 0 being the highest.
+pub fn set_task(id: TaskId, priority: u32) {
+    unsafe { UTIL_SEQ_SetTask(id.mask(), priority) }
+}
+
+/// Returns whether `id` is pending and not paused.
+pub fn is_task_schedulable(id: TaskId) -> bool {
+    unsafe { UTIL_SEQ_IsSchedulableTask(id.mask()) != 0 }
+}
+
+/// Keeps `id` from running until [`resume_task`]; a pending request is kept.
+pub fn pause_task(id: TaskId) {
+    unsafe { UTIL_SEQ_PauseTask(id.mask()) }
+}
+
+pub fn resume_task(id: TaskId) {
+    unsafe { UTIL_SEQ_ResumeTask(id.mask()) }
+}
+
+/// Returns whether `id` is paused.
+pub fn is_task_paused(id: TaskId) -> bool {
+    unsafe { UTIL_SEQ_IsPauseTask(id.mask()) != 0 }
+}
+
+pub fn set_event(id: EventId) {
+    unsafe { UTIL_SEQ_SetEvt(id.mask()) }
+}
+
+pub fn clear_event(id: EventId) {
+    unsafe { UTIL_SEQ_ClrEvt(id.mask()) }
+}
+
+/// Runs other tasks until `id` is set, then consumes it.
+///
+/// Must be called from a sequencer task, which [`run`] only runs in thread
+/// mode; the calling task is not run again while it waits.
+pub fn wait_event(_thread: &ThreadToken, id: EventId) {
+    unsafe { UTIL_SEQ_WaitEvt(id.mask()) }
+}
+
+/// Runs the pending tasks selected by `mask`, then idles.
+///
+/// Applications call `run(&thread, ALL)` in their main loop. Running the
+/// sequencer from an interrupt handler would keep lower-priority interrupts
+/// from ever waking it, hence the [`ThreadToken`].
+pub fn run(_thread: &ThreadToken, mask: u32) {
+    unsafe { UTIL_SEQ_Run(mask) }
+}
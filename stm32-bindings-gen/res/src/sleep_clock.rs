@@ -0,0 +1,123 @@
+//! Source and accuracy of the link-layer sleep clock.
+//!
+//! The sleep timer times radio events across low-power periods, so the
+//! link layer widens its receive windows by the accuracy of the clock
+//! driving it. [`configure_sleep_clock`] hands the source selected in RCC
+//! and its accuracy to the link layer, and refuses accuracies worse than the
+//! 500 ppm BLE allows.
+//!
+//! With `stm32_lpm`, `power::PowerManager` only puts the radio into deep
+//! sleep once a sleep clock is configured that keeps running there: HSE
+//! stops in Stop mode, so HSE/1000 does not.
+
+use core::cell::UnsafeCell;
+
+use crate::bindings::wba_link_layer::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The accuracy is worse than 500 ppm.
+    Inaccurate,
+    /// The link layer refused the sleep clock accuracy.
+    Rejected,
+}
+
+/// Clock of the sleep timer, as selected in RCC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SleepClockSource {
+    Lse,
+    Lsi,
+    /// HSE divided by 1000, stopped in Stop mode.
+    HseDiv1000,
+}
+
+/// Sleep clock accuracy, as encoded in BLE PDUs and HCI commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum Sca {
+    Ppm251To500 = 0,
+    Ppm151To250 = 1,
+    Ppm101To150 = 2,
+    Ppm76To100 = 3,
+    Ppm51To75 = 4,
+    Ppm31To50 = 5,
+    Ppm21To30 = 6,
+    Ppm0To20 = 7,
+}
+
+impl Sca {
+    /// Range covering an accuracy of `ppm`, or `None` if it is worse than
+    /// 500 ppm.
+    pub const fn from_ppm(ppm: u16) -> Option<Self> {
+        Some(match ppm {
+            0..=20 => Self::Ppm0To20,
+            21..=30 => Self::Ppm21To30,
+            31..=50 => Self::Ppm31To50,
+            51..=75 => Self::Ppm51To75,
+            76..=100 => Self::Ppm76To100,
+            101..=150 => Self::Ppm101To150,
+            151..=250 => Self::Ppm151To250,
+            251..=500 => Self::Ppm251To500,
+            _ => return None,
+        })
+    }
+
+    /// Worst accuracy of the range, in ppm.
+    pub const fn max_ppm(self) -> u16 {
+        match self {
+            Self::Ppm251To500 => 500,
+            Self::Ppm151To250 => 250,
+            Self::Ppm101To150 => 150,
+            Self::Ppm76To100 => 100,
+            Self::Ppm51To75 => 75,
+            Self::Ppm31To50 => 50,
+            Self::Ppm21To30 => 30,
+            Self::Ppm0To20 => 20,
+        }
+    }
+}
+
+/// Sleep clock the link layer runs on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SleepClockConfig {
+    pub source: SleepClockSource,
+    /// Worst-case accuracy over temperature and aging, in ppm.
+    pub accuracy_ppm: u16,
+}
+
+struct Configured(UnsafeCell<Option<SleepClockConfig>>);
+
+// Only accessed inside critical sections.
+unsafe impl Sync for Configured {}
+
+static CONFIGURED: Configured = Configured(UnsafeCell::new(None));
+
+/// Has the link layer use the sleep clock selected in RCC, described by
+/// `config`, and returns the accuracy it advertises.
+///
+/// Call once the link layer is initialized, after selecting the clock in
+/// RCC.
+pub fn configure_sleep_clock(config: SleepClockConfig) -> Result<Sca, Error> {
+    let sca = Sca::from_ppm(config.accuracy_ppm).ok_or(Error::Inaccurate)?;
+    unsafe { ll_sys_sleep_clock_source_selection() };
+    if unsafe { le_set_sleep_clock_accuracy(sca as u8) } != 0 {
+        return Err(Error::Rejected);
+    }
+    cortex_m::interrupt::free(|_| unsafe { *CONFIGURED.0.get() = Some(config) });
+    Ok(sca)
+}
+
+/// Sleep clock last configured with [`configure_sleep_clock`].
+pub fn sleep_clock() -> Option<SleepClockConfig> {
+    cortex_m::interrupt::free(|_| unsafe { *CONFIGURED.0.get() })
+}
+
+/// Whether the radio may enter deep sleep: a sleep clock is configured, and
+/// it keeps running in Stop mode.
+pub fn deep_sleep_allowed() -> bool {
+    sleep_clock().is_some_and(|config| config.source != SleepClockSource::HseDiv1000)
+}
@@ -0,0 +1,96 @@
+//! Timebase on top of the link-layer sleep timer.
+//!
+//! The sleep timer keeps counting in every low-power mode the link layer
+//! uses, in steps of [`TICK_NS`] nanoseconds. [`SleepTimer`] extends its
+//! 32-bit counter to 64 bits, so it can drive an RTIC monotonic or an
+//! embassy time driver, and implements [`embedded_hal::delay::DelayNs`].
+//!
+//! The counter wraps about every 37 hours. A wrap is only seen if
+//! [`SleepTimer::now`] is called at least once in between, e.g. from the
+//! time driver's alarm.
+
+use core::cell::UnsafeCell;
+
+use embedded_hal::delay::DelayNs;
+
+use crate::bindings::wba_link_layer::cmn_get_slptmr_value;
+
+/// Length of one sleep-timer tick, in nanoseconds.
+pub const TICK_NS: u32 = 31_250;
+
+struct Extension {
+    last: u32,
+    wraps: u32,
+}
+
+struct State(UnsafeCell<Extension>);
+
+unsafe impl Sync for State {}
+
+static STATE: State = State(UnsafeCell::new(Extension { last: 0, wraps: 0 }));
+
+/// Monotonic clock and delay provider reading the sleep timer.
+///
+/// The values are meaningful once the link layer is initialized.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SleepTimer {
+    _private: (),
+}
+
+impl SleepTimer {
+    pub const fn new() -> Self {
+        Self { _private: () }
+    }
+
+    /// Ticks elapsed since the link layer started the sleep timer.
+    pub fn now(&self) -> u64 {
+        cortex_m::interrupt::free(|_| {
+            // Read inside the critical section, so a preempting caller cannot
+            // store a later value before this one.
+            let raw = unsafe { cmn_get_slptmr_value() };
+            let state = unsafe { &mut *STATE.0.get() };
+            if raw < state.last {
+                state.wraps = state.wraps.wrapping_add(1);
+            }
+            state.last = raw;
+            (u64::from(state.wraps) << 32) | u64::from(raw)
+        })
+    }
+
+    /// Microseconds elapsed since the link layer started the sleep timer.
+    pub fn now_us(&self) -> u64 {
+        ticks_to_ns(self.now()) / 1_000
+    }
+
+    /// Waits until at least `ticks` full ticks have elapsed.
+    fn wait_ticks(&self, ticks: u64) {
+        // The first tick may already be partly over.
+        let ticks = ticks.saturating_add(1);
+        let start = self.now();
+        while self.now().wrapping_sub(start) < ticks {}
+    }
+}
+
+/// Converts a tick count to nanoseconds, saturating after about 18 years.
+pub const fn ticks_to_ns(ticks: u64) -> u64 {
+    ticks.saturating_mul(TICK_NS as u64)
+}
+
+/// Number of ticks lasting at least `ns` nanoseconds.
+pub const fn ns_to_ticks(ns: u64) -> u64 {
+    ns.div_ceil(TICK_NS as u64)
+}
+
+impl DelayNs for SleepTimer {
+    fn delay_ns(&mut self, ns: u32) {
+        self.wait_ticks(ns_to_ticks(u64::from(ns)));
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        self.wait_ticks(ns_to_ticks(u64::from(us) * 1_000));
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        self.wait_ticks(ns_to_ticks(u64::from(ms) * 1_000_000));
+    }
+}
@@ -0,0 +1,104 @@
+//! Radio temperature compensation driven by an ADC driver.
+//!
+//! The link layer recalibrates the radio when the die temperature drifts and
+//! asks for a measurement through `LINKLAYER_PLAT_RequestTemperature`. Here
+//! that hook calls the [`RequestHandler`] given to
+//! [`TemperatureCompensation::init`], which starts a conversion; the driver
+//! hands the result to [`report_temperature`] once it is available, from
+//! thread or interrupt context. Measurements can also be requested
+//! periodically from the link-layer sleep timer, so slow drifts are caught
+//! while the link layer does not ask.
+//!
+//! Unlike a `platform::TemperatureSensor`, the measurement does not have to
+//! complete inside the hook.
+
+use core::cell::UnsafeCell;
+
+use crate::bindings::wba_link_layer::*;
+use crate::os_timer::PeriodicTimer;
+
+#[cfg(feature = "platform_temperature")]
+compile_error!(
+    "the `temperature_compensation` and `platform_temperature` features both implement `LINKLAYER_PLAT_RequestTemperature`"
+);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// [`TemperatureCompensation::init`] was already called.
+    AlreadyInitialized,
+    /// The sleep timer used for refreshes could not be created or started.
+    Timer,
+}
+
+/// Starts a temperature measurement, to be passed to [`report_temperature`].
+///
+/// Called from the link-layer context, so it should only trigger the
+/// conversion.
+pub type RequestHandler = fn();
+
+struct State {
+    request: Option<RequestHandler>,
+}
+
+struct Global(UnsafeCell<State>);
+
+// Only accessed inside critical sections.
+unsafe impl Sync for Global {}
+
+static STATE: Global = Global(UnsafeCell::new(State { request: None }));
+
+fn state<R>(f: impl FnOnce(&mut State) -> R) -> R {
+    cortex_m::interrupt::free(|_| f(unsafe { &mut *STATE.0.get() }))
+}
+
+fn request() {
+    if let Some(request) = state(|state| state.request) {
+        request();
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn LINKLAYER_PLAT_RequestTemperature() {
+    request();
+}
+
+unsafe extern "C" fn refresh(_arg: *const core::ffi::c_void) {
+    request();
+}
+
+static REFRESH: PeriodicTimer = PeriodicTimer::new(refresh);
+
+/// Hands a measured die temperature, in degrees Celsius, to the link layer,
+/// which recalibrates the radio if it changed enough.
+pub fn report_temperature(celsius: i16) {
+    unsafe { cmn_set_temperature_value(celsius as i32 as u32) };
+}
+
+/// Token proving temperature compensation is set up.
+#[derive(Debug, Clone, Copy)]
+pub struct TemperatureCompensation {
+    _private: (),
+}
+
+impl TemperatureCompensation {
+    /// Has the link layer's measurement requests served by `request`.
+    pub fn init(request: RequestHandler) -> Result<Self, Error> {
+        state(|state| match state.request {
+            Some(_) => Err(Error::AlreadyInitialized),
+            None => {
+                state.request = Some(request);
+                Ok(Self { _private: () })
+            }
+        })
+    }
+
+    /// Requests a measurement every `period_ms` milliseconds in addition to
+    /// the link layer's own requests, or stops doing so with `None`.
+    ///
+    /// Call once the link layer is initialized, as the refresh runs on its
+    /// sleep timer.
+    pub fn set_refresh_period(&self, period_ms: Option<u32>) -> Result<(), Error> {
+        REFRESH.set_period(period_ms).map_err(|_| Error::Timer)
+    }
+}
@@ -0,0 +1,225 @@
+//! Safe interface to the ST `UTIL_TIMER` timer server.
+//!
+//! The timer server multiplexes software timers onto one hardware alarm. Its
+//! hardware driver, `UTIL_TimerDriver`, is still provided by the application
+//! (usually on top of the RTC), and the alarm interrupt must call
+//! [`TimerServer::on_interrupt`].
+//!
+//! Callbacks are `'static` closures kept in one of [`TIMER_SLOTS`] static
+//! slots, so no allocator is needed. They run in the alarm interrupt.
+
+use core::cell::UnsafeCell;
+use core::ffi::c_void;
+
+use crate::bindings::stm32_timer::*;
+
+/// Number of [`Timer`]s that can exist at the same time.
+pub const TIMER_SLOTS: usize = 16;
+
+/// Closure run when a timer elapses.
+pub type Callback = &'static mut (dyn FnMut() + Send);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    InvalidParam,
+    Hardware,
+    Unknown,
+    /// All [`TIMER_SLOTS`] slots are in use.
+    NoFreeSlot,
+    /// [`TimerServer::init`] was already called.
+    AlreadyInitialized,
+}
+
+fn check(status: UTIL_TIMER_Status_t) -> Result<(), Error> {
+    match status {
+        UTIL_TIMER_Status_t::UTIL_TIMER_OK => Ok(()),
+        UTIL_TIMER_Status_t::UTIL_TIMER_INVALID_PARAM => Err(Error::InvalidParam),
+        UTIL_TIMER_Status_t::UTIL_TIMER_HW_ERROR => Err(Error::Hardware),
+        _ => Err(Error::Unknown),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Mode {
+    /// Runs the callback once per start.
+    OneShot,
+    /// Restarts after every expiry until stopped.
+    Periodic,
+}
+
+impl Mode {
+    fn raw(self) -> UTIL_TIMER_Mode_t {
+        match self {
+            Mode::OneShot => UTIL_TIMER_Mode_t::UTIL_TIMER_ONESHOT,
+            Mode::Periodic => UTIL_TIMER_Mode_t::UTIL_TIMER_PERIODIC,
+        }
+    }
+}
+
+struct Slot {
+    object: UnsafeCell<UTIL_TIMER_Object_t>,
+    callback: UnsafeCell<Option<Callback>>,
+    used: UnsafeCell<bool>,
+}
+
+// Slot state is only touched inside critical sections, and the timer object
+// only through the `Timer` owning the slot.
+unsafe impl Sync for Slot {}
+
+impl Slot {
+    const fn new() -> Self {
+        Self {
+            object: UnsafeCell::new(unsafe { core::mem::zeroed() }),
+            callback: UnsafeCell::new(None),
+            used: UnsafeCell::new(false),
+        }
+    }
+}
+
+static SLOTS: [Slot; TIMER_SLOTS] = [const { Slot::new() }; TIMER_SLOTS];
+
+struct Initialized(UnsafeCell<bool>);
+
+unsafe impl Sync for Initialized {}
+
+static INITIALIZED: Initialized = Initialized(UnsafeCell::new(false));
+
+/// Runs the closure of the slot passed as `argument`.
+///
+/// The closure is taken out of its slot while it runs, so dropping the
+/// [`Timer`] from a higher-priority interrupt cannot alias it.
+unsafe extern "C" fn trampoline(argument: *mut c_void) {
+    let slot = &SLOTS[argument as usize];
+    let callback = cortex_m::interrupt::free(|_| unsafe { (*slot.callback.get()).take() });
+    let Some(callback) = callback else {
+        return;
+    };
+
+    callback();
+
+    cortex_m::interrupt::free(|_| unsafe {
+        let stored = &mut *slot.callback.get();
+        if *slot.used.get() && stored.is_none() {
+            *stored = Some(callback);
+        }
+    });
+}
+
+/// Token proving the timer server is initialized.
+#[derive(Debug, Clone, Copy)]
+pub struct TimerServer {
+    _private: (),
+}
+
+impl TimerServer {
+    /// Initializes the timer server and its hardware driver.
+    pub fn init() -> Result<Self, Error> {
+        let first = cortex_m::interrupt::free(|_| unsafe {
+            let initialized = &mut *INITIALIZED.0.get();
+            !core::mem::replace(initialized, true)
+        });
+        if !first {
+            return Err(Error::AlreadyInitialized);
+        }
+
+        check(unsafe { UTIL_TIMER_Init() })?;
+        Ok(Self { _private: () })
+    }
+
+    /// Creates a stopped timer running `callback` every `period_ms`
+    /// milliseconds.
+    pub fn create(&self, mode: Mode, period_ms: u32, callback: Callback) -> Result<Timer, Error> {
+        let slot = cortex_m::interrupt::free(|_| {
+            let index = SLOTS.iter().position(|slot| unsafe { !*slot.used.get() })?;
+            let slot = &SLOTS[index];
+            unsafe {
+                *slot.used.get() = true;
+                *slot.callback.get() = Some(callback);
+            }
+            Some(index)
+        })
+        .ok_or(Error::NoFreeSlot)?;
+
+        let created = check(unsafe {
+            UTIL_TIMER_Create(
+                SLOTS[slot].object.get(),
+                period_ms,
+                mode.raw(),
+                Some(trampoline),
+                slot as *mut c_void,
+            )
+        });
+        if let Err(err) = created {
+            // No `Timer` yet: dropping one would stop an uncreated object.
+            release(slot);
+            return Err(err);
+        }
+        Ok(Timer { slot })
+    }
+
+    /// Current time of the timer server, in milliseconds.
+    pub fn now(&self) -> u32 {
+        unsafe { UTIL_TIMER_GetCurrentTime() }
+    }
+
+    /// Milliseconds elapsed since `past`, a value returned by [`Self::now`].
+    pub fn elapsed_since(&self, past: u32) -> u32 {
+        unsafe { UTIL_TIMER_GetElapsedTime(past) }
+    }
+
+    /// Processes expired timers. Call from the hardware alarm interrupt.
+    pub fn on_interrupt() {
+        unsafe { UTIL_TIMER_IRQ_Handler() }
+    }
+}
+
+/// A software timer owning one static slot. Dropping it stops the timer.
+pub struct Timer {
+    slot: usize,
+}
+
+impl Timer {
+    fn object(&self) -> *mut UTIL_TIMER_Object_t {
+        SLOTS[self.slot].object.get()
+    }
+
+    pub fn start(&mut self) -> Result<(), Error> {
+        check(unsafe { UTIL_TIMER_Start(self.object()) })
+    }
+
+    /// Changes the period to `period_ms` and (re)starts the timer.
+    pub fn start_with_period(&mut self, period_ms: u32) -> Result<(), Error> {
+        check(unsafe { UTIL_TIMER_StartWithPeriod(self.object(), period_ms) })
+    }
+
+    pub fn stop(&mut self) -> Result<(), Error> {
+        check(unsafe { UTIL_TIMER_Stop(self.object()) })
+    }
+
+    /// Changes the period used from the next start on.
+    pub fn set_period(&mut self, period_ms: u32) -> Result<(), Error> {
+        check(unsafe { UTIL_TIMER_SetPeriod(self.object(), period_ms) })
+    }
+
+    pub fn is_running(&self) -> bool {
+        unsafe { UTIL_TIMER_IsRunning(self.object()) != 0 }
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        let _ = self.stop();
+        release(self.slot);
+    }
+}
+
+/// Frees `slot` and drops its callback.
+fn release(slot: usize) {
+    let slot = &SLOTS[slot];
+    cortex_m::interrupt::free(|_| unsafe {
+        *slot.callback.get() = None;
+        *slot.used.get() = false;
+    });
+}
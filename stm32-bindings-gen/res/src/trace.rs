@@ -0,0 +1,289 @@
+//! Output through the ST `UTIL_ADV_TRACE` advanced trace utility.
+//!
+//! Rust logs and the trace output of the vendor C code share one FIFO and
+//! one transport, provided by the application as `UTIL_TraceDriver` (usually
+//! a UART with DMA). With the `log` feature, [`init_logger`] installs a `log`
+//! backend; with `adv_trace_defmt`, the utility is the defmt global logger.
+
+use core::cell::UnsafeCell;
+use core::fmt::{self, Write};
+
+use crate::bindings::stm32_adv_trace::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    InvalidParam,
+    Hardware,
+    /// The FIFO has no room for the message; it is dropped.
+    MemFull,
+    Unknown,
+}
+
+fn check(status: UTIL_ADV_TRACE_Status_t) -> Result<(), Error> {
+    match status {
+        UTIL_ADV_TRACE_Status_t::UTIL_ADV_TRACE_OK => Ok(()),
+        UTIL_ADV_TRACE_Status_t::UTIL_ADV_TRACE_INVALID_PARAM => Err(Error::InvalidParam),
+        UTIL_ADV_TRACE_Status_t::UTIL_ADV_TRACE_HW_ERROR => Err(Error::Hardware),
+        UTIL_ADV_TRACE_Status_t::UTIL_ADV_TRACE_MEM_FULL => Err(Error::MemFull),
+        _ => Err(Error::Unknown),
+    }
+}
+
+/// A value only written inside critical sections.
+struct Shared<T>(UnsafeCell<T>);
+
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+impl<T: Copy> Shared<T> {
+    const fn new(value: T) -> Self {
+        Self(UnsafeCell::new(value))
+    }
+
+    fn get(&self) -> T {
+        cortex_m::interrupt::free(|_| unsafe { *self.0.get() })
+    }
+
+    fn set(&self, value: T) {
+        cortex_m::interrupt::free(|_| unsafe { *self.0.get() = value });
+    }
+}
+
+/// Writes a timestamp into the buffer and returns the number of bytes written.
+pub type Timestamp = fn(&mut [u8]) -> usize;
+
+static TIMESTAMP: Shared<Option<Timestamp>> = Shared::new(None);
+static OVERRUN_MARKER: Shared<&'static [u8]> = Shared::new(b"");
+
+/// Initializes the utility and its driver.
+pub fn init() -> Result<(), Error> {
+    check(unsafe { UTIL_ADV_TRACE_Init() })
+}
+
+/// Queues `data` for output, as is.
+pub fn send(data: &[u8]) -> Result<(), Error> {
+    for chunk in data.chunks(u16::MAX as usize) {
+        check(unsafe { UTIL_ADV_TRACE_Send(chunk.as_ptr(), chunk.len() as u16) })?;
+    }
+    Ok(())
+}
+
+/// Drops formatted vendor traces above `level`.
+pub fn set_verbose_level(level: u8) {
+    unsafe { UTIL_ADV_TRACE_SetVerboseLevel(level) }
+}
+
+/// Enables the vendor trace regions in the `regions` mask.
+pub fn set_regions(regions: u32) {
+    unsafe { UTIL_ADV_TRACE_SetRegion(regions) }
+}
+
+unsafe extern "C" fn timestamp_trampoline(data: *mut u8, size: *mut u16) {
+    let len = match TIMESTAMP.get() {
+        Some(timestamp) => {
+            let max = UTIL_ADV_TRACE_TMP_MAX_TIMESTMAP_SIZE as usize;
+            let buf = unsafe { core::slice::from_raw_parts_mut(data, max) };
+            timestamp(buf).min(max)
+        }
+        None => 0,
+    };
+    unsafe { *size = len as u16 };
+}
+
+/// Sets the function writing the timestamp that prefixes timestamped vendor
+/// traces and Rust logs.
+pub fn set_timestamp(timestamp: Timestamp) {
+    TIMESTAMP.set(Some(timestamp));
+    unsafe { UTIL_ADV_TRACE_RegisterTimeStampFunction(Some(timestamp_trampoline)) }
+}
+
+unsafe extern "C" fn overrun_trampoline(data: *mut *mut u8, size: *mut u16) {
+    let marker = OVERRUN_MARKER.get();
+    unsafe {
+        *data = marker.as_ptr().cast_mut();
+        *size = marker.len() as u16;
+    }
+}
+
+/// Sets the text queued in place of messages dropped because the FIFO was
+/// full.
+pub fn set_overrun_marker(marker: &'static [u8]) {
+    OVERRUN_MARKER.set(&marker[..marker.len().min(u16::MAX as usize)]);
+    unsafe { UTIL_ADV_TRACE_RegisterOverRunFunction(Some(overrun_trampoline)) }
+}
+
+/// Length of the longest Rust log line; longer lines are cut.
+pub const LINE_LEN: usize = 256;
+
+/// Formats one log line, cutting it at [`LINE_LEN`] bytes.
+struct Line {
+    buf: [u8; LINE_LEN],
+    len: usize,
+}
+
+impl Line {
+    const fn new() -> Self {
+        Self {
+            buf: [0; LINE_LEN],
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        // Keep room for the line ending.
+        let room = LINE_LEN - 2 - self.len;
+        let bytes = &bytes[..bytes.len().min(room)];
+        self.buf[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+    }
+
+    fn finish(&mut self) -> &[u8] {
+        self.buf[self.len..self.len + 2].copy_from_slice(b"\r\n");
+        &self.buf[..self.len + 2]
+    }
+}
+
+impl Write for Line {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push(s.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "log")]
+struct Logger;
+
+#[cfg(feature = "log")]
+static LOGGER: Logger = Logger;
+
+#[cfg(feature = "log")]
+impl log::Log for Logger {
+    fn enabled(&self, _metadata: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        let mut line = Line::new();
+        if let Some(timestamp) = TIMESTAMP.get() {
+            let mut stamp = [0; UTIL_ADV_TRACE_TMP_MAX_TIMESTMAP_SIZE as usize];
+            let len = timestamp(&mut stamp).min(stamp.len());
+            line.push(&stamp[..len]);
+            line.push(b" ");
+        }
+        let _ = write!(
+            line,
+            "{} {}: {}",
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        let _ = send(line.finish());
+    }
+
+    fn flush(&self) {}
+}
+
+/// Installs the `log` backend and sets the maximum level.
+#[cfg(feature = "log")]
+pub fn init_logger(level: log::LevelFilter) -> Result<(), log::SetLoggerError> {
+    log::set_logger(&LOGGER)?;
+    log::set_max_level(level);
+    Ok(())
+}
+
+/// Length of the longest defmt frame; longer frames are dropped.
+#[cfg(feature = "adv_trace_defmt")]
+pub const DEFMT_FRAME_LEN: usize = 256;
+
+#[cfg(feature = "adv_trace_defmt")]
+mod defmt_logger {
+    use core::cell::UnsafeCell;
+
+    use super::DEFMT_FRAME_LEN;
+
+    /// Encoded frame, queued as a whole on release: a frame cut by a full
+    /// FIFO would corrupt the stream for the decoder.
+    struct Frame {
+        buf: [u8; DEFMT_FRAME_LEN],
+        len: usize,
+        overflow: bool,
+    }
+
+    struct State {
+        taken: bool,
+        restore_interrupts: bool,
+        encoder: defmt::Encoder,
+        frame: Frame,
+    }
+
+    struct Global(UnsafeCell<State>);
+
+    // Only accessed between `acquire` and `release`, with interrupts disabled.
+    unsafe impl Sync for Global {}
+
+    static STATE: Global = Global(UnsafeCell::new(State {
+        taken: false,
+        restore_interrupts: false,
+        encoder: defmt::Encoder::new(),
+        frame: Frame {
+            buf: [0; DEFMT_FRAME_LEN],
+            len: 0,
+            overflow: false,
+        },
+    }));
+
+    impl Frame {
+        fn push(&mut self, bytes: &[u8]) {
+            match self.buf.get_mut(self.len..self.len + bytes.len()) {
+                Some(room) if !self.overflow => {
+                    room.copy_from_slice(bytes);
+                    self.len += bytes.len();
+                }
+                _ => self.overflow = true,
+            }
+        }
+    }
+
+    #[defmt::global_logger]
+    struct AdvTraceLogger;
+
+    unsafe impl defmt::Logger for AdvTraceLogger {
+        fn acquire() {
+            let primask = cortex_m::register::primask::read();
+            cortex_m::interrupt::disable();
+
+            let state = unsafe { &mut *STATE.0.get() };
+            if state.taken {
+                panic!("defmt logger taken reentrantly");
+            }
+            state.taken = true;
+            state.restore_interrupts = primask.is_active();
+            state.frame.len = 0;
+            state.frame.overflow = false;
+            let frame = &mut state.frame;
+            state.encoder.start_frame(|bytes| frame.push(bytes));
+        }
+
+        unsafe fn flush() {}
+
+        unsafe fn release() {
+            let state = unsafe { &mut *STATE.0.get() };
+            let frame = &mut state.frame;
+            state.encoder.end_frame(|bytes| frame.push(bytes));
+            if !frame.overflow {
+                // A single send: the FIFO takes the frame whole or drops it.
+                let _ = super::send(&frame.buf[..frame.len]);
+            }
+            state.taken = false;
+            if state.restore_interrupts {
+                unsafe { cortex_m::interrupt::enable() };
+            }
+        }
+
+        unsafe fn write(bytes: &[u8]) {
+            let state = unsafe { &mut *STATE.0.get() };
+            let frame = &mut state.frame;
+            state.encoder.write(bytes, |bytes| frame.push(bytes));
+        }
+    }
+}
@@ -0,0 +1,410 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::FloatAbi;
+
+// Shared with the emitted build.rs, which also checks duplicate symbols.
+#[path = "../res/archive_index.rs"]
+#[cfg_attr(not(test), allow(dead_code))]
+mod archive_index;
+
+/// Reads the global symbol index of the GNU-style `ar` archive at `path`,
+/// with the parser the emitted build.rs uses.
+///
+/// Returns an empty list for archives without an index or in another format.
+pub(crate) fn archive_symbols(path: &Path) -> Vec<String> {
+    fs::read(path)
+        .map(|data| archive_index::archive_symbols(&data))
+        .unwrap_or_default()
+}
+
+/// Float ABI the members of a GNU-style `ar` archive were built for, from
+/// the `Tag_ABI_VFP_args` and `Tag_FP_arch` build attributes of their ELF
+/// objects.
+///
+/// An archive mixing ABIs gets the most demanding one. Returns `None` for
+/// archives without build attributes, or that cannot be read.
+pub(crate) fn archive_float_abi(path: &Path) -> Option<FloatAbi> {
+    let data = fs::read(path).ok()?;
+    let mut rest = data.strip_prefix(b"!<arch>\n")?;
+
+    let mut abi = None;
+    while rest.len() >= 60 {
+        let (header, body) = rest.split_at(60);
+        let size = String::from_utf8_lossy(&header[48..58])
+            .trim()
+            .parse::<usize>()
+            .ok()?;
+        let member = body.get(..size)?;
+        rest = body.get(size + size % 2..).unwrap_or_default();
+
+        let member_abi = match object_attributes(member) {
+            // VFP registers carry floating-point arguments.
+            Some((_, 1)) => Some(FloatAbi::Hard),
+            // Compatible with both calling conventions.
+            Some((_, 3)) => None,
+            Some((fp_arch, _)) if fp_arch != 0 => Some(FloatAbi::SoftFp),
+            Some(_) => Some(FloatAbi::Soft),
+            None => None,
+        };
+        abi = abi.max(member_abi);
+    }
+    abi
+}
+
+/// `Tag_FP_arch` and `Tag_ABI_VFP_args` of a 32-bit little-endian ELF
+/// object, both defaulting to 0, or `None` if it has no build attributes.
+fn object_attributes(object: &[u8]) -> Option<(u64, u64)> {
+    const SHT_ARM_ATTRIBUTES: u32 = 0x7000_0003;
+    const TAG_FP_ARCH: u64 = 10;
+    const TAG_ABI_VFP_ARGS: u64 = 28;
+
+    let u16_at = |at: usize| {
+        let bytes = object.get(at..at + 2)?;
+        Some(usize::from(u16::from_le_bytes([bytes[0], bytes[1]])))
+    };
+    let u32_at = |data: &[u8], at: usize| {
+        let bytes = data.get(at..at + 4)?;
+        Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    };
+
+    // 32-bit, little-endian ELF.
+    if object.get(..6)? != b"\x7fELF\x01\x01" {
+        return None;
+    }
+    let section_headers = u32_at(object, 0x20)? as usize;
+    let (entry_size, count) = (u16_at(0x2e)?, u16_at(0x30)?);
+    let attributes = (0..count).find_map(|index| {
+        let header = object.get(section_headers + index * entry_size..)?;
+        if u32_at(header, 4)? != SHT_ARM_ATTRIBUTES {
+            return None;
+        }
+        let (offset, size) = (u32_at(header, 16)? as usize, u32_at(header, 20)? as usize);
+        object.get(offset..offset + size)
+    })?;
+
+    // Format version 'A', then the "aeabi" subsection with its file-scope
+    // attributes, in increasing tag order.
+    let mut data = attributes.strip_prefix(b"A")?;
+    let mut found = None;
+    while data.len() >= 4 {
+        let length = u32_at(data, 0)? as usize;
+        let subsection = data.get(4..length)?;
+        data = &data[length..];
+        let Some(mut attrs) = subsection.strip_prefix(b"aeabi\0") else {
+            continue;
+        };
+        // File scope: tag 1 and its size, including both.
+        if attrs.first() != Some(&1) {
+            continue;
+        }
+        let size = u32_at(attrs, 1)? as usize;
+        attrs = attrs.get(5..size)?;
+
+        let (mut fp_arch, mut vfp_args) = (0, 0);
+        while let Some(tag) = read_uleb128(&mut attrs) {
+            if tag > TAG_ABI_VFP_ARGS {
+                break;
+            }
+            // `Tag_CPU_raw_name` and `Tag_CPU_name` are strings, every
+            // other tag up to `Tag_ABI_VFP_args` a number.
+            if tag == 4 || tag == 5 {
+                let end = attrs.iter().position(|byte| *byte == 0)?;
+                attrs = &attrs[end + 1..];
+                continue;
+            }
+            let value = read_uleb128(&mut attrs)?;
+            match tag {
+                TAG_FP_ARCH => fp_arch = value,
+                TAG_ABI_VFP_ARGS => vfp_args = value,
+                _ => {}
+            }
+        }
+        found = Some((fp_arch, vfp_args));
+    }
+    found
+}
+
+fn read_uleb128(data: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    for (index, byte) in data.iter().enumerate() {
+        value |= u64::from(byte & 0x7f) << (7 * index.min(9));
+        if byte & 0x80 == 0 {
+            *data = &data[index + 1..];
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Lists every `.a` file below `path`, or `path` itself if it is one.
+pub(crate) fn find_archives(path: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut archives = Vec::new();
+    if path.is_file() {
+        if path.extension().is_some_and(|ext| ext == "a") {
+            archives.push(path.to_path_buf());
+        }
+        return Ok(archives);
+    }
+    for entry in fs::read_dir(path)? {
+        archives.extend(find_archives(&entry?.path())?);
+    }
+    archives.sort();
+    Ok(archives)
+}
+
+/// Lists the symbols `archive` references without defining them, using
+/// `arm-none-eabi-nm`.
+pub(crate) fn undefined_symbols(archive: &Path) -> io::Result<Vec<String>> {
+    let output = Command::new(crate::toolchain::gnu_tool("nm"))
+        .args(["--undefined-only", "--portability"])
+        .arg(archive)
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "arm-none-eabi-nm failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    // Member headers end with `:`; symbol lines start with the name.
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| !line.ends_with(':'))
+        .filter_map(|line| line.split_whitespace().next())
+        .map(str::to_owned)
+        .collect())
+}
+
+/// Rewrites `archive` in place with `arm-none-eabi-objcopy` so its content
+/// only depends on its members: timestamps, owners and modes are zeroed.
+/// Optionally drops debug information and makes the `localize` symbols
+/// local to their object file.
+pub(crate) fn postprocess(
+    archive: &Path,
+    strip_debug: bool,
+    localize: &[String],
+) -> io::Result<()> {
+    let mut command = Command::new(crate::toolchain::gnu_tool("objcopy"));
+    command.arg("--enable-deterministic-archives");
+    if strip_debug {
+        command.arg("--strip-debug");
+    }
+
+    let symbols_file = archive.with_extension("localize");
+    if !localize.is_empty() {
+        fs::write(&symbols_file, localize.join("\n"))?;
+        command.arg(format!("--localize-symbols={}", symbols_file.display()));
+    }
+
+    let output = command.arg(archive).output();
+    if !localize.is_empty() {
+        fs::remove_file(&symbols_file)?;
+    }
+    let output = output?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "arm-none-eabi-objcopy failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An `ar` member with a `name` of at most 16 bytes.
+    fn member(name: &str, data: &[u8]) -> Vec<u8> {
+        let mut member = format!(
+            "{name:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+            0,
+            0,
+            0,
+            644,
+            data.len()
+        )
+        .into_bytes();
+        member.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            member.push(b'\n');
+        }
+        member
+    }
+
+    fn archive(members: &[Vec<u8>]) -> Vec<u8> {
+        let mut archive = b"!<arch>\n".to_vec();
+        for member in members {
+            archive.extend_from_slice(member);
+        }
+        archive
+    }
+
+    fn write_temp(dir: &tempfile::TempDir, name: &str, data: &[u8]) -> PathBuf {
+        let path = dir.path().join(name);
+        fs::write(&path, data).unwrap();
+        path
+    }
+
+    /// A 32-bit ELF object whose only section holds the build attributes
+    /// `Tag_CPU_name`, `Tag_FP_arch` and `Tag_ABI_VFP_args`.
+    fn object(fp_arch: u8, vfp_args: u8) -> Vec<u8> {
+        let mut attrs = vec![5];
+        attrs.extend_from_slice(b"Cortex-M33\0");
+        attrs.extend_from_slice(&[10, fp_arch, 28, vfp_args]);
+        let mut file_scope = vec![1];
+        file_scope.extend_from_slice(&(5 + attrs.len() as u32).to_le_bytes());
+        file_scope.extend_from_slice(&attrs);
+        let mut section = b"A".to_vec();
+        section.extend_from_slice(&(4 + 6 + file_scope.len() as u32).to_le_bytes());
+        section.extend_from_slice(b"aeabi\0");
+        section.extend_from_slice(&file_scope);
+
+        let mut object = vec![0; 52];
+        object[..7].copy_from_slice(b"\x7fELF\x01\x01\x01");
+        object.extend_from_slice(&section);
+        let section_headers = object.len() as u32;
+        object[0x20..0x24].copy_from_slice(&section_headers.to_le_bytes());
+        object[0x2e..0x30].copy_from_slice(&40u16.to_le_bytes());
+        object[0x30..0x32].copy_from_slice(&2u16.to_le_bytes());
+        object.extend_from_slice(&[0; 40]);
+        let mut header = [0; 40];
+        header[4..8].copy_from_slice(&0x7000_0003u32.to_le_bytes());
+        header[16..20].copy_from_slice(&52u32.to_le_bytes());
+        header[20..24].copy_from_slice(&(section.len() as u32).to_le_bytes());
+        object.extend_from_slice(&header);
+        object
+    }
+
+    /// A symbol index member named `name`, with `word`-byte big-endian
+    /// count and offsets.
+    fn symbol_index(name: &str, word: usize, symbols: &[&str]) -> Vec<u8> {
+        let count = symbols.len() as u64;
+        let mut data = count.to_be_bytes()[8 - word..].to_vec();
+        for _ in symbols {
+            data.extend_from_slice(&vec![0; word]);
+        }
+        for symbol in symbols {
+            data.extend_from_slice(symbol.as_bytes());
+            data.push(0);
+        }
+        member(name, &data)
+    }
+
+    #[test]
+    fn reads_32_bit_symbol_index() {
+        let data = archive(&[
+            symbol_index("/", 4, &["ll_sys_init", "HW_AES_Init"]),
+            member("x.o/", b"object"),
+        ]);
+        assert_eq!(
+            archive_index::archive_symbols(&data),
+            ["ll_sys_init", "HW_AES_Init"]
+        );
+    }
+
+    #[test]
+    fn reads_64_bit_symbol_index() {
+        let data = archive(&[symbol_index("/SYM64/", 8, &["aci_gap_init", "hci_reset"])]);
+        assert_eq!(
+            archive_index::archive_symbols(&data),
+            ["aci_gap_init", "hci_reset"]
+        );
+    }
+
+    #[test]
+    fn archives_without_index_have_no_symbols() {
+        let truncated = {
+            let mut data = archive(&[symbol_index("/", 4, &["ll_sys_init"])]);
+            data.truncate(80);
+            data
+        };
+        for data in [
+            archive(&[member("x.o/", b"object")]),
+            b"not an archive".to_vec(),
+            truncated,
+        ] {
+            assert!(archive_index::archive_symbols(&data).is_empty());
+        }
+    }
+
+    #[test]
+    fn reads_symbols_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_temp(
+            &dir,
+            "libx.a",
+            &archive(&[symbol_index("/", 4, &["ll_sys_init"])]),
+        );
+        assert_eq!(archive_symbols(&path), ["ll_sys_init"]);
+        assert!(archive_symbols(&dir.path().join("missing.a")).is_empty());
+    }
+
+    #[test]
+    fn duplicate_symbols_are_grouped_by_library_pair() {
+        let library = |name: &str, symbols: &[&str]| {
+            (
+                name.to_owned(),
+                symbols.iter().map(|symbol| symbol.to_string()).collect(),
+            )
+        };
+        let conflicts = archive_index::duplicate_symbols(&[
+            library("ble_full", &["ll_init", "ble_init", "ble_init"]),
+            library("ble_basic", &["ll_init", "ble_init"]),
+            library("mac", &["ll_init", "mac_init"]),
+        ]);
+        let conflicts = conflicts
+            .iter()
+            .map(|((first, second), symbols)| (first.as_str(), second.as_str(), symbols.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            conflicts,
+            [
+                (
+                    "ble_full",
+                    "ble_basic",
+                    vec!["ble_init".to_owned(), "ll_init".to_owned()]
+                ),
+                ("ble_full", "mac", vec!["ll_init".to_owned()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn float_abi_follows_build_attributes() {
+        let dir = tempfile::tempdir().unwrap();
+        let cases = [
+            ("hard", object(7, 1), Some(FloatAbi::Hard)),
+            ("softfp", object(7, 0), Some(FloatAbi::SoftFp)),
+            ("soft", object(0, 0), Some(FloatAbi::Soft)),
+            ("either", object(0, 3), None),
+            ("none", b"not an object".to_vec(), None),
+        ];
+        for (name, object, abi) in cases {
+            let path = write_temp(
+                &dir,
+                &format!("{name}.a"),
+                &archive(&[member("x.o/", &object)]),
+            );
+            assert_eq!(archive_float_abi(&path), abi, "{name}");
+        }
+    }
+
+    #[test]
+    fn float_abi_of_mixed_archive_is_the_most_demanding() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_temp(
+            &dir,
+            "mixed.a",
+            &archive(&[
+                member("soft.o/", &object(0, 0)),
+                member("hard.o/", &object(7, 1)),
+                member("softfp.o/", &object(7, 0)),
+            ]),
+        );
+        assert_eq!(archive_float_abi(&path), Some(FloatAbi::Hard));
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use regex::Regex;
+
+use crate::CUBE_WBA_TAG;
+
+/// Number of directories suggested for a header missing from a spec.
+const MAX_HEADER_MATCHES: usize = 3;
+
+/// Explains the common causes of a failed bindgen run for a `BindingSpec`,
+/// naming the spec field to fix.
+///
+/// `cube_version` is the detected version of the STM32CubeWBA sources.
+pub(crate) fn hints(
+    error: &bindgen::BindgenError,
+    sources_dir: &Path,
+    cube_version: Option<&str>,
+) -> Vec<String> {
+    let mut hints = Vec::new();
+
+    let bindgen::BindgenError::ClangDiagnostic(errors) = error else {
+        if let bindgen::BindgenError::NotExist(header) = error {
+            hints.push(format!(
+                "`header` points at {}, which does not exist",
+                header.display()
+            ));
+        }
+        return hints;
+    };
+
+    let missing_header = Regex::new(r"fatal error: '([^']+)' file not found").unwrap();
+    let mut headers = BTreeSet::new();
+    for captures in missing_header.captures_iter(errors) {
+        headers.insert(captures[1].to_owned());
+    }
+    for header in headers {
+        let dirs = find_header(sources_dir, &header);
+        if dirs.is_empty() {
+            hints.push(format!(
+                "`{header}` is not in the sources; it may come from a newer STM32CubeWBA or from the application, \
+                 in which case add it to the spec's `header` in stm32-bindings-gen/inc"
+            ));
+        } else {
+            hints.push(format!(
+                "`{header}` is not found: add its directory to `include_dirs`, e.g. {}",
+                dirs.join(" or ")
+            ));
+        }
+    }
+
+    let undeclared =
+        Regex::new(r"error: (?:unknown type name|use of undeclared identifier) '([^']+)'").unwrap();
+    let mut names = BTreeSet::new();
+    for captures in undeclared.captures_iter(errors) {
+        names.insert(captures[1].to_owned());
+    }
+    for name in names {
+        let is_macro = name
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+        if is_macro {
+            hints.push(format!(
+                "`{name}` looks like a configuration macro: define it in `clang_args` (`-D{name}=...`)"
+            ));
+        } else {
+            hints.push(format!(
+                "`{name}` is declared by a header the spec does not include: check `header` and `include_dirs`"
+            ));
+        }
+    }
+
+    // `#error` directives, which vendor headers use to demand a configuration.
+    let directive = Regex::new(r#"error: "([^"]*)""#).unwrap();
+    for captures in directive.captures_iter(errors) {
+        hints.push(format!(
+            "a `#error` fired (\"{}\"): it usually asks for a `-D` define in `clang_args`",
+            &captures[1]
+        ));
+    }
+
+    if let Some(version) = cube_version
+        && CUBE_WBA_TAG.trim_start_matches('v') != version
+    {
+        hints.push(format!(
+            "the sources are STM32CubeWBA {version}, but the specs are written for {CUBE_WBA_TAG}: \
+             run with --fetch to download it"
+        ));
+    }
+
+    hints
+}
+
+/// Directories of `sources_dir` containing `header`, relative to it.
+fn find_header(sources_dir: &Path, header: &str) -> Vec<String> {
+    let mut found = Vec::new();
+    let mut pending = vec![sources_dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.filter_map(Result::ok) {
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.ends_with(header)
+                && let Some(include_dir) = path
+                    .to_str()
+                    .and_then(|path| path.strip_suffix(header))
+                    .map(|dir| Path::new(dir.trim_end_matches(['/', '\\'])))
+            {
+                let relative = include_dir.strip_prefix(sources_dir).unwrap_or(include_dir);
+                found.push(format!("\"{}\"", relative.display()));
+                if found.len() == MAX_HEADER_MATCHES {
+                    return found;
+                }
+            }
+        }
+    }
+    found
+}
+
+/// Renders the clang invocation bindgen performs, for `--verbose-clang`.
+pub(crate) fn command_line(header: &str, args: &[String]) -> String {
+    let quote = |arg: &str| {
+        if arg.contains([' ', '"']) {
+            format!("\"{}\"", arg.replace('"', "\\\""))
+        } else {
+            arg.to_owned()
+        }
+    };
+    let mut line = String::from("clang");
+    for arg in args.iter().map(String::as_str).chain([header]) {
+        line.push(' ');
+        line.push_str(&quote(arg));
+    }
+    line
+}
@@ -0,0 +1,138 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// STM32CubeWBA release the binding specs are written against.
+pub const CUBE_WBA_TAG: &str = "v1.8.0";
+
+/// SHA-256 of the [`CUBE_WBA_TAG`] source archive, to be updated with it.
+/// `--sha256` overrides it.
+///
+/// Until it is recorded, `--sha256` must give a digest obtained
+/// independently of the download, e.g. from a trusted mirror: an archive is
+/// never extracted unverified, and the digest of the downloaded archive
+/// itself proves nothing.
+pub const CUBE_WBA_SHA256: Option<&str> = None;
+
+const CUBE_WBA_ARCHIVE_URL: &str =
+    "https://github.com/STMicroelectronics/STM32CubeWBA/archive/refs/tags";
+
+/// Downloads the pinned STM32CubeWBA release into `dest`.
+///
+/// The archive is checked against `expected_sha256`, or else against
+/// [`CUBE_WBA_SHA256`]. An existing `dest` is replaced only after a
+/// successful download, verification and extraction.
+pub fn fetch_sources(dest: &Path, expected_sha256: Option<&str>) {
+    let url = format!("{CUBE_WBA_ARCHIVE_URL}/{CUBE_WBA_TAG}.tar.gz");
+    let Some(expected) = expected_sha256.or(CUBE_WBA_SHA256) else {
+        panic!(
+            "No checksum is pinned for STM32CubeWBA {CUBE_WBA_TAG}: pass --sha256 with the digest \
+             of {url} obtained from a trusted source, or record it in CUBE_WBA_SHA256"
+        );
+    };
+    // Extract next to `dest`, so it can be moved in place with a rename.
+    let parent = dest
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    fs::create_dir_all(parent)
+        .unwrap_or_else(|err| panic!("Unable to create {}: {err}", parent.display()));
+    let workdir = tempfile::tempdir_in(parent).unwrap_or_else(|err| {
+        panic!(
+            "Unable to create a temporary directory in {}: {err}",
+            parent.display()
+        )
+    });
+    let archive = workdir.path().join("STM32CubeWBA.tar.gz");
+
+    println!("Fetching STM32CubeWBA {CUBE_WBA_TAG} from {url}");
+    run(Command::new("curl")
+        .args([
+            "--fail",
+            "--location",
+            "--silent",
+            "--show-error",
+            "--output",
+        ])
+        .arg(&archive)
+        .arg(&url));
+
+    let digest = sha256_file(&archive);
+    if !expected.trim().eq_ignore_ascii_case(&digest) {
+        panic!(
+            "Checksum mismatch for {url}: expected {}, got {digest}",
+            expected.trim()
+        );
+    }
+    println!("  checksum verified ({digest})");
+
+    let extract_dir = workdir.path().join("extract");
+    fs::create_dir_all(&extract_dir).expect("Unable to create extraction directory");
+    run(Command::new("tar")
+        .arg("-xzf")
+        .arg(&archive)
+        .arg("-C")
+        .arg(&extract_dir));
+
+    let mut roots = fs::read_dir(&extract_dir)
+        .expect("Unable to read extracted archive")
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_dir());
+    let root = match (roots.next(), roots.next()) {
+        (Some(root), None) => root,
+        _ => panic!("Unexpected layout in {url}: expected a single top-level directory"),
+    };
+
+    if dest.exists() {
+        fs::remove_dir_all(dest)
+            .unwrap_or_else(|err| panic!("Unable to remove {}: {err}", dest.display()));
+    }
+    fs::rename(&root, dest).unwrap_or_else(|err| {
+        panic!(
+            "Unable to move {} to {}: {err}",
+            root.display(),
+            dest.display()
+        )
+    });
+}
+
+fn sha256_file(path: &Path) -> String {
+    let candidates: [(&str, &[&str]); 2] = [("sha256sum", &[]), ("shasum", &["-a", "256"])];
+    for (tool, args) in candidates {
+        let Ok(output) = Command::new(tool).args(args).arg(path).output() else {
+            continue;
+        };
+        if !output.status.success() {
+            continue;
+        }
+        if let Some(digest) = String::from_utf8_lossy(&output.stdout)
+            .split_whitespace()
+            .next()
+        {
+            return digest.to_ascii_lowercase();
+        }
+    }
+    // Windows ships certutil, which prints the digest alone on the second
+    // line, with spaces between bytes on older versions.
+    if let Ok(output) = Command::new("certutil")
+        .arg("-hashfile")
+        .arg(path)
+        .arg("SHA256")
+        .output()
+        && output.status.success()
+        && let Some(line) = String::from_utf8_lossy(&output.stdout).lines().nth(1)
+    {
+        return line.replace(' ', "").to_ascii_lowercase();
+    }
+    panic!("Unable to compute SHA-256: none of sha256sum, shasum or certutil is available");
+}
+
+fn run(command: &mut Command) {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let status = command
+        .status()
+        .unwrap_or_else(|err| panic!("Unable to run {program}: {err}"));
+    if !status.success() {
+        panic!("{program} exited with {status}");
+    }
+}
@@ -0,0 +1,339 @@
+use std::collections::BTreeMap;
+
+use quote::format_ident;
+
+/// Integer, float and C types, `Copy` in every module.
+const PRIMITIVES: &[&str] = &[
+    "bool",
+    "u8",
+    "u16",
+    "u32",
+    "u64",
+    "u128",
+    "usize",
+    "i8",
+    "i16",
+    "i32",
+    "i64",
+    "i128",
+    "isize",
+    "f32",
+    "f64",
+    "c_char",
+    "c_schar",
+    "c_uchar",
+    "c_short",
+    "c_ushort",
+    "c_int",
+    "c_uint",
+    "c_long",
+    "c_ulong",
+    "c_longlong",
+    "c_ulonglong",
+    "c_float",
+    "c_double",
+];
+
+/// Generates `<name>_load` and `<name>_store` functions accessing each
+/// mutable global of `bindings` inside a critical section.
+///
+/// The vendor libraries share configuration and state through such
+/// globals. Accessing them from several interrupt priorities is only sound
+/// on a single core with the interrupts masked, which the accessors do, so
+/// they are safe to call. Globals whose type is not `Copy` are left out, as
+/// they cannot be read by value.
+///
+/// Values holding pointers, function pointers included, are dereferenced or
+/// called by the vendor code, which the critical section does not make
+/// valid, so their store functions stay `unsafe`.
+///
+/// The critical section is `cortex_m::interrupt::free` rather than the
+/// `critical-section` crate: the emitted crate only targets the single-core
+/// STM32WBA, and uses it everywhere else, so applications do not have to
+/// provide a `critical-section` implementation for it.
+pub(crate) fn accessors(module: &str, bindings: &str) -> String {
+    let file = syn::parse_file(bindings)
+        .unwrap_or_else(|err| panic!("Unable to parse generated bindings for {module}: {err}"));
+
+    let mut definitions = BTreeMap::new();
+    for item in &file.items {
+        let ident = match item {
+            syn::Item::Struct(item) => &item.ident,
+            syn::Item::Union(item) => &item.ident,
+            syn::Item::Enum(item) => &item.ident,
+            syn::Item::Type(item) => &item.ident,
+            syn::Item::Fn(item) => &item.sig.ident,
+            syn::Item::ForeignMod(foreign) => {
+                for foreign_item in &foreign.items {
+                    match foreign_item {
+                        syn::ForeignItem::Fn(func) => {
+                            definitions.insert(func.sig.ident.to_string(), None);
+                        }
+                        syn::ForeignItem::Static(stat) => {
+                            definitions.insert(stat.ident.to_string(), None);
+                        }
+                        _ => {}
+                    }
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        definitions.insert(ident.to_string(), Some(item));
+    }
+
+    let mut items: Vec<syn::Item> = Vec::new();
+    for foreign in file.items.iter().filter_map(|item| match item {
+        syn::Item::ForeignMod(foreign) => Some(foreign),
+        _ => None,
+    }) {
+        for foreign_item in &foreign.items {
+            let syn::ForeignItem::Static(stat) = foreign_item else {
+                continue;
+            };
+            if !matches!(stat.mutability, syn::StaticMutability::Mut(_))
+                || !is_copy(&stat.ty, &definitions)
+            {
+                continue;
+            }
+            let (name, ty) = (&stat.ident, &stat.ty);
+            let load = format_ident!("{name}_load");
+            let store = format_ident!("{name}_store");
+            if definitions.contains_key(&load.to_string())
+                || definitions.contains_key(&store.to_string())
+            {
+                continue;
+            }
+            let load_doc = format!(" Reads [`{name}`] inside a critical section.");
+            let store_doc = format!(" Writes [`{name}`] inside a critical section.");
+            items.push(syn::parse_quote! {
+                #[doc = #load_doc]
+                pub fn #load() -> #ty {
+                    ::cortex_m::interrupt::free(|_| unsafe { #name })
+                }
+            });
+            if holds_pointer(ty, &definitions) {
+                items.push(syn::parse_quote! {
+                    #[doc = #store_doc]
+                    ///
+                    /// # Safety
+                    ///
+                    /// The vendor code dereferences or calls the pointers in
+                    /// `value`, which must stay valid for as long as it uses
+                    /// them.
+                    pub unsafe fn #store(value: #ty) {
+                        ::cortex_m::interrupt::free(|_| unsafe { #name = value })
+                    }
+                });
+            } else {
+                items.push(syn::parse_quote! {
+                    #[doc = #store_doc]
+                    pub fn #store(value: #ty) {
+                        ::cortex_m::interrupt::free(|_| unsafe { #name = value })
+                    }
+                });
+            }
+        }
+    }
+
+    if items.is_empty() {
+        return String::new();
+    }
+    let accessors = syn::File {
+        shebang: None,
+        attrs: Vec::new(),
+        items,
+    };
+    format!("\n{}", prettyplease::unparse(&accessors))
+}
+
+/// Whether values of `ty` can be copied out of a global. Types from other
+/// modules count as not `Copy`.
+fn is_copy(ty: &syn::Type, definitions: &BTreeMap<String, Option<&syn::Item>>) -> bool {
+    match ty {
+        syn::Type::Ptr(_) | syn::Type::BareFn(_) => true,
+        syn::Type::Array(array) => is_copy(&array.elem, definitions),
+        syn::Type::Paren(paren) => is_copy(&paren.elem, definitions),
+        syn::Type::Path(path) if path.qself.is_none() => {
+            let Some(last) = path.path.segments.last() else {
+                return false;
+            };
+            let name = last.ident.to_string();
+            if PRIMITIVES.contains(&name.as_str()) {
+                return true;
+            }
+            if name == "Option" {
+                let syn::PathArguments::AngleBracketed(args) = &last.arguments else {
+                    return false;
+                };
+                return args.args.iter().all(|arg| match arg {
+                    syn::GenericArgument::Type(ty) => is_copy(ty, definitions),
+                    _ => false,
+                });
+            }
+            if path.path.segments.len() > 1 {
+                return false;
+            }
+            match definitions.get(&name).copied().flatten() {
+                Some(syn::Item::Type(alias)) => is_copy(&alias.ty, definitions),
+                Some(
+                    syn::Item::Struct(syn::ItemStruct { attrs, .. })
+                    | syn::Item::Union(syn::ItemUnion { attrs, .. })
+                    | syn::Item::Enum(syn::ItemEnum { attrs, .. }),
+                ) => derives_copy(attrs),
+                _ => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+/// Whether values of `ty` hold a pointer or function pointer. Types from
+/// other modules count as holding one.
+fn holds_pointer(ty: &syn::Type, definitions: &BTreeMap<String, Option<&syn::Item>>) -> bool {
+    match ty {
+        syn::Type::Ptr(_) | syn::Type::BareFn(_) => true,
+        syn::Type::Array(array) => holds_pointer(&array.elem, definitions),
+        syn::Type::Paren(paren) => holds_pointer(&paren.elem, definitions),
+        syn::Type::Path(path) if path.qself.is_none() => {
+            let Some(last) = path.path.segments.last() else {
+                return true;
+            };
+            let name = last.ident.to_string();
+            if PRIMITIVES.contains(&name.as_str()) {
+                return false;
+            }
+            if name == "Option" {
+                let syn::PathArguments::AngleBracketed(args) = &last.arguments else {
+                    return true;
+                };
+                return args.args.iter().any(|arg| match arg {
+                    syn::GenericArgument::Type(ty) => holds_pointer(ty, definitions),
+                    _ => true,
+                });
+            }
+            if path.path.segments.len() > 1 {
+                return true;
+            }
+            match definitions.get(&name).copied().flatten() {
+                Some(syn::Item::Type(alias)) => holds_pointer(&alias.ty, definitions),
+                Some(syn::Item::Struct(item)) => item
+                    .fields
+                    .iter()
+                    .any(|field| holds_pointer(&field.ty, definitions)),
+                Some(syn::Item::Union(item)) => item
+                    .fields
+                    .named
+                    .iter()
+                    .any(|field| holds_pointer(&field.ty, definitions)),
+                Some(syn::Item::Enum(_)) => false,
+                _ => true,
+            }
+        }
+        _ => true,
+    }
+}
+
+fn derives_copy(attrs: &[syn::Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        attr.path().is_ident("derive")
+            && attr
+                .parse_args_with(
+                    syn::punctuated::Punctuated::<syn::Path, syn::Token![,]>::parse_terminated,
+                )
+                .is_ok_and(|paths| paths.iter().any(|path| path.is_ident("Copy")))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::accessors;
+
+    #[test]
+    fn copyable_mutable_globals_get_accessors() {
+        let bindings = r#"
+            pub type config_t = u8;
+            #[repr(C)]
+            #[derive(Debug, Copy, Clone)]
+            pub struct params_t { pub value: u32 }
+            unsafe extern "C" {
+                pub static mut level: config_t;
+                pub static mut params: params_t;
+                pub static mut table: [*mut u8; 4usize];
+                pub static mut hook: ::core::option::Option<unsafe extern "C" fn()>;
+            }
+        "#;
+        let accessors = accessors("test", bindings);
+        for name in ["level", "params", "table", "hook"] {
+            assert!(
+                accessors.contains(&format!("pub fn {name}_load()")),
+                "{accessors}"
+            );
+        }
+        for name in ["level", "params"] {
+            assert!(
+                accessors.contains(&format!("pub fn {name}_store(value: ")),
+                "{accessors}"
+            );
+        }
+    }
+
+    #[test]
+    fn stores_of_pointers_are_unsafe() {
+        let bindings = r#"
+            pub type callback_t = ::core::option::Option<unsafe extern "C" fn()>;
+            #[repr(C)]
+            #[derive(Debug, Copy, Clone)]
+            pub struct buffer_t { pub data: *mut u8, pub len: u16 }
+            #[repr(C)]
+            #[derive(Debug, Copy, Clone)]
+            pub struct config_t { pub buffer: buffer_t }
+            unsafe extern "C" {
+                pub static mut table: [*mut u8; 4usize];
+                pub static mut hook: callback_t;
+                pub static mut config: config_t;
+                pub static mut level: u8;
+            }
+        "#;
+        let accessors = accessors("test", bindings);
+        for name in ["table", "hook", "config"] {
+            assert!(
+                accessors.contains(&format!("pub unsafe fn {name}_store(value: ")),
+                "{accessors}"
+            );
+            assert!(
+                accessors.contains(&format!("pub fn {name}_load()")),
+                "{accessors}"
+            );
+        }
+        assert!(
+            accessors.contains("pub fn level_store(value: u8)"),
+            "{accessors}"
+        );
+    }
+
+    #[test]
+    fn immutable_and_non_copy_globals_are_skipped() {
+        let bindings = r#"
+            #[repr(C)]
+            pub struct buffer_t { pub data: [u8; 16usize] }
+            unsafe extern "C" {
+                pub static version: u32;
+                pub static mut buffer: buffer_t;
+                pub static mut shared: common::shared_t;
+            }
+        "#;
+        assert_eq!(accessors("test", bindings), "");
+    }
+
+    #[test]
+    fn existing_names_are_not_shadowed() {
+        let bindings = r#"
+            unsafe extern "C" {
+                pub static mut state: u32;
+                pub fn state_load() -> u32;
+            }
+        "#;
+        assert_eq!(accessors("test", bindings), "");
+    }
+}
@@ -1,9 +1,30 @@
-use bindgen::callbacks::{ItemInfo, ItemKind, ParseCallbacks};
-use std::collections::BTreeSet;
-use std::io::{self, Write};
+use bindgen::callbacks::{AttributeInfo, ItemInfo, ItemKind, ParseCallbacks, TypeKind};
+use regex::Regex;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
-use std::{env, fs};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+use std::{env, fs, mem, panic, thread};
+
+mod archive;
+mod diagnostics;
+mod fetch;
+mod globals;
+mod licenses;
+mod manifest;
+mod report;
+mod shared_types;
+mod split;
+mod toolchain;
+
+pub use fetch::{CUBE_WBA_SHA256, CUBE_WBA_TAG, fetch_sources};
+pub use toolchain::Libc;
+
+use manifest::Manifest;
+use split::SplitArea;
 
 const STD_TO_CORE_REPLACEMENTS: &[(&str, &str)] = &[
     ("::std::mem::", "::core::mem::"),
@@ -16,18 +37,152 @@ const STD_TO_CORE_REPLACEMENTS: &[(&str, &str)] = &[
     (":: std :: ptr ::", ":: core :: ptr ::"),
 ];
 
+/// Name of the emitted crate.
+const CRATE_NAME: &str = "stm32-bindings";
+
+/// Lints denied in the generated modules with `Options::audit`.
+const AUDIT_LINTS: &str = "#![deny(\n    clippy::panic,\n    clippy::unwrap_used,\n    clippy::expect_used,\n    clippy::indexing_slicing,\n    clippy::cast_possible_truncation,\n    clippy::cast_sign_loss,\n    clippy::cast_possible_wrap\n)]\n\n";
+
 const NEWLIB_SHARED_OPAQUES: &[&str] = &["_reent", "__sFILE", "__sFILE64"];
 
+/// C library functions and globals that newlib's `<stdio.h>` and
+/// `<string.h>` and the CMSIS core headers declare in every module including
+/// them, for the specs without an allowlist. Rust code uses `core` instead.
+const NEWLIB_CMSIS_BLOCKLIST: &[&str] = &[
+    // newlib reentrancy state.
+    "_impure_ptr",
+    "_impure_data",
+    "_global_impure_ptr",
+    "__getreent",
+    // <stdio.h>, with the `_<name>_r` reentrant variants.
+    "_?v?(?:f|s|sn|as|d)?printf(?:_r)?",
+    "_?v?(?:f|s)?scanf(?:_r)?",
+    "_?f?(?:puts|gets|putc|getc)(?:_r)?",
+    "_?(?:putchar|getchar|ungetc)(?:_r)?",
+    "f(?:open|close|flush|read|write|seek|tell|eof|error|ileno|dopen|reopen|getpos|setpos)",
+    "clearerr|perror|remove|rename|rewind|setv?buf|tmpfile|tmpnam",
+    // <string.h>
+    "mem(?:chr|cmp|cpy|move|set)",
+    "str(?:n?cat|n?cmp|n?cpy|r?chr|coll|c?spn|error|len|pbrk|str|tok|xfrm|n?dup)",
+    // CMSIS core_cm33.h
+    "ITM_RxBuffer",
+];
+
+/// CMSIS core register blocks and their `cortex-m` equivalents, used with
+/// `Options::cortex_m_types`. `FPU_Type` is missing: `cortex-m` only has it
+/// on hard-float targets.
+const CORTEX_M_TYPES: &[(&str, &str)] = &[
+    ("NVIC_Type", "nvic"),
+    ("SCB_Type", "scb"),
+    ("SCnSCB_Type", "icb"),
+    ("SysTick_Type", "syst"),
+    ("ITM_Type", "itm"),
+    ("DWT_Type", "dwt"),
+    ("TPI_Type", "tpiu"),
+    ("MPU_Type", "mpu"),
+    ("SAU_Type", "sau"),
+    ("CoreDebug_Type", "dcb"),
+];
+
 #[derive(Debug, Clone, Copy)]
 struct BindingSpec {
     module: &'static str,
     feature: Option<&'static str>,
-    header: &'static str,
+    header: Header,
     include_dirs: &'static [&'static str],
     clang_args: &'static [&'static str],
     allowlist: &'static [&'static str],
+    /// Items left out of the bindings, e.g. CMSIS or newlib definitions that
+    /// collide with another module. Blocklisted types must be provided by
+    /// whoever uses the module.
+    blocklist: &'static [&'static str],
+    /// Types emitted as opaque blobs of the right size and alignment.
+    opaque_types: &'static [&'static str],
+    /// Enums emitted as `#[repr(transparent)]` newtypes with associated consts.
+    newtype_enums: &'static [&'static str],
+    /// Enums emitted as `#[non_exhaustive]` Rust enums.
+    rustified_enums: &'static [&'static str],
     aliases: &'static [&'static str],
+    renames: &'static [RenameRule],
+    /// `static inline` functions to expose through a generated C shim.
+    inline_fns: &'static [&'static str],
+    /// Structs and enums deriving `serde::{Serialize, Deserialize}` and
+    /// `defmt::Format` behind the emitted crate's `serde` and `defmt`
+    /// features, e.g. configuration mirrored by host-side provisioning tools.
+    serde_types: &'static [&'static str],
+    /// Audited thread-safety of generated types.
+    thread_safety: &'static [ThreadSafetyRule],
     library_artifacts: &'static [LibraryArtifact],
+    /// C sources (and the headers next to them) of source-only utilities,
+    /// copied to `src/csrc/<module>/` and compiled by the emitted build.rs
+    /// when the spec's feature is enabled.
+    c_sources: &'static [&'static str],
+    /// Areas of the bindings emitted as submodules behind their own features
+    /// of the emitted crate, to cut the compile time of large modules.
+    split: &'static [SplitArea],
+}
+
+/// The C header bindgen reads for a spec.
+#[derive(Debug, Clone, Copy)]
+enum Header {
+    /// A handwritten wrapper in `stm32-bindings-gen/inc`, for headers that
+    /// need more than includes.
+    File(&'static str),
+    /// Headers a wrapper is generated for, included in order. Names in angle
+    /// brackets are system headers; other names are quoted.
+    Includes(&'static [&'static str]),
+}
+
+/// Replaces `prefix` with `replacement` in the Rust name of matching items.
+///
+/// bindgen adds `#[link_name]` to renamed functions and statics, so the
+/// linked symbol stays the C name.
+#[derive(Debug, Clone, Copy)]
+struct RenameRule {
+    kind: RenameKind,
+    prefix: &'static str,
+    replacement: &'static str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RenameKind {
+    Function,
+    Var,
+}
+
+/// Records whether values of the types matching `pattern` may cross
+/// executor tasks or interrupt contexts.
+///
+/// `reason` is added to the type's documentation, so the audit is visible
+/// to users of the generated crate.
+#[derive(Debug, Clone, Copy)]
+struct ThreadSafetyRule {
+    pattern: &'static str,
+    safety: ThreadSafety,
+    reason: &'static str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ThreadSafety {
+    /// Emits `unsafe impl Send`.
+    Send,
+    /// Emits `unsafe impl Send` and `unsafe impl Sync`.
+    Sync,
+    /// Must stay on the context that created it; users wrap it in
+    /// `stm32_bindings::markers::Local`.
+    Local,
+}
+
+impl ThreadSafety {
+    fn describe(self) -> &'static str {
+        match self {
+            ThreadSafety::Send => "may be sent to another task or interrupt context",
+            ThreadSafety::Sync => "may be sent and shared between tasks and interrupt contexts",
+            ThreadSafety::Local => {
+                "must stay on the context that created it; wrap it in [`crate::markers::Local`]"
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -36,11 +191,75 @@ struct LibraryArtifact {
     destination: &'static str,
 }
 
+/// Floating-point calling convention and FPU use a prebuilt archive was
+/// compiled for, read from the build attributes of its objects.
+///
+/// Ordered from the least to the most demanding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum FloatAbi {
+    /// No FPU instructions, arguments in core registers.
+    Soft,
+    /// FPU instructions, arguments in core registers.
+    SoftFp,
+    /// Arguments in FPU registers.
+    Hard,
+}
+
+impl FloatAbi {
+    fn as_str(self) -> &'static str {
+        match self {
+            FloatAbi::Soft => "soft",
+            FloatAbi::SoftFp => "softfp",
+            FloatAbi::Hard => "hard",
+        }
+    }
+}
+
+/// A directory of copied archives, relative to the generated crate root.
+///
+/// Directories without a float ABI are linked for every target.
+#[derive(Debug, Clone)]
+struct LinkDir {
+    path: String,
+    float_abi: Option<FloatAbi>,
+}
+
+/// Shared by the link-layer, MAC and BLE specs: differing rules would keep
+/// `Options::shared_types` from sharing the type.
+const BLE_BUFF_HDR_SAFETY: ThreadSafetyRule = ThreadSafetyRule {
+    pattern: "ble_buff_hdr_t",
+    safety: ThreadSafety::Send,
+    reason: "The stack hands packet buffers over with their ownership, e.g. from the radio interrupt to the host task.",
+};
+
 const BINDING_SPECS: &[BindingSpec] = &[
     BindingSpec {
         module: "wba_link_layer",
         feature: Some("wba_wpan"),
-        header: "stm32-bindings-gen/inc/link_layer.h",
+        header: Header::Includes(&[
+            "bsp.h",
+            "common_types.h",
+            "dtm.h",
+            "event_manager.h",
+            "evnt_schdlr_gnrc_if.h",
+            "hci.h",
+            "ll_error.h",
+            "ll_intf.h",
+            "ll_intf_cmn.h",
+            "mac_host_intf.h",
+            "mem_intf.h",
+            "os_wrapper.h",
+            "platform.h",
+            "power_table.h",
+            "pta.h",
+            "ral.h",
+            "rfd_dev_config.h",
+            "linklayer_plat.h",
+            "ll_sys_sequencer.h",
+            "ll_sys_startup.h",
+            "ll_sys.h",
+            "ll_version.h",
+        ]),
         include_dirs: &[
             "Middlewares/ST/STM32_WPAN",
             "Middlewares/ST/STM32_WPAN/mac_802_15_4/core/inc",
@@ -67,16 +286,42 @@ const BINDING_SPECS: &[BindingSpec] = &[
             "-DEXT_ADDRESS_LENGTH=8",
         ],
         allowlist: &[],
+        blocklist: NEWLIB_CMSIS_BLOCKLIST,
+        opaque_types: NEWLIB_SHARED_OPAQUES,
+        newtype_enums: &["os_timer_type"],
+        rustified_enums: &[],
         aliases: &[],
+        renames: &[RenameRule {
+            kind: RenameKind::Function,
+            prefix: "ll_intf_",
+            replacement: "",
+        }],
+        inline_fns: &[],
+        serde_types: &["antenna_diversity_st"],
+        thread_safety: &[
+            BLE_BUFF_HDR_SAFETY,
+            ThreadSafetyRule {
+                pattern: "antenna_diversity_st",
+                safety: ThreadSafety::Sync,
+                reason: "Plain configuration values, copied by the link layer when applied.",
+            },
+            ThreadSafetyRule {
+                pattern: "extrnl_evnt_st_t",
+                safety: ThreadSafety::Local,
+                reason: "The event scheduler keeps a pointer to a registered event and calls its callbacks from the registering context.",
+            },
+        ],
         library_artifacts: &[LibraryArtifact {
             source: "Middlewares/ST/STM32_WPAN/link_layer/ll_cmd_lib/lib",
             destination: "src/lib/link_layer",
         }],
+        c_sources: &[],
+        split: &[],
     },
     BindingSpec {
         module: "wba_wpan_mac",
         feature: Some("wba_wpan_mac"),
-        header: "stm32-bindings-gen/inc/wba_wpan_mac.h",
+        header: Header::File("stm32-bindings-gen/inc/wba_wpan_mac.h"),
         include_dirs: &[
             "Middlewares/ST/STM32_WPAN",
             "Middlewares/ST/STM32_WPAN/mac_802_15_4/core/inc",
@@ -91,7 +336,32 @@ const BINDING_SPECS: &[BindingSpec] = &[
         ],
         clang_args: &["-DSUPPORT_MAC=1", "-DMAC=1", "-DMAC_LAYER=1"],
         allowlist: &[],
+        blocklist: NEWLIB_CMSIS_BLOCKLIST,
+        opaque_types: NEWLIB_SHARED_OPAQUES,
+        newtype_enums: &["mac_status_enum_t"],
+        rustified_enums: &[],
         aliases: &["mac", "mac_802_15_4", "wpan_wba"],
+        renames: &[],
+        inline_fns: &[],
+        serde_types: &[],
+        thread_safety: &[
+            BLE_BUFF_HDR_SAFETY,
+            ThreadSafetyRule {
+                pattern: "ST_MAC_.*Req_t",
+                safety: ThreadSafety::Send,
+                reason: "Requests are read by the MAC before the request function returns; their buffers may be prepared on another context.",
+            },
+            ThreadSafetyRule {
+                pattern: "ST_MAC_.*(?:Ind|Cnf)_t",
+                safety: ThreadSafety::Local,
+                reason: "Indications and confirmations point into MAC buffers that are only valid during the callback receiving them.",
+            },
+            ThreadSafetyRule {
+                pattern: "ST_MAC_callbacks_t",
+                safety: ThreadSafety::Sync,
+                reason: "A table of function pointers, registered once and only read afterwards.",
+            },
+        ],
         library_artifacts: &[
             LibraryArtifact {
                 source: "Middlewares/ST/STM32_WPAN/mac_802_15_4/lib",
@@ -102,11 +372,59 @@ const BINDING_SPECS: &[BindingSpec] = &[
                 destination: "src/lib/wba_mac_lib.a",
             },
         ],
+        c_sources: &[],
+        split: &[],
     },
     BindingSpec {
         module: "wba_ble_stack",
         feature: Some("wba_wpan_ble"),
-        header: "stm32-bindings-gen/inc/wba_ble.h",
+        header: Header::Includes(&[
+            "<stdbool.h>",
+            "<stddef.h>",
+            "<stdint.h>",
+            "cmsis_compiler.h",
+            "blestack.h",
+            "ble_bufsize.h",
+            "ble_codec.h",
+            "ble_const.h",
+            "ble_core.h",
+            "ble_defs.h",
+            "ble_legacy.h",
+            "ble_std.h",
+            "bleplat.h",
+            "auto/ble_events.h",
+            "auto/ble_gap_aci.h",
+            "auto/ble_gatt_aci.h",
+            "auto/ble_hal_aci.h",
+            "auto/ble_hci_le.h",
+            "auto/ble_l2cap_aci.h",
+            "auto/ble_raw_api.h",
+            "auto/ble_types.h",
+            "auto/ble_vs_codes.h",
+            "ble_audio_stack.h",
+            "ble_audio_plat.h",
+            "audio_types.h",
+            "bap_bufsize.h",
+            "bap_types.h",
+            "cap.h",
+            "cap_types.h",
+            "ccp.h",
+            "ccp_types.h",
+            "csip.h",
+            "csip_types.h",
+            "ltv_utils.h",
+            "mcp.h",
+            "mcp_types.h",
+            "micp.h",
+            "micp_types.h",
+            "vcp.h",
+            "vcp_types.h",
+            "codec_if.h",
+            "codec_mngr.h",
+            "LC3.h",
+            "LC3_decoder.h",
+            "LC3_encoder.h",
+        ]),
         include_dirs: &[
             "Middlewares/ST/STM32_WPAN",
             "Middlewares/ST/STM32_WPAN/ble/stack/include",
@@ -135,7 +453,22 @@ const BINDING_SPECS: &[BindingSpec] = &[
             "-DEXT_ADDRESS_LENGTH=8",
         ],
         allowlist: &[],
+        blocklist: NEWLIB_CMSIS_BLOCKLIST,
+        opaque_types: NEWLIB_SHARED_OPAQUES,
+        newtype_enums: &[],
+        rustified_enums: &[],
         aliases: &["ble", "ble_wba"],
+        renames: &[],
+        inline_fns: &[],
+        serde_types: &[],
+        thread_safety: &[
+            BLE_BUFF_HDR_SAFETY,
+            ThreadSafetyRule {
+                pattern: "BleStack_init_t",
+                safety: ThreadSafety::Send,
+                reason: "Read once by `BleStack_Init`; the buffers it points to then belong to the stack, whichever context initializes it.",
+            },
+        ],
         library_artifacts: &[
             LibraryArtifact {
                 source: "Middlewares/ST/STM32_WPAN/ble/stack/lib",
@@ -154,40 +487,343 @@ const BINDING_SPECS: &[BindingSpec] = &[
                 destination: "src/lib/ble/lc3",
             },
         ],
+        c_sources: &[],
+        split: &[
+            SplitArea {
+                name: "gap",
+                feature: "ble_gap",
+                prefixes: &["aci_gap_"],
+            },
+            SplitArea {
+                name: "gatt",
+                feature: "ble_gatt",
+                prefixes: &["aci_gatt_", "aci_att_"],
+            },
+            SplitArea {
+                name: "hci",
+                feature: "ble_hci",
+                prefixes: &["hci_"],
+            },
+            SplitArea {
+                name: "aci",
+                feature: "ble_aci",
+                prefixes: &["aci_"],
+            },
+            SplitArea {
+                name: "audio",
+                feature: "ble_audio",
+                prefixes: &[
+                    "BAP_", "CAP_", "CCP_", "CSIP_", "MCP_", "MICP_", "VCP_", "LTV_", "CODEC_",
+                    "LC3_",
+                ],
+            },
+        ],
+    },
+    BindingSpec {
+        module: "stm32_seq",
+        feature: Some("stm32_seq"),
+        header: Header::Includes(&["<stdint.h>", "utilities_conf.h", "stm32_seq.h"]),
+        include_dirs: &["Utilities/sequencer"],
+        clang_args: &[],
+        allowlist: &["UTIL_SEQ_.*"],
+        blocklist: &[],
+        opaque_types: NEWLIB_SHARED_OPAQUES,
+        newtype_enums: &[],
+        rustified_enums: &[],
+        aliases: &[],
+        renames: &[],
+        // The PRIMASK helpers of `utilities_conf.h`, used by the critical
+        // sections of every utility.
+        inline_fns: &["utilities_conf_.*"],
+        serde_types: &[],
+        thread_safety: &[],
+        library_artifacts: &[],
+        c_sources: &[
+            "Utilities/sequencer/stm32_seq.c",
+            "Utilities/sequencer/stm32_seq.h",
+        ],
+        split: &[],
+    },
+    BindingSpec {
+        module: "stm32_timer",
+        feature: Some("stm32_timer"),
+        header: Header::Includes(&["<stdint.h>", "utilities_conf.h", "stm32_timer.h"]),
+        include_dirs: &["Utilities/tim_serv"],
+        clang_args: &[],
+        allowlist: &["UTIL_TIMER_.*", "UTIL_TimerDriver.*"],
+        blocklist: &[],
+        opaque_types: NEWLIB_SHARED_OPAQUES,
+        newtype_enums: &["UTIL_TIMER_Status_t", "UTIL_TIMER_Mode_t"],
+        rustified_enums: &[],
+        aliases: &[],
+        renames: &[],
+        inline_fns: &[],
+        serde_types: &[],
+        thread_safety: &[],
+        library_artifacts: &[],
+        c_sources: &[
+            "Utilities/tim_serv/stm32_timer.c",
+            "Utilities/tim_serv/stm32_timer.h",
+        ],
+        split: &[],
+    },
+    BindingSpec {
+        module: "stm32_lpm",
+        feature: Some("stm32_lpm"),
+        header: Header::Includes(&["<stdint.h>", "utilities_conf.h", "stm32_lpm.h"]),
+        include_dirs: &["Utilities/lpm/tiny_lpm"],
+        clang_args: &[],
+        allowlist: &["UTIL_LPM_.*", "UTIL_PowerDriver"],
+        blocklist: &[],
+        opaque_types: NEWLIB_SHARED_OPAQUES,
+        newtype_enums: &["UTIL_LPM_State_t", "UTIL_LPM_Mode_t"],
+        rustified_enums: &[],
+        aliases: &[],
+        renames: &[],
+        inline_fns: &[],
+        serde_types: &[],
+        thread_safety: &[],
+        library_artifacts: &[],
+        c_sources: &[
+            "Utilities/lpm/tiny_lpm/stm32_lpm.c",
+            "Utilities/lpm/tiny_lpm/stm32_lpm.h",
+        ],
+        split: &[],
+    },
+    BindingSpec {
+        module: "stm32_adv_trace",
+        feature: Some("stm32_adv_trace"),
+        header: Header::Includes(&["<stdint.h>", "utilities_conf.h", "stm32_adv_trace.h"]),
+        include_dirs: &["Utilities/trace/adv_trace"],
+        clang_args: &[],
+        allowlist: &["UTIL_ADV_TRACE_.*", "UTIL_TraceDriver"],
+        blocklist: &[],
+        opaque_types: NEWLIB_SHARED_OPAQUES,
+        newtype_enums: &["UTIL_ADV_TRACE_Status_t"],
+        rustified_enums: &[],
+        aliases: &[],
+        renames: &[],
+        inline_fns: &[],
+        serde_types: &[],
+        thread_safety: &[],
+        library_artifacts: &[],
+        c_sources: &[
+            "Utilities/trace/adv_trace/stm32_adv_trace.c",
+            "Utilities/trace/adv_trace/stm32_adv_trace.h",
+        ],
+        split: &[],
+    },
+    BindingSpec {
+        module: "wba_nvm",
+        feature: Some("wba_nvm"),
+        header: Header::Includes(&[
+            "<stdint.h>",
+            "app_conf.h",
+            "utilities_conf.h",
+            "flash_driver.h",
+            "flash_manager.h",
+            "snvma.h",
+        ]),
+        include_dirs: &[
+            "Projects/Common/WPAN/Modules/Flash",
+            "Projects/Common/WPAN/Modules/SNVMA",
+        ],
+        clang_args: &[],
+        allowlist: &["FM_.*", "FD_.*", "SNVMA_.*"],
+        blocklist: &[],
+        opaque_types: NEWLIB_SHARED_OPAQUES,
+        newtype_enums: &[
+            "FM_Cmd_Status_t",
+            "FM_FlashOp_Status_t",
+            "FD_FlashOp_Status_t",
+            "FD_Flash_ctrl_bm_t",
+            "FD_FLASH_Status_t",
+            "SNVMA_Cmd_Status_t",
+            "SNVMA_Callback_Status_t",
+        ],
+        rustified_enums: &[],
+        aliases: &[],
+        renames: &[],
+        inline_fns: &[],
+        serde_types: &[],
+        thread_safety: &[],
+        library_artifacts: &[],
+        c_sources: &[],
+        split: &[],
+    },
+    BindingSpec {
+        module: "wba_hw_if",
+        feature: Some("wba_hw_if"),
+        header: Header::File("stm32-bindings-gen/inc/wba_hw_if.h"),
+        include_dirs: &[],
+        clang_args: &[],
+        allowlist: &["HW_AES_.*", "HW_PKA_.*", "HW_RNG_.*"],
+        blocklist: &[],
+        opaque_types: NEWLIB_SHARED_OPAQUES,
+        newtype_enums: &[],
+        rustified_enums: &[],
+        aliases: &[],
+        renames: &[],
+        inline_fns: &[],
+        serde_types: &[],
+        thread_safety: &[],
+        library_artifacts: &[],
+        c_sources: &[],
+        split: &[],
+    },
+];
+
+/// Vendor hook functions that applications normally implement in C.
+///
+/// Every function declared in `headers` gets a default implementation in
+/// `src/stubs/<name>.rs`, compiled only with the `stubs_<name>` feature.
+#[derive(Debug, Clone, Copy)]
+struct HookStubs {
+    name: &'static str,
+    spec: &'static str,
+    headers: &'static [&'static str],
+}
+
+const HOOK_STUBS: &[HookStubs] = &[
+    HookStubs {
+        name: "linklayer_plat",
+        spec: "wba_wpan_mac",
+        headers: &[".*/linklayer_plat\\.h"],
     },
+    HookStubs {
+        name: "ll_sys",
+        spec: "wba_link_layer",
+        headers: &[".*/ll_sys\\.h"],
+    },
+    HookStubs {
+        name: "bleplat",
+        spec: "wba_ble_stack",
+        headers: &[".*/bleplat\\.h"],
+    },
+];
+
+/// Hooks the emitted crate implements in Rust behind a feature; their stubs
+/// are left out when any of those features is on.
+const RUST_HOOKS: &[(&str, &[&str])] = &[
+    ("LINKLAYER_PLAT_GetRNG", &["platform_rng"]),
+    (
+        "LINKLAYER_PLAT_RequestTemperature",
+        &["platform_temperature", "temperature_compensation"],
+    ),
+    ("LINKLAYER_PLAT_ClockInit", &["platform_clock"]),
+    ("LINKLAYER_PLAT_AclkCtrl", &["platform_clock"]),
+    ("LINKLAYER_PLAT_WaitHclkRdy", &["platform_clock"]),
+    ("LINKLAYER_PLAT_DelayUs", &["platform_clock"]),
+    ("LINKLAYER_PLAT_SetupRadioIT", &["platform_radio_irq"]),
+    ("LINKLAYER_PLAT_SetupSwLowIT", &["platform_radio_irq"]),
+    ("LINKLAYER_PLAT_TriggerSwLowIT", &["platform_radio_irq"]),
+    ("LINKLAYER_PLAT_EnableRadioIT", &["platform_radio_irq"]),
+    ("LINKLAYER_PLAT_DisableRadioIT", &["platform_radio_irq"]),
+    ("LINKLAYER_PLAT_Assert", &["platform_assert"]),
+    (
+        "LINKLAYER_PLAT_SCHLDR_TIMING_UPDATE_NOT",
+        &["scheduler_timing"],
+    ),
+    ("LINKLAYER_PLAT_RCOStartClbr", &["calibration"]),
+    ("LINKLAYER_PLAT_RCOStopClbr", &["calibration"]),
+    ("LINKLAYER_PLAT_PhyStartClbr", &["calibration"]),
+    ("LINKLAYER_PLAT_PhyStopClbr", &["calibration"]),
 ];
 
 #[derive(Debug)]
-struct UppercaseCallbacks;
+struct SpecCallbacks {
+    renames: &'static [RenameRule],
+    serde_types: Vec<Regex>,
+}
 
-impl ParseCallbacks for UppercaseCallbacks {
+impl ParseCallbacks for SpecCallbacks {
     fn item_name(&self, item: ItemInfo<'_>) -> Option<String> {
-        if matches!(item.kind, ItemKind::Var) {
-            Some(item.name.to_ascii_uppercase())
-        } else {
-            None
+        let kind = match item.kind {
+            ItemKind::Function => RenameKind::Function,
+            ItemKind::Var => RenameKind::Var,
+            _ => return None,
+        };
+
+        let renamed = self.renames.iter().find_map(|rule| {
+            let rest = item.name.strip_prefix(rule.prefix)?;
+            let renamed = format!("{}{rest}", rule.replacement);
+            let valid = renamed
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+            (rule.kind == kind && valid).then_some(renamed)
+        });
+
+        match kind {
+            RenameKind::Var => Some(renamed.as_deref().unwrap_or(item.name).to_ascii_uppercase()),
+            RenameKind::Function => renamed,
+        }
+    }
+
+    fn add_attributes(&self, info: &AttributeInfo<'_>) -> Vec<String> {
+        let derivable = matches!(info.kind, TypeKind::Struct | TypeKind::Enum);
+        if !derivable
+            || !self
+                .serde_types
+                .iter()
+                .any(|regex| regex.is_match(info.name))
+        {
+            return Vec::new();
         }
+        vec![
+            "#[cfg_attr(feature = \"serde\", derive(serde::Serialize, serde::Deserialize))]"
+                .to_owned(),
+            "#[cfg_attr(feature = \"defmt\", derive(defmt::Format))]".to_owned(),
+        ]
     }
 }
 
 pub struct Options {
     pub out_dir: PathBuf,
     pub sources_dir: PathBuf,
-    pub target_triple: String,
+    /// Targets to generate bindings for. With more than one target, each gets
+    /// its own `src/bindings/<triple>/` directory selected at build time.
+    pub target_triples: Vec<String>,
+    /// Emit types that several modules define identically once, in
+    /// `bindings::common`, and re-export them from each module.
+    pub shared_types: bool,
+    /// Drop debug information from the copied archives.
+    pub strip_archives: bool,
+    /// Make the global symbols of the copied archives that no header names
+    /// and no other archive member uses local to their object file.
+    pub localize_internal_symbols: bool,
+    /// Sysroot providing the C headers, instead of the one of the
+    /// discovered toolchain.
+    pub sysroot: Option<PathBuf>,
+    /// C library of the sysroot, when it cannot be detected or is not the
+    /// toolchain's default.
+    pub libc: Option<Libc>,
+    /// Print the clang command line of every bindgen run.
+    pub verbose_clang: bool,
+    /// Also write the per-module size report to this file.
+    pub report: Option<PathBuf>,
+    /// Modules to regenerate in an existing output, leaving everything else
+    /// untouched. Empty regenerates the whole crate.
+    pub only: Vec<String>,
+    /// Generate code free of implicit panics and lossy casts, and deny
+    /// warnings and panicking constructs in the emitted crate, for
+    /// panic-freedom audits.
+    pub audit: bool,
+    /// Leave the CMSIS core register blocks out of the bindings and alias
+    /// the `cortex-m` crate's equivalents instead.
+    pub cortex_m_types: bool,
 }
 
 fn host_isystem_args() -> Vec<String> {
     let mut args = Vec::new();
-    if cfg!(target_os = "macos") {
-        if let Ok(output) = Command::new("xcrun").arg("--show-sdk-path").output() {
-            if output.status.success() {
-                if let Ok(path) = String::from_utf8(output.stdout) {
-                    let trimmed = path.trim();
-                    if !trimmed.is_empty() {
-                        args.push(format!("-isystem{}/usr/include", trimmed));
-                    }
-                }
-            }
+    if cfg!(target_os = "macos")
+        && let Ok(output) = Command::new("xcrun").arg("--show-sdk-path").output()
+        && output.status.success()
+        && let Ok(path) = String::from_utf8(output.stdout)
+    {
+        let trimmed = path.trim();
+        if !trimmed.is_empty() {
+            args.push(format!("-isystem{}/usr/include", trimmed));
         }
     }
     args
@@ -195,30 +831,196 @@ fn host_isystem_args() -> Vec<String> {
 
 pub struct Gen {
     opts: Options,
+    /// Wrappers generated for `Header::Includes`, kept for the whole run.
+    wrapper_dir: tempfile::TempDir,
+}
+
+/// What [`Gen::generate_spec`] produced for one spec.
+struct SpecOutput {
+    link_dirs: Vec<LinkDir>,
+    c_source_dir: Option<String>,
+    attributions: Vec<licenses::Attribution>,
+    providers: BTreeMap<String, Vec<String>>,
+    /// Bindings for each target, in the order of `Options::target_triples`.
+    bindings: Vec<String>,
+    report: report::ModuleReport,
+}
+
+thread_local! {
+    /// Lines logged by the [`parallel_map`] item running on this thread.
+    static LOG: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+/// Prints a progress line. Inside [`parallel_map`], the line is printed
+/// with the other lines of its item, in item order.
+macro_rules! progress {
+    ($($arg:tt)*) => {
+        log_line(format!($($arg)*))
+    };
+}
+
+fn log_line(line: String) {
+    let line = LOG.with_borrow_mut(|log| match log {
+        Some(log) => {
+            log.push(line);
+            None
+        }
+        None => Some(line),
+    });
+    if let Some(line) = line {
+        println!("{line}");
+    }
+}
+
+/// Runs `f`, returning the lines it logs instead of printing them. If `f`
+/// panics, they are printed right away.
+fn capture_log<R>(f: impl FnOnce() -> R) -> (R, Vec<String>) {
+    struct FlushOnPanic;
+
+    impl Drop for FlushOnPanic {
+        fn drop(&mut self) {
+            for line in LOG.take().into_iter().flatten() {
+                println!("{line}");
+            }
+        }
+    }
+
+    LOG.set(Some(Vec::new()));
+    let guard = FlushOnPanic;
+    let result = f();
+    mem::forget(guard);
+    (result, LOG.take().unwrap_or_default())
+}
+
+/// Runs `f` on every item on up to one thread per CPU and returns the
+/// results in item order. A panic in `f` is raised again on the caller.
+/// Lines logged with `progress!` are printed in item order too.
+fn parallel_map<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let next = AtomicUsize::new(0);
+    let workers = thread::available_parallelism()
+        .map_or(1, usize::from)
+        .min(items.len());
+
+    let mut results = thread::scope(|scope| {
+        let handles = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(item) = items.get(index) else {
+                            break done;
+                        };
+                        let (result, log) = capture_log(|| f(item));
+                        done.push((index, result, log));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .flat_map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|panic| panic::resume_unwind(panic))
+            })
+            .collect::<Vec<_>>()
+    });
+    results.sort_by_key(|(index, _, _)| *index);
+    results
+        .into_iter()
+        .map(|(_, result, log)| {
+            for line in log {
+                println!("{line}");
+            }
+            result
+        })
+        .collect()
 }
 
 impl Gen {
     pub fn new(opts: Options) -> Self {
-        Self { opts }
+        let wrapper_dir = tempfile::tempdir().expect("Unable to create temporary directory");
+        Self { opts, wrapper_dir }
     }
 
     pub fn run_gen(&mut self) {
+        assert!(
+            !self.opts.target_triples.is_empty(),
+            "At least one target triple is required"
+        );
         println!(
             "Generating bindings into {} for target {}",
             self.opts.out_dir.display(),
-            self.opts.target_triple
+            self.opts.target_triples.join(", ")
         );
 
-        self.prepare_out_dir();
-        self.write_static_files();
+        let specs = self.selected_specs();
+        let partial = specs.len() < BINDING_SPECS.len();
+
+        Self::check_patterns();
+        self.write_wrapper_headers();
+        self.check_sources(&specs);
+
+        let cube_version = self.detect_cube_version().unwrap_or_else(|| {
+            println!(
+                "  warning: unable to detect the STM32CubeWBA version in {}",
+                self.opts.sources_dir.display()
+            );
+            "unknown".to_owned()
+        });
+        println!("  -> STM32CubeWBA version {cube_version}");
+
+        if partial {
+            println!(
+                "  -> regenerating {}",
+                specs
+                    .iter()
+                    .map(|spec| format!("`{}`", spec.module))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        } else {
+            self.prepare_out_dir();
+            self.write_static_files(&cube_version);
+        }
 
         let mut modules = Vec::new();
         let mut aliases = Vec::new();
-
-        for spec in BINDING_SPECS {
-            println!("  -> generating `{}` bindings", spec.module);
-            self.generate_bindings_for_spec(spec);
-            self.copy_artifacts_for_spec(spec);
+        let mut link_dirs = Vec::new();
+        let mut providers = BTreeMap::new();
+        let mut generated: BTreeMap<&str, Vec<(String, String)>> = BTreeMap::new();
+
+        let mut c_source_dirs = Vec::new();
+        let mut attributions = Vec::new();
+        let mut reports = Vec::new();
+        let public_symbols = self
+            .opts
+            .localize_internal_symbols
+            .then(|| self.public_symbols());
+
+        // Specs only write to their own paths, so they are generated in
+        // parallel and merged in spec order to keep the output stable.
+        let outputs = parallel_map(&specs, |spec| {
+            if partial {
+                self.remove_spec_outputs(spec);
+            }
+            self.generate_spec(spec, public_symbols.as_ref())
+        });
+        for (spec, output) in specs.iter().zip(outputs) {
+            link_dirs.extend(output.link_dirs);
+            if let Some(dir) = output.c_source_dir {
+                c_source_dirs.push((dir, spec.feature.map(str::to_owned)));
+            }
+            attributions.extend(output.attributions);
+            providers.insert(spec.module, output.providers);
+            reports.push(output.report);
+            for (target, contents) in self.opts.target_triples.iter().zip(output.bindings) {
+                generated
+                    .entry(target.as_str())
+                    .or_default()
+                    .push((spec.module.to_owned(), contents));
+            }
 
             modules.push((spec.module.to_owned(), spec.feature.map(str::to_owned)));
             for alias in spec.aliases {
@@ -230,27 +1032,539 @@ impl Gen {
             }
         }
 
-        self.write_bindings_mod(&modules, &aliases);
+        for (target, mut bindings) in generated {
+            if self.opts.shared_types
+                && let Some(common) = shared_types::extract_shared_types(&mut bindings)
+            {
+                println!("  -> moving shared types into `common` for {target}");
+                bindings.push((shared_types::COMMON_MODULE.to_owned(), common));
+            }
+            for (module, contents) in bindings {
+                let no_providers = BTreeMap::new();
+                let providers = providers.get(module.as_str()).unwrap_or(&no_providers);
+                let split = BINDING_SPECS
+                    .iter()
+                    .find(|spec| spec.module == module)
+                    .map_or(&[][..], |spec| spec.split);
+                self.write_bindings(&module, target, contents, providers, split);
+            }
+        }
+        if self.opts.shared_types {
+            modules.insert(0, (shared_types::COMMON_MODULE.to_owned(), None));
+        }
+
+        // The set of modules is fixed, so the files listing them are still
+        // up to date after a partial regeneration.
+        if !partial {
+            self.write_bindings_mod(&cube_version, &modules, &aliases);
+            self.write_link_dirs(&link_dirs);
+            self.write_c_sources(&c_source_dirs);
+            self.write_string(
+                "LICENSES.md",
+                licenses::render(&cube_version, &attributions),
+            );
+        }
+
+        let hook_stubs = HOOK_STUBS
+            .iter()
+            .filter(|stubs| specs.iter().any(|spec| spec.module == stubs.spec))
+            .collect::<Vec<_>>();
+        let stub_specs = parallel_map(&hook_stubs, |stubs| {
+            progress!("  -> generating `{}` hook stubs", stubs.name);
+            self.generate_hook_stubs(stubs)
+        });
+        if !partial {
+            let stub_modules = hook_stubs
+                .iter()
+                .zip(stub_specs)
+                .map(|(stubs, spec)| (stubs.name.to_owned(), spec.feature.map(str::to_owned)))
+                .collect::<Vec<_>>();
+            self.write_stubs_mod(&stub_modules);
+        }
+
+        let report = report::render(&reports);
+        println!("\n{report}");
+        if let Some(path) = &self.opts.report {
+            fs::write(path, &report)
+                .unwrap_or_else(|err| panic!("Failed to write {}: {err}", path.display()));
+            println!("Report written to {}", path.display());
+        }
+    }
+
+    /// Copies the files of `spec` into the generated crate and generates its
+    /// bindings for every target.
+    fn generate_spec(
+        &self,
+        spec: &BindingSpec,
+        public_symbols: Option<&BTreeSet<String>>,
+    ) -> SpecOutput {
+        let link_dirs = self.copy_artifacts_for_spec(spec, public_symbols);
+        let c_source_dir = self.copy_c_sources_for_spec(spec);
+        let attributions = self.copy_licenses_for_spec(spec);
+        let providers = self.symbol_providers(spec);
+        let mut report = report::ModuleReport {
+            module: spec.module.to_owned(),
+            artifact_bytes: self.artifact_bytes(spec),
+            ..report::ModuleReport::default()
+        };
+        let bindings = self
+            .opts
+            .target_triples
+            .iter()
+            .map(|target| {
+                progress!("  -> generating `{}` bindings for {target}", spec.module);
+                let start = Instant::now();
+                let bindings = self.generate_bindings_for_spec(spec, target);
+                report.bindgen_time += start.elapsed();
+                bindings
+            })
+            .collect::<Vec<_>>();
+        report.count_items(&bindings[0]);
+
+        SpecOutput {
+            link_dirs,
+            c_source_dir,
+            attributions,
+            providers,
+            bindings,
+            report,
+        }
+    }
+
+    /// The specs named by `Options::only`, or every spec.
+    ///
+    /// Regenerating a subset needs the output of a full run, and is refused
+    /// with `shared_types`, whose `common` module depends on every spec.
+    fn selected_specs(&self) -> Vec<&'static BindingSpec> {
+        if self.opts.only.is_empty() {
+            return BINDING_SPECS.iter().collect();
+        }
+
+        let mut specs = Vec::new();
+        for module in &self.opts.only {
+            let spec = BINDING_SPECS
+                .iter()
+                .find(|spec| spec.module == module)
+                .unwrap_or_else(|| {
+                    panic!(
+                        "Unknown module `{module}`, expected one of: {}",
+                        BINDING_SPECS
+                            .iter()
+                            .map(|spec| spec.module)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )
+                });
+            if !specs
+                .iter()
+                .any(|known: &&BindingSpec| known.module == spec.module)
+            {
+                specs.push(spec);
+            }
+        }
+        // Keep spec order, so output does not depend on the order given.
+        specs.sort_by_key(|spec| {
+            BINDING_SPECS
+                .iter()
+                .position(|known| known.module == spec.module)
+        });
+
+        if specs.len() < BINDING_SPECS.len() {
+            assert!(
+                !self.opts.shared_types,
+                "Regenerating single modules is not supported with shared types, which depend on every module"
+            );
+            assert!(
+                self.opts.out_dir.join("src/bindings").is_dir(),
+                "{} has no generated crate to update: run a full generation first",
+                self.opts.out_dir.display()
+            );
+        }
+        specs
+    }
+
+    /// Removes the copied artifacts and C sources of `spec`, so files
+    /// dropped from the sources do not linger after a partial regeneration.
+    fn remove_spec_outputs(&self, spec: &BindingSpec) {
+        for artifact in spec.library_artifacts {
+            let dst = self.opts.out_dir.join(artifact.destination);
+            if self.opts.sources_dir.join(artifact.source).is_file() {
+                let _ = fs::remove_file(Self::copied_lib_path(&dst).unwrap_or(dst));
+            } else {
+                let _ = fs::remove_dir_all(dst);
+            }
+        }
+        let _ = fs::remove_dir_all(self.opts.out_dir.join("src/csrc").join(spec.module));
+        let shims_dir = self.opts.out_dir.join("src/shims");
+        let _ = fs::remove_file(shims_dir.join(format!("{}.c", spec.module)));
+        for target in &self.opts.target_triples {
+            let _ = fs::remove_file(shims_dir.join(target).join(format!("{}.c", spec.module)));
+        }
+        // Split modules are directories, so a spec that changed its `split`
+        // must not leave the other form behind.
+        let mut bindings_dirs = vec![self.opts.out_dir.join("src/bindings")];
+        if self.opts.target_triples.len() > 1 {
+            bindings_dirs = self
+                .opts
+                .target_triples
+                .iter()
+                .map(|target| bindings_dirs[0].join(target))
+                .collect();
+        }
+        for dir in bindings_dirs {
+            let _ = fs::remove_file(dir.join(format!("{}.rs", spec.module)));
+            let _ = fs::remove_dir_all(dir.join(spec.module));
+        }
+    }
+
+    /// Checks that the item patterns of every binding spec are valid regexes,
+    /// so a typo fails up front instead of inside bindgen.
+    fn check_patterns() {
+        let mut invalid = Vec::new();
+
+        for spec in BINDING_SPECS {
+            let patterns = [
+                ("allowlist", spec.allowlist),
+                ("blocklist", spec.blocklist),
+                ("opaque type", spec.opaque_types),
+                ("newtype enum", spec.newtype_enums),
+                ("rustified enum", spec.rustified_enums),
+                ("inline fn", spec.inline_fns),
+                ("serde type", spec.serde_types),
+            ];
+            let thread_safety = spec
+                .thread_safety
+                .iter()
+                .map(|rule| rule.pattern)
+                .collect::<Vec<_>>();
+            let patterns = patterns
+                .into_iter()
+                .chain([("thread safety", thread_safety.as_slice())]);
+            for (what, patterns) in patterns {
+                for pattern in patterns {
+                    // bindgen anchors every pattern at both ends.
+                    if let Err(err) = Regex::new(&format!("^(?:{pattern})$")) {
+                        invalid.push(format!(
+                            "`{}` {what} pattern is not a valid regex: {pattern}: {err}",
+                            spec.module
+                        ));
+                    }
+                }
+            }
+        }
+
+        if !invalid.is_empty() {
+            panic!("Invalid binding spec patterns:\n  {}", invalid.join("\n  "));
+        }
+    }
+
+    /// Writes the wrapper header of every spec with `Header::Includes`.
+    fn write_wrapper_headers(&self) {
+        for spec in BINDING_SPECS {
+            let Header::Includes(headers) = spec.header else {
+                continue;
+            };
+            let guard = format!("STM32_BINDINGS_{}_H_", spec.module.to_ascii_uppercase());
+            let mut contents = format!(
+                "/* Generated by stm32-bindings-gen for `{}`. */\n#ifndef {guard}\n#define {guard}\n\n",
+                spec.module
+            );
+            for header in headers {
+                if header.starts_with('<') {
+                    contents.push_str(&format!("#include {header}\n"));
+                } else {
+                    contents.push_str(&format!("#include \"{header}\"\n"));
+                }
+            }
+            contents.push_str(&format!("\n#endif /* {guard} */\n"));
+
+            let path = self.header_path(spec);
+            fs::write(&path, contents)
+                .unwrap_or_else(|err| panic!("Failed to write {}: {err}", path.display()));
+        }
+    }
+
+    /// Path of the header bindgen reads for `spec`.
+    fn header_path(&self, spec: &BindingSpec) -> PathBuf {
+        match spec.header {
+            Header::File(path) => PathBuf::from(path),
+            Header::Includes(_) => self.wrapper_dir.path().join(format!("{}.h", spec.module)),
+        }
+    }
+
+    /// Checks that every path referenced by the binding specs exists.
+    ///
+    /// Missing headers and library artifacts abort generation with a list of
+    /// everything that is missing; missing include directories only warn,
+    /// since clang ignores them.
+    fn check_sources(&self, specs: &[&BindingSpec]) {
+        let mut missing = Vec::new();
+
+        for spec in specs {
+            if let Header::File(header) = spec.header
+                && !Path::new(header).is_file()
+            {
+                missing.push(format!("`{}` header: {header}", spec.module));
+            }
+
+            // System headers come from the toolchain; quoted ones must be in
+            // the generator's `inc` directory or an include dir of the spec.
+            if let Header::Includes(headers) = spec.header {
+                let crate_inc = Path::new(env!("CARGO_MANIFEST_DIR")).join("inc");
+                let search = std::iter::once(crate_inc)
+                    .chain(
+                        spec.include_dirs
+                            .iter()
+                            .map(|dir| self.opts.sources_dir.join(dir)),
+                    )
+                    .collect::<Vec<_>>();
+                for header in headers.iter().filter(|header| !header.starts_with('<')) {
+                    if !search.iter().any(|dir| dir.join(header).is_file()) {
+                        missing.push(format!(
+                            "`{}` header: {header} (in none of its include dirs)",
+                            spec.module
+                        ));
+                    }
+                }
+            }
+
+            for source in spec.c_sources {
+                let src = self.opts.sources_dir.join(source);
+                if !src.is_file() {
+                    missing.push(format!("`{}` C source: {}", spec.module, src.display()));
+                }
+            }
+
+            for artifact in spec.library_artifacts {
+                let src = self.opts.sources_dir.join(artifact.source);
+                if !src.exists() {
+                    missing.push(format!(
+                        "`{}` library artifact: {}",
+                        spec.module,
+                        src.display()
+                    ));
+                }
+            }
+
+            for dir in spec.include_dirs {
+                let dir = self.opts.sources_dir.join(dir);
+                if !dir.is_dir() {
+                    println!(
+                        "  warning: `{}` include dir does not exist: {}",
+                        spec.module,
+                        dir.display()
+                    );
+                }
+            }
+        }
+
+        if !missing.is_empty() {
+            panic!(
+                "Sources in {} do not match the binding specs (run with --fetch to download STM32CubeWBA {CUBE_WBA_TAG}):\n  {}",
+                self.opts.sources_dir.display(),
+                missing.join("\n  ")
+            );
+        }
     }
 
     fn prepare_out_dir(&self) {
         let _ = fs::remove_dir_all(&self.opts.out_dir);
         self.create_dir(self.opts.out_dir.join("src/bindings"));
+        self.create_dir(self.opts.out_dir.join("src/stubs"));
         self.create_dir(self.opts.out_dir.join("src/lib"));
     }
 
-    fn write_static_files(&self) {
-        self.write_bytes("README.md", include_bytes!("../res/README.md"));
-        self.write_bytes("Cargo.toml", include_bytes!("../res/Cargo.toml"));
+    /// Reads the package version from `package.xml`, falling back to the
+    /// first version heading of the release notes.
+    fn detect_cube_version(&self) -> Option<String> {
+        let package_xml = self.opts.sources_dir.join("package.xml");
+        if let Ok(contents) = fs::read_to_string(package_xml) {
+            let release = Regex::new(r#"Release="FW\.WBA\.([0-9]+(?:\.[0-9]+)*)""#).unwrap();
+            if let Some(captures) = release.captures(&contents) {
+                return Some(captures[1].to_owned());
+            }
+        }
+
+        let release_notes = self.opts.sources_dir.join("Release_Notes.html");
+        if let Ok(contents) = fs::read_to_string(release_notes) {
+            let heading = Regex::new(r"\bV([0-9]+\.[0-9]+\.[0-9]+)\b").unwrap();
+            if let Some(captures) = heading.captures(&contents) {
+                return Some(captures[1].to_owned());
+            }
+        }
+
+        None
+    }
+
+    /// Replaces the `{{name}}` placeholders of the template file `name` with
+    /// the value of `vars`. Unknown placeholders abort generation.
+    fn render_template(name: &str, template: &str, vars: &[(&str, &str)]) -> String {
+        let placeholder = Regex::new(r"\{\{\s*([A-Za-z0-9_]+)\s*\}\}").unwrap();
+        let mut unknown = BTreeSet::new();
+        let rendered = placeholder.replace_all(template, |captures: &regex::Captures| {
+            let key = &captures[1];
+            match vars.iter().find(|(var, _)| *var == key) {
+                Some((_, value)) => value.to_string(),
+                None => {
+                    unknown.insert(key.to_owned());
+                    captures[0].to_owned()
+                }
+            }
+        });
+        if !unknown.is_empty() {
+            panic!(
+                "Template res/{name} uses unknown placeholders: {}",
+                unknown.into_iter().collect::<Vec<_>>().join(", ")
+            );
+        }
+        rendered.into_owned()
+    }
+
+    fn write_static_files(&self, cube_version: &str) {
+        let vars = [
+            ("crate_name", CRATE_NAME),
+            ("cube_version", cube_version),
+            ("generator_version", env!("CARGO_PKG_VERSION")),
+        ];
+        self.write_string(
+            "README.md",
+            Self::render_template("README.md", include_str!("../res/README.md"), &vars),
+        );
+        self.write_string(
+            "Cargo.toml",
+            Self::render_template("Cargo.toml", include_str!("../res/Cargo.toml"), &vars),
+        );
+
         self.write_bytes("build.rs", include_bytes!("../res/build.rs"));
-        self.write_bytes("src/lib.rs", include_bytes!("../res/src/lib.rs"));
+        self.write_bytes(
+            "archive_index.rs",
+            include_bytes!("../res/archive_index.rs"),
+        );
+        if self.opts.audit {
+            self.write_string(
+                "src/lib.rs",
+                Self::audit_lib_rs(include_str!("../res/src/lib.rs")),
+            );
+            self.write_bytes(
+                "src/bitfield_unit.rs",
+                include_bytes!("../res/src/bitfield_unit.rs"),
+            );
+        } else {
+            self.write_bytes("src/lib.rs", include_bytes!("../res/src/lib.rs"));
+        }
+        self.write_bytes("src/adv_data.rs", include_bytes!("../res/src/adv_data.rs"));
+        self.write_bytes("src/assert.rs", include_bytes!("../res/src/assert.rs"));
+        self.write_bytes("src/markers.rs", include_bytes!("../res/src/markers.rs"));
+        self.write_bytes(
+            "src/calibration.rs",
+            include_bytes!("../res/src/calibration.rs"),
+        );
+        self.write_bytes(
+            "src/scheduler_timing.rs",
+            include_bytes!("../res/src/scheduler_timing.rs"),
+        );
+        self.write_bytes(
+            "src/sequencer.rs",
+            include_bytes!("../res/src/sequencer.rs"),
+        );
+        self.write_bytes(
+            "src/sleep_clock.rs",
+            include_bytes!("../res/src/sleep_clock.rs"),
+        );
+        self.write_bytes(
+            "src/sleep_timer.rs",
+            include_bytes!("../res/src/sleep_timer.rs"),
+        );
+        self.write_bytes(
+            "src/timer_server.rs",
+            include_bytes!("../res/src/timer_server.rs"),
+        );
+        self.write_bytes("src/power.rs", include_bytes!("../res/src/power.rs"));
+        self.write_bytes("src/trace.rs", include_bytes!("../res/src/trace.rs"));
+        self.write_bytes("src/capture.rs", include_bytes!("../res/src/capture.rs"));
+        self.write_bytes(
+            "src/channel_map.rs",
+            include_bytes!("../res/src/channel_map.rs"),
+        );
+        self.write_bytes(
+            "src/event_mask.rs",
+            include_bytes!("../res/src/event_mask.rs"),
+        );
+        self.write_bytes(
+            "src/mac_frame.rs",
+            include_bytes!("../res/src/mac_frame.rs"),
+        );
+        self.write_bytes("src/nvm.rs", include_bytes!("../res/src/nvm.rs"));
+        self.write_bytes("src/os_timer.rs", include_bytes!("../res/src/os_timer.rs"));
+        self.write_bytes(
+            "src/bond_store.rs",
+            include_bytes!("../res/src/bond_store.rs"),
+        );
+        self.write_bytes(
+            "src/hw_crypto.rs",
+            include_bytes!("../res/src/hw_crypto.rs"),
+        );
+        self.write_bytes("src/platform.rs", include_bytes!("../res/src/platform.rs"));
+        self.write_bytes(
+            "src/temperature.rs",
+            include_bytes!("../res/src/temperature.rs"),
+        );
+        self.write_bytes(
+            "src/csrc/include/utilities_conf.h",
+            include_bytes!("../inc/utilities_conf.h"),
+        );
     }
 
     fn write_bindings_mod(
         &self,
+        cube_version: &str,
         modules: &[(String, Option<String>)],
         aliases: &[(String, String, Option<String>)],
     ) {
+        let mut body = String::new();
+        if self.opts.audit {
+            body.push_str(AUDIT_LINTS);
+        }
+        body.push_str(
+            "/// STM32CubeWBA package version the bindings and libraries were generated from.\n",
+        );
+        body.push_str(&format!(
+            "pub const CUBE_FW_VERSION: &str = \"{cube_version}\";\n\n"
+        ));
+
+        let targets = &self.opts.target_triples;
+        if targets.len() == 1 {
+            body.push_str(&Self::module_list(modules, aliases));
+        } else {
+            // `build.rs` sets `stm32_bindings_target` to the triple being built.
+            let predicates = targets
+                .iter()
+                .map(|target| format!("stm32_bindings_target = \"{target}\""))
+                .collect::<Vec<_>>();
+            for (target, predicate) in targets.iter().zip(&predicates) {
+                body.push_str(&format!(
+                    "#[cfg({predicate})]\n#[path = \"{target}/mod.rs\"]\nmod target;\n"
+                ));
+                self.write_string(
+                    &format!("src/bindings/{target}/mod.rs"),
+                    Self::module_list(modules, aliases),
+                );
+            }
+            body.push_str(&format!(
+                "#[cfg(not(any({})))]\ncompile_error!(\"stm32-bindings was generated for {} only\");\n\n",
+                predicates.join(", "),
+                targets.join(", ")
+            ));
+            body.push_str("pub use self::target::*;\n");
+        }
+
+        self.write_string("src/bindings/mod.rs", body);
+    }
+
+    fn module_list(
+        modules: &[(String, Option<String>)],
+        aliases: &[(String, String, Option<String>)],
+    ) -> String {
         let mut body = String::new();
         for (module, feature) in modules {
             if let Some(feature) = feature {
@@ -273,48 +1587,56 @@ impl Gen {
                 body.push_str(";\n");
             }
         }
-        self.write_string("src/bindings/mod.rs", body);
+        body
     }
 
-    fn generate_bindings_for_spec(&self, spec: &BindingSpec) {
-        let mut builder = bindgen::Builder::default()
-            .parse_callbacks(Box::new(UppercaseCallbacks))
-            .header(spec.header)
-            .clang_arg(format!("--target={}", self.opts.target_triple));
-
-        for arg in host_isystem_args() {
-            builder = builder.clang_arg(arg);
+    fn write_stubs_mod(&self, stub_modules: &[(String, Option<String>)]) {
+        let mut body = String::new();
+        if self.opts.audit {
+            body.push_str(AUDIT_LINTS);
         }
-
-        let crate_inc = Path::new(env!("CARGO_MANIFEST_DIR")).join("inc");
-        builder = builder.clang_arg(format!("-iquote{}", crate_inc.display()));
-        builder = builder.clang_arg(format!("-I{}", crate_inc.display()));
-
-        if Self::is_thumb_target(&self.opts.target_triple) {
-            builder = builder.clang_arg("-mthumb");
+        for (name, feature) in stub_modules {
+            match feature {
+                Some(feature) => body.push_str(&format!(
+                    "#[cfg(all(feature = \"stubs_{name}\", feature = \"{feature}\"))]\n"
+                )),
+                None => body.push_str(&format!("#[cfg(feature = \"stubs_{name}\")]\n")),
+            }
+            body.push_str(&format!("pub mod {name};\n"));
         }
+        self.write_string("src/stubs/mod.rs", body);
+    }
 
-        for dir in spec.include_dirs {
-            let include_path = Path::new(dir);
-            let resolved = if include_path.is_absolute() {
-                include_path.to_path_buf()
-            } else {
-                self.opts.sources_dir.join(include_path)
+    fn write_c_sources(&self, dirs: &[(String, Option<String>)]) {
+        let mut body = String::from("// Generated by stm32-bindings-gen. Do not edit.\n");
+        body.push_str("const C_SOURCES: &[(&str, Option<&str>)] = &[\n");
+        for (dir, feature) in dirs {
+            let feature = match feature {
+                Some(feature) => format!("Some(\"{feature}\")"),
+                None => "None".to_owned(),
             };
-            builder = builder.clang_arg(format!("-I{}", resolved.display()));
-        }
-
-        for arg in spec.clang_args {
-            builder = builder.clang_arg(*arg);
+            body.push_str(&format!("    (\"{dir}\", {feature}),\n"));
         }
+        body.push_str("];\n");
+        self.write_string("c_sources.rs", body);
+    }
 
-        for ty in NEWLIB_SHARED_OPAQUES {
-            builder = builder.opaque_type(ty);
+    fn write_link_dirs(&self, link_dirs: &[LinkDir]) {
+        let mut body = String::from("// Generated by stm32-bindings-gen. Do not edit.\n");
+        body.push_str("const LINK_DIRS: &[(&str, Option<&str>)] = &[\n");
+        for dir in link_dirs {
+            let float_abi = match dir.float_abi {
+                Some(abi) => format!("Some(\"{}\")", abi.as_str()),
+                None => "None".to_owned(),
+            };
+            body.push_str(&format!("    (\"{}\", {float_abi}),\n", dir.path));
         }
+        body.push_str("];\n");
+        self.write_string("link_dirs.rs", body);
+    }
 
-        for arg in arm_sysroot_args() {
-            builder = builder.clang_arg(arg);
-        }
+    fn generate_bindings_for_spec(&self, spec: &BindingSpec, target: &str) -> String {
+        let mut builder = self.builder_for_spec(spec, target);
 
         if !spec.allowlist.is_empty() {
             for pattern in spec.allowlist {
@@ -325,40 +1647,1051 @@ impl Gen {
             }
         }
 
-        let bindings = builder
-            .generate()
-            .unwrap_or_else(|err| panic!("Unable to generate bindings for {}: {err}", spec.module));
+        for pattern in spec.newtype_enums {
+            builder = builder.newtype_enum(pattern);
+        }
+        for pattern in spec.rustified_enums {
+            builder = builder.rustified_non_exhaustive_enum(pattern);
+        }
+        if self.opts.audit {
+            // Replaced by the panic-free `bitfield_unit` module.
+            builder = builder.blocklist_type("__BindgenBitfieldUnit");
+        }
+
+        let bindings = self.generate(builder, spec, target, "bindings");
 
         let mut file_contents = bindings.to_string();
         file_contents = Self::normalize_bindings(file_contents);
+        file_contents = Self::gate_layout_checks(spec.module, &file_contents);
+        if self.opts.audit {
+            file_contents = Self::audit_bindings(spec.module, &file_contents);
+        }
+
+        if !spec.inline_fns.is_empty() {
+            file_contents.push_str(&self.generate_inline_shims(spec, target));
+        }
+
+        if !spec.renames.is_empty() {
+            Self::check_renamed_items(spec.module, &file_contents);
+        }
+
+        if !spec.newtype_enums.is_empty() || !spec.rustified_enums.is_empty() {
+            file_contents.push_str(&Self::enum_compat_consts(
+                spec.module,
+                &file_contents,
+                self.opts.audit,
+            ));
+        }
+
+        file_contents.push_str(&globals::accessors(spec.module, &file_contents));
+
+        if !spec.thread_safety.is_empty() {
+            file_contents = Self::annotate_thread_safety(spec, &file_contents);
+        }
+
+        if self.opts.cortex_m_types {
+            file_contents.push_str(&Self::cortex_m_aliases(&file_contents));
+        }
+
+        file_contents
+    }
+
+    /// Aliases the CMSIS core register blocks `bindings` still refers to,
+    /// e.g. through pointer fields, to their `cortex-m` equivalents.
+    fn cortex_m_aliases(bindings: &str) -> String {
+        let mut aliases = String::new();
+        for (name, module) in CORTEX_M_TYPES {
+            let referenced = Regex::new(&format!(r"\b{name}\b")).unwrap();
+            if referenced.is_match(bindings) {
+                aliases.push_str(&format!(
+                    "pub type {name} = ::cortex_m::peripheral::{module}::RegisterBlock;\n"
+                ));
+            }
+        }
+        aliases
+    }
+
+    /// Puts bindgen's layout assertions behind the emitted crate's
+    /// `layout_checks` feature.
+    ///
+    /// bindgen emits them as `const _: () = { ... };` blocks comparing
+    /// `size_of`, `align_of` and `offset_of!` with the layout clang computed
+    /// for the target, so building for the target checks that the Rust
+    /// types match what the C libraries were compiled against.
+    fn gate_layout_checks(module: &str, bindings: &str) -> String {
+        let mut file = syn::parse_file(bindings)
+            .unwrap_or_else(|err| panic!("Unable to parse generated bindings for {module}: {err}"));
+        let mut gated = false;
+        for item in &mut file.items {
+            if manifest::is_layout_check(item)
+                && let syn::Item::Const(item) = item
+            {
+                item.attrs
+                    .push(syn::parse_quote!(#[cfg(feature = "layout_checks")]));
+                // Evaluated at compile time, where an out-of-range index is
+                // the intended build error.
+                item.attrs
+                    .push(syn::parse_quote!(#[allow(clippy::indexing_slicing)]));
+                gated = true;
+            }
+        }
+        if gated {
+            prettyplease::unparse(&file)
+        } else {
+            bindings.to_owned()
+        }
+    }
+
+    /// Adds the panicking-construct lints to the emitted `lib.rs`, denies
+    /// warnings, and declares the `bitfield_unit` module.
+    fn audit_lib_rs(lib_rs: &str) -> String {
+        let mut contents = String::new();
+        for line in lib_rs.lines() {
+            contents.push_str(line);
+            contents.push('\n');
+            if line == "#![no_std]" {
+                contents.push_str("#![deny(warnings)]\n");
+            } else if line == "pub mod bindings;" {
+                contents.push_str("#[doc(hidden)]\npub mod bitfield_unit;\n");
+            }
+        }
+        contents
+    }
+
+    /// Makes bitfield accessors panic-free: they use the `bitfield_unit`
+    /// module instead of bindgen's storage type, and convert the field
+    /// values with `From` and `bitfield_unit::FromLowBits` instead of `as`.
+    fn audit_bindings(module: &str, bindings: &str) -> String {
+        struct BitfieldCasts;
+
+        impl syn::visit_mut::VisitMut for BitfieldCasts {
+            fn visit_expr_mut(&mut self, expr: &mut syn::Expr) {
+                syn::visit_mut::visit_expr_mut(self, expr);
+                let syn::Expr::Cast(cast) = expr else {
+                    return;
+                };
+                let (value, ty) = (&cast.expr, &cast.ty);
+                let is_getter = match value.as_ref() {
+                    syn::Expr::MethodCall(call) => call.method == "get",
+                    syn::Expr::Call(call) => matches!(
+                        call.func.as_ref(),
+                        syn::Expr::Path(path)
+                            if path.path.segments.last().is_some_and(|last| last.ident == "raw_get")
+                    ),
+                    _ => false,
+                };
+                let is_widening = matches!(value.as_ref(), syn::Expr::Path(_))
+                    && matches!(ty.as_ref(), syn::Type::Path(path) if path.path.is_ident("u64"));
+                if is_getter {
+                    *expr = syn::parse_quote!(
+                        <#ty as crate::bitfield_unit::FromLowBits>::from_low_bits(#value)
+                    );
+                } else if is_widening {
+                    *expr = syn::parse_quote!(<u64>::from(#value));
+                }
+            }
+        }
+
+        if !bindings.contains("__BindgenBitfieldUnit") {
+            return bindings.to_owned();
+        }
+        let mut file = syn::parse_file(bindings)
+            .unwrap_or_else(|err| panic!("Unable to parse generated bindings for {module}: {err}"));
+        syn::visit_mut::VisitMut::visit_file_mut(&mut BitfieldCasts, &mut file);
+        file.items.insert(
+            0,
+            syn::parse_quote!(
+                use crate::bitfield_unit::__BindgenBitfieldUnit;
+            ),
+        );
+        prettyplease::unparse(&file)
+    }
+
+    /// Documents the audited thread safety of the types matched by the
+    /// spec's rules and emits the `Send`/`Sync` impls they allow.
+    fn annotate_thread_safety(spec: &BindingSpec, bindings: &str) -> String {
+        let mut file = syn::parse_file(bindings).unwrap_or_else(|err| {
+            panic!(
+                "Unable to parse generated bindings for {}: {err}",
+                spec.module
+            )
+        });
+        let rules = spec
+            .thread_safety
+            .iter()
+            .map(|rule| {
+                let regex = Regex::new(&format!("^(?:{})$", rule.pattern)).unwrap();
+                (regex, rule)
+            })
+            .collect::<Vec<_>>();
+
+        let mut impls: Vec<syn::Item> = Vec::new();
+        let mut matched = BTreeSet::new();
+        for item in &mut file.items {
+            let (ident, attrs) = match item {
+                syn::Item::Struct(item) => (&item.ident, &mut item.attrs),
+                syn::Item::Union(item) => (&item.ident, &mut item.attrs),
+                syn::Item::Enum(item) => (&item.ident, &mut item.attrs),
+                _ => continue,
+            };
+            let name = ident.to_string();
+            let Some((regex, rule)) = rules.iter().find(|(regex, _)| regex.is_match(&name)) else {
+                continue;
+            };
+            matched.insert(regex.as_str().to_owned());
+
+            let doc = format!(
+                " Thread safety: {}. {}",
+                rule.safety.describe(),
+                rule.reason
+            );
+            attrs.push(syn::parse_quote!(#[doc = ""]));
+            attrs.push(syn::parse_quote!(#[doc = #doc]));
+
+            let reason = format!(" {}", rule.reason);
+            if matches!(rule.safety, ThreadSafety::Send | ThreadSafety::Sync) {
+                impls.push(syn::parse_quote! {
+                    #[doc = #reason]
+                    unsafe impl Send for #ident {}
+                });
+            }
+            if rule.safety == ThreadSafety::Sync {
+                impls.push(syn::parse_quote! {
+                    #[doc = #reason]
+                    unsafe impl Sync for #ident {}
+                });
+            }
+        }
+
+        for (regex, rule) in &rules {
+            if !matched.contains(regex.as_str()) {
+                progress!(
+                    "  warning: thread safety rule `{}` of `{}` matches no type",
+                    rule.pattern,
+                    spec.module
+                );
+            }
+        }
+
+        file.items.extend(impls);
+        prettyplease::unparse(&file)
+    }
+
+    fn write_bindings(
+        &self,
+        module: &str,
+        target: &str,
+        file_contents: String,
+        providers: &BTreeMap<String, Vec<String>>,
+        split: &[SplitArea],
+    ) {
+        let mut out_path = self.opts.out_dir.join("src/bindings");
+        if self.opts.target_triples.len() > 1 {
+            out_path.push(target);
+        }
+        out_path.push(format!("{module}.rs"));
+
+        // Downstream tooling reads the manifest instead of parsing the bindings.
+        let manifest = Manifest::from_bindings(module, target, &file_contents, providers);
+        let manifest =
+            serde_json::to_string_pretty(&manifest).expect("Unable to serialize manifest");
+        self.write_string_path(&out_path.with_extension("json"), manifest);
+
+        if split.is_empty() {
+            self.write_string_path(&out_path, file_contents);
+            return;
+        }
+        let split = split::split_module(module, &file_contents, split);
+        let dir = out_path.with_extension("");
+        self.write_string_path(&dir.join("mod.rs"), split.root);
+        for (name, contents) in split.submodules {
+            self.write_string_path(&dir.join(format!("{name}.rs")), contents);
+        }
+    }
+
+    fn generate_hook_stubs(&self, stubs: &HookStubs) -> &'static BindingSpec {
+        let spec = BINDING_SPECS
+            .iter()
+            .find(|spec| spec.module == stubs.spec)
+            .unwrap_or_else(|| {
+                panic!(
+                    "Hook stubs `{}` reference unknown spec `{}`",
+                    stubs.name, stubs.spec
+                )
+            });
+
+        // Hook signatures do not depend on the float ABI, so the first target
+        // is representative.
+        let mut builder = self.builder_for_spec(spec, &self.opts.target_triples[0]);
+        for header in stubs.headers {
+            builder = builder.allowlist_file(header);
+        }
+
+        let bindings = self.generate(
+            builder,
+            spec,
+            &self.opts.target_triples[0],
+            &format!("`{}` hook stubs", stubs.name),
+        );
+        let contents = Self::normalize_bindings(bindings.to_string());
 
         let out_path = self
             .opts
             .out_dir
-            .join("src/bindings")
-            .join(format!("{}.rs", spec.module));
+            .join("src/stubs")
+            .join(format!("{}.rs", stubs.name));
+        self.write_string_path(&out_path, Self::render_hook_stubs(spec.module, &contents));
+
+        spec
+    }
+
+    /// Binds the requested `static inline` functions through C wrappers.
+    ///
+    /// bindgen writes a C file defining a `<name>__extern` wrapper for each
+    /// function; it is preprocessed into a self-contained `src/shims/<module>.c`,
+    /// or `src/shims/<target>/<module>.c` when generating for several targets,
+    /// that the emitted build.rs compiles with `cc`. Returns the Rust
+    /// declarations of the wrappers.
+    fn generate_inline_shims(&self, spec: &BindingSpec, target: &str) -> String {
+        let workdir = tempfile::tempdir().expect("Unable to create temporary directory");
+        let shim_base = workdir.path().join(spec.module);
+
+        let mut builder = self
+            .builder_for_spec(spec, target)
+            .wrap_static_fns(true)
+            .wrap_static_fns_path(&shim_base);
+        for pattern in spec.inline_fns {
+            builder = builder.allowlist_function(pattern);
+        }
+
+        let bindings = self.generate(builder, spec, target, "inline shims");
+
+        let shim_source = shim_base.with_extension("c");
+        if !shim_source.is_file() {
+            progress!(
+                "  warning: no static inline functions of `{}` match {:?}",
+                spec.module,
+                spec.inline_fns
+            );
+            return String::new();
+        }
+
+        let mut out_path = self.opts.out_dir.join("src/shims");
+        if self.opts.target_triples.len() > 1 {
+            out_path.push(target);
+        }
+        out_path.push(format!("{}.c", spec.module));
+        self.preprocess_shim(spec, target, &shim_source, &out_path);
+
+        let decls = Self::normalize_bindings(bindings.to_string());
+        Self::shim_declarations(spec.module, &decls)
+    }
+
+    fn preprocess_shim(&self, spec: &BindingSpec, target: &str, src: &Path, dst: &Path) {
+        if let Some(parent) = dst.parent() {
+            self.create_dir(parent);
+        }
+
+        // `--target` and `-mthumb` are clang spellings; the GNU preprocessor
+        // picks the architecture from its own configuration.
+        let args = self
+            .clang_args_for_spec(spec, target)
+            .into_iter()
+            .filter(|arg| !arg.starts_with("--target=") && arg != "-mthumb");
+
+        let output = Command::new(toolchain::gnu_tool("gcc"))
+            .args(["-E", "-P", "-xc"])
+            .args(args)
+            .arg(src)
+            .arg("-o")
+            .arg(dst)
+            .output()
+            .unwrap_or_else(|err| panic!("Unable to run arm-none-eabi-gcc: {err}"));
+
+        if !output.status.success() {
+            panic!(
+                "Unable to preprocess inline shims for {}:\n{}",
+                spec.module,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+    }
+
+    /// Keeps only the `__extern` wrapper declarations of a shim bindgen pass;
+    /// everything else is already part of the module.
+    fn shim_declarations(module: &str, bindings: &str) -> String {
+        let file = syn::parse_file(bindings)
+            .unwrap_or_else(|err| panic!("Unable to parse shim bindings for {module}: {err}"));
+
+        let mut items = Vec::new();
+        for item in file.items {
+            let syn::Item::ForeignMod(mut foreign) = item else {
+                continue;
+            };
+            foreign.items.retain(|item| {
+                let syn::ForeignItem::Fn(func) = item else {
+                    return false;
+                };
+                func.attrs.iter().any(|attr| match &attr.meta {
+                    syn::Meta::NameValue(syn::MetaNameValue {
+                        path,
+                        value:
+                            syn::Expr::Lit(syn::ExprLit {
+                                lit: syn::Lit::Str(symbol),
+                                ..
+                            }),
+                        ..
+                    }) => path.is_ident("link_name") && symbol.value().ends_with("__extern"),
+                    _ => false,
+                })
+            });
+            if !foreign.items.is_empty() {
+                items.push(syn::Item::ForeignMod(foreign));
+            }
+        }
+
+        if items.is_empty() {
+            return String::new();
+        }
+        let shims = syn::File {
+            shebang: None,
+            attrs: Vec::new(),
+            items,
+        };
+        format!("\n{}", prettyplease::unparse(&shims))
+    }
+
+    /// Fails when rename rules made two functions or statics collide.
+    fn check_renamed_items(module: &str, bindings: &str) {
+        let file = syn::parse_file(bindings)
+            .unwrap_or_else(|err| panic!("Unable to parse generated bindings for {module}: {err}"));
+
+        let mut seen = BTreeSet::new();
+        let mut duplicates = BTreeSet::new();
+        for item in &file.items {
+            let syn::Item::ForeignMod(foreign) = item else {
+                continue;
+            };
+            for item in &foreign.items {
+                let name = match item {
+                    syn::ForeignItem::Fn(func) => func.sig.ident.to_string(),
+                    syn::ForeignItem::Static(stat) => stat.ident.to_string(),
+                    _ => continue,
+                };
+                if !seen.insert(name.clone()) {
+                    duplicates.insert(name);
+                }
+            }
+        }
+
+        if !duplicates.is_empty() {
+            panic!(
+                "Rename rules of `{module}` produce duplicate items: {}",
+                duplicates.into_iter().collect::<Vec<_>>().join(", ")
+            );
+        }
+    }
+
+    /// Re-creates the `<ENUM>_<VARIANT>` constants bindgen emits for C enums
+    /// by default, for enums switched to the newtype or Rust enum style.
+    ///
+    /// The constants are gated behind the emitted crate's `enum_consts`
+    /// feature so existing users keep compiling while they migrate.
+    fn enum_compat_consts(module: &str, bindings: &str, audit: bool) -> String {
+        let file = syn::parse_file(bindings)
+            .unwrap_or_else(|err| panic!("Unable to parse generated bindings for {module}: {err}"));
+
+        let mut consts: Vec<syn::Item> = Vec::new();
+        for item in &file.items {
+            match item {
+                syn::Item::Enum(item) => {
+                    let Some(repr) = item.attrs.iter().find_map(|attr| {
+                        attr.path()
+                            .is_ident("repr")
+                            .then(|| attr.parse_args::<syn::Type>().ok())
+                            .flatten()
+                    }) else {
+                        continue;
+                    };
+                    let name = &item.ident;
+                    for variant in &item.variants {
+                        let compat = Self::compat_const_ident(name, &variant.ident);
+                        // bindgen spells out every discriminant, so audited
+                        // bindings need no cast.
+                        let value: syn::Expr = match &variant.discriminant {
+                            Some((_, discriminant)) if audit => discriminant.clone(),
+                            _ => {
+                                let variant = &variant.ident;
+                                syn::parse_quote!(#name::#variant as #repr)
+                            }
+                        };
+                        consts.push(syn::parse_quote! {
+                            #[cfg(feature = "enum_consts")]
+                            pub const #compat: #repr = #value;
+                        });
+                    }
+                }
+                syn::Item::Impl(item) if item.trait_.is_none() => {
+                    let syn::Type::Path(self_ty) = item.self_ty.as_ref() else {
+                        continue;
+                    };
+                    let Some(name) = self_ty.path.get_ident() else {
+                        continue;
+                    };
+                    let Some(inner) = file.items.iter().find_map(|other| match other {
+                        syn::Item::Struct(newtype) if &newtype.ident == name => {
+                            match &newtype.fields {
+                                syn::Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                                    Some(fields.unnamed[0].ty.clone())
+                                }
+                                _ => None,
+                            }
+                        }
+                        _ => None,
+                    }) else {
+                        continue;
+                    };
+                    for impl_item in &item.items {
+                        let syn::ImplItem::Const(constant) = impl_item else {
+                            continue;
+                        };
+                        let variant = &constant.ident;
+                        let compat = Self::compat_const_ident(name, variant);
+                        consts.push(syn::parse_quote! {
+                            #[cfg(feature = "enum_consts")]
+                            pub const #compat: #inner = #name::#variant.0;
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if consts.is_empty() {
+            return String::new();
+        }
+        let compat = syn::File {
+            shebang: None,
+            attrs: Vec::new(),
+            items: consts,
+        };
+        format!("\n{}", prettyplease::unparse(&compat))
+    }
+
+    fn compat_const_ident(name: &syn::Ident, variant: &syn::Ident) -> syn::Ident {
+        syn::Ident::new(
+            &format!("{name}_{variant}").to_ascii_uppercase(),
+            variant.span(),
+        )
+    }
+
+    /// Turns the `extern "C"` declarations of `bindings` into exported no-op
+    /// definitions. Non-unit return values are zero-initialized.
+    fn render_hook_stubs(module: &str, bindings: &str) -> String {
+        let file = syn::parse_file(bindings)
+            .unwrap_or_else(|err| panic!("Unable to parse hook declarations for {module}: {err}"));
+        let module = syn::Ident::new(module, proc_macro2::Span::call_site());
+
+        let mut items: Vec<syn::Item> = vec![syn::parse_quote! {
+            use crate::bindings::#module::*;
+        }];
+
+        for item in file.items {
+            let syn::Item::ForeignMod(foreign) = item else {
+                continue;
+            };
+            for item in foreign.items {
+                let syn::ForeignItem::Fn(func) = item else {
+                    continue;
+                };
+                if func.sig.variadic.is_some() {
+                    continue;
+                }
+
+                let docs = func.attrs.iter().filter(|attr| attr.path().is_ident("doc"));
+                // Renamed hooks must still export the C symbol; bindgen prefixes
+                // link names with `\u{1}` to disable LLVM mangling.
+                let link_name = func.attrs.iter().find_map(|attr| match &attr.meta {
+                    syn::Meta::NameValue(syn::MetaNameValue {
+                        path,
+                        value:
+                            syn::Expr::Lit(syn::ExprLit {
+                                lit: syn::Lit::Str(symbol),
+                                ..
+                            }),
+                        ..
+                    }) if path.is_ident("link_name") => Some(symbol.value()),
+                    _ => None,
+                });
+                let export = match &link_name {
+                    Some(symbol) => {
+                        let symbol = symbol.trim_start_matches('\u{1}');
+                        quote::quote! { #[unsafe(export_name = #symbol)] }
+                    }
+                    None => quote::quote! { #[unsafe(no_mangle)] },
+                };
+                let name = &func.sig.ident;
+                let symbol = link_name
+                    .as_deref()
+                    .map(|symbol| symbol.trim_start_matches('\u{1}').to_owned())
+                    .unwrap_or_else(|| name.to_string());
+                let cfg =
+                    RUST_HOOKS
+                        .iter()
+                        .find(|(hook, _)| *hook == symbol)
+                        .map(|(_, features)| {
+                            quote::quote! { #[cfg(not(any(#(feature = #features),*)))] }
+                        });
+                let inputs = &func.sig.inputs;
+                let output = &func.sig.output;
+                let body = match output {
+                    syn::ReturnType::Default => quote::quote! {},
+                    syn::ReturnType::Type(..) => quote::quote! { unsafe { ::core::mem::zeroed() } },
+                };
+
+                items.push(syn::parse_quote! {
+                    #(#docs)*
+                    #cfg
+                    #export
+                    pub extern "C" fn #name(#inputs) #output {
+                        #body
+                    }
+                });
+            }
+        }
+
+        let stubs = syn::File {
+            shebang: None,
+            attrs: Vec::new(),
+            items,
+        };
+        format!(
+            "// Generated by stm32-bindings-gen. Do not edit.\n{}",
+            prettyplease::unparse(&stubs)
+        )
+    }
+
+    /// Runs bindgen for `spec`. A failure panics with hints on the spec
+    /// fields likely at fault.
+    fn generate(
+        &self,
+        builder: bindgen::Builder,
+        spec: &BindingSpec,
+        target: &str,
+        what: &str,
+    ) -> bindgen::Bindings {
+        if self.opts.verbose_clang {
+            let args = self.clang_args_for_spec(spec, target);
+            let header = self.header_path(spec);
+            progress!(
+                "     {}",
+                diagnostics::command_line(&header.to_string_lossy(), &args)
+            );
+        }
+
+        builder.generate().unwrap_or_else(|err| {
+            let cube_version = self.detect_cube_version();
+            let hints = diagnostics::hints(&err, &self.opts.sources_dir, cube_version.as_deref());
+            let mut message = format!(
+                "Unable to generate {what} for `{}` ({target}): {err}",
+                spec.module
+            );
+            if !hints.is_empty() {
+                message.push_str("\n\nHints for the `");
+                message.push_str(spec.module);
+                message.push_str("` binding spec:\n  ");
+                message.push_str(&hints.join("\n  "));
+                if !self.opts.verbose_clang {
+                    message.push_str("\n\nRun with --verbose-clang to see the clang command line.");
+                }
+            }
+            panic!("{message}");
+        })
+    }
+
+    fn builder_for_spec(&self, spec: &BindingSpec, target: &str) -> bindgen::Builder {
+        let mut builder = bindgen::Builder::default()
+            .parse_callbacks(Box::new(SpecCallbacks {
+                renames: spec.renames,
+                serde_types: spec
+                    .serde_types
+                    .iter()
+                    .map(|pattern| Regex::new(&format!("^(?:{pattern})$")).unwrap())
+                    .collect(),
+            }))
+            .header(self.header_path(spec).to_string_lossy())
+            .clang_args(self.clang_args_for_spec(spec, target))
+            .rust_target(Self::template_rust_target())
+            .rust_edition(Self::template_edition())
+            .wrap_unsafe_ops(true);
+
+        for pattern in spec.opaque_types {
+            builder = builder.opaque_type(pattern);
+        }
+        for pattern in spec.blocklist {
+            builder = builder
+                .blocklist_type(pattern)
+                .blocklist_var(pattern)
+                .blocklist_function(pattern);
+        }
+        if self.opts.cortex_m_types {
+            for (name, _) in CORTEX_M_TYPES {
+                builder = builder.blocklist_type(name);
+            }
+        }
+
+        builder
+    }
+
+    /// Edition of the emitted crate template. Bindings are generated for it,
+    /// e.g. with `unsafe extern` blocks under edition 2024.
+    fn template_edition() -> bindgen::RustEdition {
+        let manifest = include_str!("../res/Cargo.toml");
+        let edition = Regex::new(r#"(?m)^edition\s*=\s*"([^"]*)""#)
+            .unwrap()
+            .captures(manifest)
+            .map(|captures| captures[1].to_owned())
+            .expect("res/Cargo.toml does not set an edition");
+        edition
+            .parse()
+            .unwrap_or_else(|err| panic!("Unsupported edition in res/Cargo.toml: {err}"))
+    }
 
-        self.write_string_path(&out_path, file_contents);
+    /// Oldest Rust release supporting the template's edition. bindgen
+    /// otherwise targets the latest release it knows, which may predate the
+    /// edition, and then rejects it.
+    fn template_rust_target() -> bindgen::RustTarget {
+        match Self::template_edition() {
+            bindgen::RustEdition::Edition2024 => bindgen::RustTarget::stable(85, 0).unwrap(),
+            _ => bindgen::RustTarget::default(),
+        }
     }
 
-    fn copy_artifacts_for_spec(&self, spec: &BindingSpec) {
+    fn clang_args_for_spec(&self, spec: &BindingSpec, target: &str) -> Vec<String> {
+        let mut args = vec![format!("--target={target}")];
+
+        args.extend(host_isystem_args());
+
+        let crate_inc = Path::new(env!("CARGO_MANIFEST_DIR")).join("inc");
+        args.push(format!("-iquote{}", crate_inc.display()));
+        args.push(format!("-I{}", crate_inc.display()));
+
+        if Self::is_thumb_target(target) {
+            args.push("-mthumb".to_owned());
+        }
+
+        for dir in spec.include_dirs {
+            let include_path = Path::new(dir);
+            let resolved = if include_path.is_absolute() {
+                include_path.to_path_buf()
+            } else {
+                self.opts.sources_dir.join(include_path)
+            };
+            args.push(format!("-I{}", resolved.display()));
+        }
+
+        args.extend(spec.clang_args.iter().map(|arg| arg.to_string()));
+        args.extend(toolchain::sysroot_args(
+            target,
+            self.opts.sysroot.as_deref(),
+            self.opts.libc,
+        ));
+
+        args
+    }
+
+    /// Copies the library artifacts of `spec` and returns the directories to
+    /// link from. With `public_symbols`, the global symbols of the copies
+    /// outside that set are localized.
+    fn copy_artifacts_for_spec(
+        &self,
+        spec: &BindingSpec,
+        public_symbols: Option<&BTreeSet<String>>,
+    ) -> Vec<LinkDir> {
+        let mut link_dirs = Vec::new();
+
         for artifact in spec.library_artifacts {
             let src = self.opts.sources_dir.join(artifact.source);
             let dst = self.opts.out_dir.join(artifact.destination);
 
-            if src.is_file() {
+            let mut dirs = BTreeSet::new();
+            let copied = if src.is_file() {
                 self.copy_lib(&src, &dst)
                     .unwrap_or_else(|err| panic!("Failed to copy file {}: {err}", src.display()));
+                if let Some(parent) = dst.parent() {
+                    dirs.insert(parent.to_path_buf());
+                }
+                Self::copied_lib_path(&dst).unwrap_or_else(|err| {
+                    panic!("Invalid artifact destination {}: {err}", dst.display())
+                })
             } else if src.is_dir() {
                 self.copy_lib_dir(&src, &dst)
                     .unwrap_or_else(|err| panic!("Failed to copy dir {}: {err}", src.display()));
+                Self::collect_lib_dirs(&dst, &mut dirs)
+                    .unwrap_or_else(|err| panic!("Failed to scan dir {}: {err}", dst.display()));
+                dst
             } else {
                 panic!(
                     "Artifact source {} is neither file nor directory",
                     src.display()
                 );
+            };
+
+            if self.opts.strip_archives || public_symbols.is_some() {
+                self.postprocess_archives(&copied, public_symbols);
+            }
+
+            for dir in dirs {
+                link_dirs.push(LinkDir {
+                    path: self.crate_relative(&dir),
+                    float_abi: self.dir_float_abi(&dir),
+                });
+            }
+        }
+
+        link_dirs
+    }
+
+    /// Bytes of the library artifacts and C sources copied for `spec`.
+    fn artifact_bytes(&self, spec: &BindingSpec) -> u64 {
+        let mut copies = Vec::new();
+        for artifact in spec.library_artifacts {
+            let dst = self.opts.out_dir.join(artifact.destination);
+            if self.opts.sources_dir.join(artifact.source).is_file() {
+                copies.push(Self::copied_lib_path(&dst).unwrap_or(dst));
+            } else {
+                copies.push(dst);
+            }
+        }
+        if !spec.c_sources.is_empty() {
+            copies.push(self.opts.out_dir.join("src/csrc").join(spec.module));
+        }
+        copies
+            .iter()
+            .map(|path| {
+                report::disk_size(path)
+                    .unwrap_or_else(|err| panic!("Failed to measure {}: {err}", path.display()))
+            })
+            .sum()
+    }
+
+    /// Rewrites the archives at `path` deterministically, stripping them and
+    /// localizing their symbols outside `public_symbols` as requested.
+    fn postprocess_archives(&self, path: &Path, public_symbols: Option<&BTreeSet<String>>) {
+        let archives = archive::find_archives(path)
+            .unwrap_or_else(|err| panic!("Failed to scan {}: {err}", path.display()));
+        for lib in archives {
+            let localize = match public_symbols {
+                Some(public) => archive::archive_symbols(&lib)
+                    .into_iter()
+                    .filter(|symbol| !public.contains(symbol))
+                    .collect::<BTreeSet<_>>()
+                    .into_iter()
+                    .collect(),
+                None => Vec::new(),
+            };
+            archive::postprocess(&lib, self.opts.strip_archives, &localize)
+                .unwrap_or_else(|err| panic!("Failed to post-process {}: {err}", lib.display()));
+            if !localize.is_empty() {
+                progress!(
+                    "  -> localized {} internal symbols of {}",
+                    localize.len(),
+                    self.crate_relative(&lib)
+                );
+            }
+        }
+    }
+
+    /// Symbols that stay global when localizing: every identifier of the
+    /// headers the specs read, and every symbol an archive member uses
+    /// without defining it.
+    fn public_symbols(&self) -> BTreeSet<String> {
+        let identifier = Regex::new(r"[A-Za-z_][A-Za-z0-9_]*").unwrap();
+        let mut symbols = BTreeSet::new();
+
+        for spec in BINDING_SPECS {
+            let mut headers = vec![self.header_path(spec)];
+            for dir in spec.include_dirs {
+                let Ok(entries) = fs::read_dir(self.opts.sources_dir.join(dir)) else {
+                    continue;
+                };
+                headers.extend(
+                    entries
+                        .filter_map(Result::ok)
+                        .map(|entry| entry.path())
+                        .filter(|path| path.extension().is_some_and(|ext| ext == "h")),
+                );
+            }
+            for header in headers {
+                if let Ok(contents) = fs::read_to_string(&header) {
+                    symbols.extend(
+                        identifier
+                            .find_iter(&contents)
+                            .map(|name| name.as_str().to_owned()),
+                    );
+                }
+            }
+
+            for artifact in spec.library_artifacts {
+                let src = self.opts.sources_dir.join(artifact.source);
+                let archives = archive::find_archives(&src)
+                    .unwrap_or_else(|err| panic!("Failed to scan {}: {err}", src.display()));
+                for lib in archives {
+                    symbols.extend(archive::undefined_symbols(&lib).unwrap_or_else(|err| {
+                        panic!("Failed to list symbols of {}: {err}", lib.display())
+                    }));
+                }
+            }
+        }
+
+        symbols
+    }
+
+    /// Copies the C sources of `spec` and returns their directory relative to
+    /// the generated crate, if there are any.
+    fn copy_c_sources_for_spec(&self, spec: &BindingSpec) -> Option<String> {
+        if spec.c_sources.is_empty() {
+            return None;
+        }
+
+        let dst_dir = self.opts.out_dir.join("src/csrc").join(spec.module);
+        self.create_dir(&dst_dir);
+        for source in spec.c_sources {
+            let src = self.opts.sources_dir.join(source);
+            let file_name = src
+                .file_name()
+                .unwrap_or_else(|| panic!("C source {} has no file name", src.display()));
+            fs::copy(&src, dst_dir.join(file_name))
+                .unwrap_or_else(|err| panic!("Failed to copy file {}: {err}", src.display()));
+        }
+
+        Some(self.crate_relative(&dst_dir))
+    }
+
+    /// Copies the license covering each library artifact and the C sources
+    /// of `spec` next to their copies in the generated crate.
+    fn copy_licenses_for_spec(&self, spec: &BindingSpec) -> Vec<licenses::Attribution> {
+        let mut copied = Vec::new();
+        for artifact in spec.library_artifacts {
+            let dst = self.opts.out_dir.join(artifact.destination);
+            let dst_dir = if self.opts.sources_dir.join(artifact.source).is_file() {
+                dst.parent().map(Path::to_path_buf).unwrap_or(dst)
+            } else {
+                dst
+            };
+            copied.push((artifact.source, dst_dir));
+        }
+        let csrc_dir = self.opts.out_dir.join("src/csrc").join(spec.module);
+        for source in spec.c_sources {
+            copied.push((source, csrc_dir.clone()));
+        }
+
+        let mut attributions: Vec<licenses::Attribution> = Vec::new();
+        for (source, dst_dir) in copied {
+            let src = self.opts.sources_dir.join(source);
+            let Some(license) = licenses::find_license(&self.opts.sources_dir, &src) else {
+                progress!(
+                    "  warning: no license found for `{}` {}",
+                    spec.module,
+                    src.display()
+                );
+                continue;
+            };
+            let copy = dst_dir.join(license.file_name().expect("license has a file name"));
+            let covers = self.crate_relative(&dst_dir);
+            if attributions
+                .iter()
+                .any(|attribution| attribution.covers == covers)
+            {
+                continue;
             }
+            fs::copy(&license, &copy)
+                .unwrap_or_else(|err| panic!("Failed to copy file {}: {err}", license.display()));
+            let source = license
+                .strip_prefix(&self.opts.sources_dir)
+                .unwrap_or(&license);
+            attributions.push(licenses::Attribution {
+                source: source.display().to_string(),
+                covers,
+                copy: self.crate_relative(&copy),
+            });
         }
+        attributions
+    }
+
+    /// Maps every global symbol of the copied archives of `spec` to the
+    /// archives defining it, as paths relative to the generated crate.
+    fn symbol_providers(&self, spec: &BindingSpec) -> BTreeMap<String, Vec<String>> {
+        let mut providers: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+        for artifact in spec.library_artifacts {
+            let dst = self.opts.out_dir.join(artifact.destination);
+            let archives = if self.opts.sources_dir.join(artifact.source).is_file() {
+                vec![Self::copied_lib_path(&dst).unwrap_or_else(|err| {
+                    panic!("Invalid artifact destination {}: {err}", dst.display())
+                })]
+            } else {
+                archive::find_archives(&dst)
+                    .unwrap_or_else(|err| panic!("Failed to scan dir {}: {err}", dst.display()))
+            };
+
+            for archive in archives {
+                let library = self.crate_relative(&archive);
+                let mut symbols = archive::archive_symbols(&archive);
+                symbols.sort();
+                symbols.dedup();
+                for symbol in symbols {
+                    providers.entry(symbol).or_default().push(library.clone());
+                }
+            }
+        }
+
+        providers
+    }
+
+    /// Formats `path` relative to the generated crate root with `/` separators.
+    fn crate_relative(&self, path: &Path) -> String {
+        let relative = path.strip_prefix(&self.opts.out_dir).unwrap_or(path);
+        relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Float ABI of the archives directly in `dir`, if they agree on one.
+    fn dir_float_abi(&self, dir: &Path) -> Option<FloatAbi> {
+        let entries = fs::read_dir(dir)
+            .unwrap_or_else(|err| panic!("Failed to scan dir {}: {err}", dir.display()));
+        let abis = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "a"))
+            .filter_map(|path| archive::archive_float_abi(&path))
+            .collect::<BTreeSet<_>>();
+        if abis.len() > 1 {
+            progress!(
+                "  -> warning: {} mixes float ABIs ({}); linking it for every target",
+                self.crate_relative(dir),
+                abis.iter()
+                    .map(|abi| abi.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            return None;
+        }
+        abis.into_iter().next()
+    }
+
+    fn collect_lib_dirs(dir: &Path, dirs: &mut BTreeSet<PathBuf>) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                Self::collect_lib_dirs(&path, dirs)?;
+            } else {
+                dirs.insert(dir.to_path_buf());
+            }
+        }
+        Ok(())
     }
 
     fn write_bytes(&self, relative: &str, bytes: &[u8]) {
@@ -396,6 +2729,13 @@ impl Gen {
             fs::create_dir_all(parent)?;
         }
 
+        fs::copy(src, Self::copied_lib_path(dst)?)?;
+        Ok(())
+    }
+
+    /// Returns where [`Self::copy_lib`] stores a library copied to `dst`:
+    /// the file name gets a `lib` prefix and is lowercased for the linker.
+    fn copied_lib_path(dst: &Path) -> io::Result<PathBuf> {
         let file_name = "lib".to_string()
             + dst
                 .file_name()
@@ -403,13 +2743,10 @@ impl Gen {
                 .to_str()
                 .ok_or(io::Error::new(io::ErrorKind::InvalidFilename, ""))?;
 
-        let dst = dst
+        Ok(dst
             .parent()
-            .unwrap_or(&Path::new(""))
-            .join(file_name.to_ascii_lowercase());
-
-        fs::copy(src, dst)?;
-        Ok(())
+            .unwrap_or(Path::new(""))
+            .join(file_name.to_ascii_lowercase()))
     }
 
     fn copy_lib_dir(&self, src: &Path, dst: &Path) -> io::Result<()> {
@@ -437,11 +2774,11 @@ impl Gen {
         contents
             .lines()
             .map(|line| {
-                if let Some(rest) = line.strip_prefix("pub const ") {
-                    if let Some((name, tail)) = rest.split_once(':') {
-                        let upper = name.trim().to_ascii_uppercase();
-                        return format!("pub const {}:{}", upper, tail);
-                    }
+                if let Some(rest) = line.strip_prefix("pub const ")
+                    && let Some((name, tail)) = rest.split_once(':')
+                {
+                    let upper = name.trim().to_ascii_uppercase();
+                    return format!("pub const {}:{}", upper, tail);
                 }
                 line.to_owned()
             })
@@ -454,160 +2791,130 @@ impl Gen {
     }
 }
 
-fn arm_sysroot_args() -> Vec<String> {
-    let mut args = Vec::new();
-    let mut system_include_paths = BTreeSet::new();
-
-    let mut push_sysroot = |path: &Path| {
-        system_include_paths.insert(path.join("include"));
-        system_include_paths.insert(path.join("include-fixed"));
-        system_include_paths.insert(path.join("usr/include"));
-        system_include_paths.insert(path.join("usr/include/newlib"));
-        system_include_paths.insert(path.join("arm-none-eabi/include"));
-
-        let arg = format!("--sysroot={}", path.display());
-        if !args.iter().any(|existing| existing == &arg) {
-            args.push(arg);
-        }
-    };
-
-    if let Some(sysroot_os) = env::var_os("ARM_NONE_EABI_SYSROOT") {
-        let sysroot_path = PathBuf::from(&sysroot_os);
-        if sysroot_path.exists() {
-            push_sysroot(sysroot_path.as_path());
-        }
-    }
-
-    if let Some(sysroot) = gcc_query(&["-print-sysroot"]) {
-        let sysroot = sysroot.trim();
-        if !sysroot.is_empty() {
-            push_sysroot(Path::new(sysroot));
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shim_declarations_keep_only_extern_wrappers() {
+        let bindings = r#"
+            pub type primask_t = u32;
+            unsafe extern "C" {
+                #[link_name = "utilities_conf_get_primask__extern"]
+                pub fn utilities_conf_get_primask() -> u32;
+                pub fn UTIL_SEQ_Run(mask: u32);
+            }
+        "#;
+        let shims = Gen::shim_declarations("stm32_seq", bindings);
+        assert!(shims.contains("pub fn utilities_conf_get_primask() -> u32;"));
+        assert!(!shims.contains("UTIL_SEQ_Run"));
+        assert!(!shims.contains("primask_t"));
     }
 
-    if let Some(include_dir) = gcc_query(&["-print-file-name=include"]) {
-        let include_dir = include_dir.trim();
-        if !include_dir.is_empty() && include_dir != "include" {
-            system_include_paths.insert(PathBuf::from(include_dir));
+    #[test]
+    fn parallel_map_keeps_logs_with_their_item() {
+        let logs = parallel_map(&[1, 2, 3, 4], |&item| {
+            capture_log(|| {
+                progress!("{item}: start");
+                thread::yield_now();
+                progress!("{item}: done");
+            })
+            .1
+        });
+        for (item, log) in (1..).zip(logs) {
+            assert_eq!(log, [format!("{item}: start"), format!("{item}: done")]);
         }
     }
 
-    if let Some(libgcc) = gcc_query(&["-print-libgcc-file-name"]) {
-        let libgcc_path = Path::new(libgcc.trim());
-        if let Some(version_dir) = libgcc_path.parent() {
-            system_include_paths.insert(version_dir.join("include"));
-            system_include_paths.insert(version_dir.join("include-fixed"));
-
-            if let Some(toolchain_root) = version_dir.parent() {
-                if let Some(version) = version_dir.file_name().and_then(|name| name.to_str()) {
-                    system_include_paths
-                        .insert(toolchain_root.join("include").join("c++").join(version));
-                    system_include_paths.insert(
-                        toolchain_root
-                            .join("include")
-                            .join("c++")
-                            .join(version)
-                            .join("arm-none-eabi"),
-                    );
+    #[test]
+    fn audited_bitfield_getters_keep_every_bit() {
+        let bindings = r#"
+            #[repr(C)]
+            pub struct flags_t {
+                pub _bitfield_1: __BindgenBitfieldUnit<[u8; 4usize]>,
+            }
+            impl flags_t {
+                #[inline]
+                pub fn level(&self) -> i32 {
+                    unsafe { ::core::mem::transmute(self._bitfield_1.get(0usize, 32u8) as u32) }
+                }
+                #[inline]
+                pub fn set_level(&mut self, val: i32) {
+                    unsafe {
+                        let val: u32 = ::core::mem::transmute(val);
+                        self._bitfield_1.set(0usize, 32u8, val as u64)
+                    }
                 }
             }
-        }
+        "#;
+        let audited = Gen::audit_bindings("test", bindings);
+        assert!(
+            audited.contains("<u32 as crate::bitfield_unit::FromLowBits>::from_low_bits("),
+            "{audited}"
+        );
+        assert!(audited.contains("<u64>::from(val)"), "{audited}");
+        assert!(!audited.contains(" as u"), "{audited}");
     }
 
-    for path in gcc_include_search_paths() {
-        system_include_paths.insert(path);
+    /// Whether bindgen, which anchors its patterns, leaves `name` out.
+    fn blocklisted(spec: &BindingSpec, name: &str) -> bool {
+        spec.blocklist.iter().any(|pattern| {
+            Regex::new(&format!("^(?:{pattern})$"))
+                .unwrap()
+                .is_match(name)
+        })
     }
 
-    if let Some(extra) = env::var_os("ARM_NONE_EABI_INCLUDE") {
-        for path in env::split_paths(&extra) {
-            system_include_paths.insert(path);
+    #[test]
+    fn blocklist_leaves_out_c_library_and_cmsis_items() {
+        let spec = &BINDING_SPECS[0];
+        for name in [
+            "printf",
+            "vsnprintf",
+            "_fprintf_r",
+            "fopen",
+            "memcpy",
+            "strlen",
+            "strncmp",
+            "_impure_ptr",
+            "ITM_RxBuffer",
+        ] {
+            assert!(blocklisted(spec, name), "{name} is not blocklisted");
         }
     }
 
-    for path in system_include_paths {
-        if path.exists() {
-            let flag = format!("-isystem{}", path.display());
-            if !args.contains(&flag) {
-                args.push(flag);
+    #[test]
+    fn blocklist_keeps_vendor_items() {
+        for spec in BINDING_SPECS {
+            for name in [
+                "mem_intf_alloc",
+                "str_to_hex",
+                "ll_intf_init",
+                "aci_gap_init",
+                "printf_hook",
+                "UTIL_ADV_TRACE_FSend",
+                "ITM_RxBuffer_size",
+            ] {
+                assert!(
+                    !blocklisted(spec, name),
+                    "{} blocklists {name}",
+                    spec.module
+                );
             }
         }
     }
 
-    args
-}
-
-fn gcc_include_search_paths() -> Vec<PathBuf> {
-    let mut command = Command::new("arm-none-eabi-gcc");
-    command.args(["-xc", "-E", "-Wp,-v", "-"]);
-    command.stdin(Stdio::piped());
-    command.stdout(Stdio::null());
-    command.stderr(Stdio::piped());
-
-    let mut child = match command.spawn() {
-        Ok(child) => child,
-        Err(_) => return Vec::new(),
-    };
-
-    if let Some(mut stdin) = child.stdin.take() {
-        let _ = stdin.write_all(b"\n");
-    }
-
-    let output = match child.wait_with_output() {
-        Ok(output) => output,
-        Err(_) => return Vec::new(),
-    };
-
-    if !output.status.success() {
-        return Vec::new();
-    }
-
-    let stderr = match String::from_utf8(output.stderr) {
-        Ok(text) => text,
-        Err(_) => return Vec::new(),
-    };
-
-    let mut paths = Vec::new();
-    let mut capture = false;
-
-    for line in stderr.lines() {
-        if line.contains("#include <...> search starts here:") {
-            capture = true;
-            continue;
-        }
-        if capture {
-            if line.contains("End of search list.") {
-                break;
-            }
-            let trimmed = line.trim();
-            if trimmed.is_empty() {
-                continue;
-            }
-            let trimmed = trimmed.trim_start_matches("(framework directory) ");
-            let trimmed = trimmed.trim_end_matches(" (framework directory)");
-            if trimmed.is_empty() {
-                continue;
-            }
-            let candidate = PathBuf::from(trimmed);
-            if candidate.is_relative() {
-                continue;
+    #[test]
+    fn inline_fns_patterns_are_valid_regexes() {
+        for spec in BINDING_SPECS
+            .iter()
+            .filter(|spec| !spec.inline_fns.is_empty())
+        {
+            for pattern in spec.inline_fns {
+                Regex::new(pattern).unwrap_or_else(|err| {
+                    panic!("invalid inline_fns pattern of {}: {err}", spec.module)
+                });
             }
-            paths.push(candidate);
         }
     }
-
-    paths
-}
-
-fn gcc_query(args: &[&str]) -> Option<String> {
-    let mut command = Command::new("arm-none-eabi-gcc");
-    for arg in args {
-        command.arg(arg);
-    }
-    command.output().ok().and_then(|output| {
-        if output.status.success() {
-            String::from_utf8(output.stdout).ok()
-        } else {
-            None
-        }
-    })
 }
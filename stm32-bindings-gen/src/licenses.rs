@@ -0,0 +1,63 @@
+use std::path::{Path, PathBuf};
+
+/// License file names used in STM32Cube packages, most common first.
+const LICENSE_NAMES: &[&str] = &["LICENSE.md", "LICENSE.txt", "LICENSE", "License.md"];
+
+/// A vendor license copied into the generated crate, next to the files it
+/// covers.
+#[derive(Debug)]
+pub(crate) struct Attribution {
+    /// Path of the license in the STM32Cube package.
+    pub source: String,
+    /// Copied files or directories, relative to the generated crate.
+    pub covers: String,
+    /// Path of the license copy, relative to the generated crate.
+    pub copy: String,
+}
+
+/// Returns the license nearest to `path`: in `path` itself if it is a
+/// directory, else in its directory, then in each parent up to `root`.
+pub(crate) fn find_license(root: &Path, path: &Path) -> Option<PathBuf> {
+    let start = if path.is_dir() { path } else { path.parent()? };
+    start
+        .ancestors()
+        .take_while(|dir| dir.starts_with(root))
+        .find_map(|dir| {
+            LICENSE_NAMES
+                .iter()
+                .map(|name| dir.join(name))
+                .find(|license| license.is_file())
+        })
+}
+
+/// Renders `LICENSES.md`, listing the license of every vendor file shipped
+/// with the crate.
+pub(crate) fn render(cube_version: &str, attributions: &[Attribution]) -> String {
+    let mut body = String::from(
+        "# Licenses\n\
+         \n\
+         The Rust code of this crate, including the generated bindings, is licensed\n\
+         under MIT OR Apache-2.0.\n\
+         \n",
+    );
+    if attributions.is_empty() {
+        return body;
+    }
+
+    body.push_str(&format!(
+        "The STMicroelectronics libraries and sources below come from STM32CubeWBA\n\
+         {cube_version} and stay under the license copied next to them.\n\
+         \n\
+         | Files | License | Origin |\n\
+         |---|---|---|\n"
+    ));
+    for attribution in attributions {
+        body.push_str(&format!(
+            "| `{}` | [`{copy}`]({copy}) | `{}` |\n",
+            attribution.covers,
+            attribution.source,
+            copy = attribution.copy,
+        ));
+    }
+    body
+}
@@ -1,16 +1,50 @@
 use std::{env, path::PathBuf, process};
 
-use stm32_bindings_gen::{Gen, Options};
+use stm32_bindings_gen::{Gen, Libc, Options, fetch_sources};
+
+struct Cli {
+    target_triples: Vec<String>,
+    fetch: bool,
+    shared_types: bool,
+    strip_archives: bool,
+    localize_internal_symbols: bool,
+    sysroot: Option<PathBuf>,
+    libc: Option<Libc>,
+    verbose_clang: bool,
+    report: Option<PathBuf>,
+    only: Vec<String>,
+    audit: bool,
+    cortex_m_types: bool,
+    sha256: Option<String>,
+}
 
 fn main() {
+    let cli = parse_args();
     let out_dir = PathBuf::from("build/stm32-bindings");
+
+    if cli.fetch {
+        fetch_sources(
+            &PathBuf::from("sources/STM32CubeWBA"),
+            cli.sha256.as_deref(),
+        );
+    }
+
     let sources_dir = resolve_sources_dir();
-    let target_triple = resolve_target_triple();
 
     let opts = Options {
         out_dir,
         sources_dir,
-        target_triple,
+        target_triples: cli.target_triples,
+        shared_types: cli.shared_types,
+        strip_archives: cli.strip_archives,
+        localize_internal_symbols: cli.localize_internal_symbols,
+        sysroot: cli.sysroot,
+        libc: cli.libc,
+        verbose_clang: cli.verbose_clang,
+        report: cli.report,
+        only: cli.only,
+        audit: cli.audit,
+        cortex_m_types: cli.cortex_m_types,
     };
 
     Gen::new(opts).run_gen();
@@ -26,39 +60,97 @@ fn resolve_sources_dir() -> PathBuf {
     }
 }
 
-#[allow(dead_code)]
-fn resolve_target_triple() -> String {
+fn parse_args() -> Cli {
     let mut args = env::args().skip(1);
+    let mut targets: Vec<String> = Vec::new();
     let mut positional: Option<String> = None;
+    let mut fetch = false;
+    let mut shared_types = false;
+    let mut strip_archives = false;
+    let mut localize_internal_symbols = false;
+    let mut sysroot = None;
+    let mut libc = None;
+    let mut verbose_clang = false;
+    let mut report = None;
+    let mut only = Vec::new();
+    let mut audit = false;
+    let mut cortex_m_types = false;
+    let mut sha256 = None;
 
     while let Some(arg) = args.next() {
         match arg.as_str() {
             "--help" | "-h" => {
-                eprintln!("Usage: stm32-bindings-gen [--target <triple>] [triple]");
+                eprintln!(
+                    "Usage: stm32-bindings-gen [--target <triple>[,<triple>...]]... [--fetch [--sha256 <digest>]] [--shared-types] [--strip-archives] [--localize-internal-symbols] [--sysroot <dir>] [--libc newlib|picolibc] [--verbose-clang] [--audit] [--cortex-m-types] [--report <file>] [--only <module>[,<module>...]]... [triple]"
+                );
                 process::exit(0);
             }
+            "--fetch" => fetch = true,
+            "--shared-types" => shared_types = true,
+            "--strip-archives" => strip_archives = true,
+            "--localize-internal-symbols" => localize_internal_symbols = true,
+            "--verbose-clang" => verbose_clang = true,
+            "--audit" => audit = true,
+            "--cortex-m-types" => cortex_m_types = true,
             "--target" => {
                 let value = args.next().unwrap_or_else(|| {
                     eprintln!("Expected a value after --target");
                     process::exit(1);
                 });
-                let trimmed = value.trim();
-                if trimmed.is_empty() {
-                    eprintln!("Target triple cannot be empty.");
+                targets.extend(split_targets(&value));
+            }
+            "--sysroot" => {
+                let value = args.next().unwrap_or_else(|| {
+                    eprintln!("Expected a value after --sysroot");
                     process::exit(1);
-                }
-                return trimmed.to_string();
+                });
+                sysroot = Some(PathBuf::from(non_empty(&value, "Sysroot")));
+            }
+            "--libc" => {
+                let value = args.next().unwrap_or_else(|| {
+                    eprintln!("Expected a value after --libc");
+                    process::exit(1);
+                });
+                libc = Some(parse_libc(&value));
+            }
+            "--report" => {
+                let value = args.next().unwrap_or_else(|| {
+                    eprintln!("Expected a value after --report");
+                    process::exit(1);
+                });
+                report = Some(PathBuf::from(non_empty(&value, "Report path")));
+            }
+            "--only" => {
+                let value = args.next().unwrap_or_else(|| {
+                    eprintln!("Expected a value after --only");
+                    process::exit(1);
+                });
+                only.extend(split_modules(&value));
+            }
+            "--sha256" => {
+                let value = args.next().unwrap_or_else(|| {
+                    eprintln!("Expected a value after --sha256");
+                    process::exit(1);
+                });
+                sha256 = Some(non_empty(&value, "Checksum"));
             }
             _ => {
                 if let Some(value) = arg.strip_prefix("--target=") {
-                    let trimmed = value.trim();
-                    if trimmed.is_empty() {
-                        eprintln!("Target triple cannot be empty.");
-                        process::exit(1);
-                    }
-                    return trimmed.to_string();
-                }
-                if positional.is_none() {
+                    targets.extend(split_targets(value));
+                } else if let Some(value) = arg.strip_prefix("--sysroot=") {
+                    sysroot = Some(PathBuf::from(non_empty(value, "Sysroot")));
+                } else if let Some(value) = arg.strip_prefix("--libc=") {
+                    libc = Some(parse_libc(value));
+                } else if let Some(value) = arg.strip_prefix("--report=") {
+                    report = Some(PathBuf::from(non_empty(value, "Report path")));
+                } else if let Some(value) = arg.strip_prefix("--only=") {
+                    only.extend(split_modules(value));
+                } else if let Some(value) = arg.strip_prefix("--sha256=") {
+                    sha256 = Some(non_empty(value, "Checksum"));
+                } else if arg.starts_with('-') {
+                    eprintln!("Unknown option: {arg}");
+                    process::exit(1);
+                } else if positional.is_none() {
                     let trimmed = arg.trim();
                     if !trimmed.is_empty() {
                         positional = Some(trimmed.to_string());
@@ -68,15 +160,71 @@ fn resolve_target_triple() -> String {
         }
     }
 
-    positional
-        .or_else(|| env::var("BINDGEN_TARGET").ok())
-        .and_then(|s| {
-            let trimmed = s.trim();
-            if trimmed.is_empty() {
-                None
-            } else {
-                Some(trimmed.to_string())
-            }
-        })
-        .unwrap_or_else(|| "thumbv8m.main-none-eabihf".to_string())
+    if targets.is_empty() {
+        targets = positional
+            .or_else(|| env::var("BINDGEN_TARGET").ok())
+            .map(|s| {
+                s.split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+    }
+    if targets.is_empty() {
+        targets.push("thumbv8m.main-none-eabihf".to_string());
+    }
+    let mut seen = Vec::new();
+    targets.retain(|target| {
+        let first = !seen.contains(target);
+        seen.push(target.clone());
+        first
+    });
+
+    Cli {
+        target_triples: targets,
+        fetch,
+        shared_types,
+        strip_archives,
+        localize_internal_symbols,
+        sysroot,
+        libc,
+        verbose_clang,
+        report,
+        only,
+        audit,
+        cortex_m_types,
+        sha256,
+    }
+}
+
+fn split_targets(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|target| non_empty(target, "Target triple"))
+        .collect()
+}
+
+fn split_modules(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|module| non_empty(module, "Module name"))
+        .collect()
+}
+
+fn parse_libc(value: &str) -> Libc {
+    value.trim().parse().unwrap_or_else(|err| {
+        eprintln!("Invalid --libc: {err}");
+        process::exit(1);
+    })
+}
+
+fn non_empty(value: &str, what: &str) -> String {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        eprintln!("{what} cannot be empty.");
+        process::exit(1);
+    }
+    trimmed.to_string()
 }
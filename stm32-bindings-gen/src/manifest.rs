@@ -0,0 +1,102 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+/// Machine-readable summary of one generated bindings module.
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct Manifest {
+    pub module: String,
+    pub target: String,
+    pub functions: Vec<ManifestSymbol>,
+    pub statics: Vec<ManifestSymbol>,
+    pub types: Vec<String>,
+    pub constants: Vec<String>,
+}
+
+/// An extern item, the symbol it links against, and the archives defining it.
+#[derive(Debug, Serialize)]
+pub(crate) struct ManifestSymbol {
+    pub name: String,
+    pub symbol: String,
+    pub libraries: Vec<String>,
+}
+
+impl Manifest {
+    /// Collects the items of `bindings`. `providers` maps a linker symbol to
+    /// the archives (relative to the generated crate) that define it.
+    pub fn from_bindings(
+        module: &str,
+        target: &str,
+        bindings: &str,
+        providers: &BTreeMap<String, Vec<String>>,
+    ) -> Self {
+        let file = syn::parse_file(bindings)
+            .unwrap_or_else(|err| panic!("Unable to parse generated bindings for {module}: {err}"));
+
+        let mut manifest = Manifest {
+            module: module.to_owned(),
+            target: target.to_owned(),
+            ..Default::default()
+        };
+
+        let symbol = |name: &syn::Ident, attrs: &[syn::Attribute]| {
+            let symbol = link_name(attrs).unwrap_or_else(|| name.to_string());
+            ManifestSymbol {
+                name: name.to_string(),
+                libraries: providers.get(&symbol).cloned().unwrap_or_default(),
+                symbol,
+            }
+        };
+
+        for item in &file.items {
+            match item {
+                syn::Item::ForeignMod(foreign) => {
+                    for item in &foreign.items {
+                        match item {
+                            syn::ForeignItem::Fn(func) => manifest
+                                .functions
+                                .push(symbol(&func.sig.ident, &func.attrs)),
+                            syn::ForeignItem::Static(stat) => {
+                                manifest.statics.push(symbol(&stat.ident, &stat.attrs))
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                syn::Item::Struct(item) => manifest.types.push(item.ident.to_string()),
+                syn::Item::Union(item) => manifest.types.push(item.ident.to_string()),
+                syn::Item::Enum(item) => manifest.types.push(item.ident.to_string()),
+                syn::Item::Type(item) => manifest.types.push(item.ident.to_string()),
+                item if is_layout_check(item) => {}
+                syn::Item::Const(item) => manifest.constants.push(item.ident.to_string()),
+                _ => {}
+            }
+        }
+
+        manifest
+    }
+}
+
+/// Whether `item` is one of bindgen's layout assertions, an unnamed
+/// `const _: () = { ... };` block.
+pub(crate) fn is_layout_check(item: &syn::Item) -> bool {
+    matches!(item, syn::Item::Const(item) if item.ident == "_")
+}
+
+/// Returns the `#[link_name]` of an item without bindgen's `\u{1}` prefix.
+pub(crate) fn link_name(attrs: &[syn::Attribute]) -> Option<String> {
+    attrs.iter().find_map(|attr| match &attr.meta {
+        syn::Meta::NameValue(syn::MetaNameValue {
+            path,
+            value:
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(symbol),
+                    ..
+                }),
+            ..
+        }) if path.is_ident("link_name") => {
+            Some(symbol.value().trim_start_matches('\u{1}').to_owned())
+        }
+        _ => None,
+    })
+}
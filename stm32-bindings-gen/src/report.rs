@@ -0,0 +1,127 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::manifest;
+
+/// Size of the bindings generated for one spec, to track how allowlist
+/// changes affect the emitted crate.
+#[derive(Debug, Default)]
+pub(crate) struct ModuleReport {
+    pub module: String,
+    /// Lines of the bindings of the first target.
+    pub lines: usize,
+    /// Foreign functions declared by the bindings of the first target.
+    pub functions: usize,
+    /// Structs, unions, enums and type aliases of the first target.
+    pub types: usize,
+    /// Constants and statics of the first target, including the associated
+    /// constants of newtype enums.
+    pub constants: usize,
+    /// Time spent in bindgen, summed over all targets.
+    pub bindgen_time: Duration,
+    /// Size of the library artifacts and C sources copied for the spec.
+    pub artifact_bytes: u64,
+}
+
+impl ModuleReport {
+    /// Counts the items of `bindings`, which must be valid Rust.
+    pub fn count_items(&mut self, bindings: &str) {
+        self.lines = bindings.lines().count();
+        let Ok(file) = syn::parse_file(bindings) else {
+            return;
+        };
+        for item in &file.items {
+            match item {
+                syn::Item::ForeignMod(foreign) => {
+                    for item in &foreign.items {
+                        match item {
+                            syn::ForeignItem::Fn(_) => self.functions += 1,
+                            syn::ForeignItem::Static(_) => self.constants += 1,
+                            _ => {}
+                        }
+                    }
+                }
+                syn::Item::Struct(_)
+                | syn::Item::Union(_)
+                | syn::Item::Enum(_)
+                | syn::Item::Type(_) => self.types += 1,
+                item if manifest::is_layout_check(item) => {}
+                syn::Item::Const(_) | syn::Item::Static(_) => self.constants += 1,
+                syn::Item::Impl(imp) if imp.trait_.is_none() => {
+                    self.constants += imp
+                        .items
+                        .iter()
+                        .filter(|item| matches!(item, syn::ImplItem::Const(_)))
+                        .count();
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Total size of the files at `path`, a file or a directory.
+pub(crate) fn disk_size(path: &Path) -> io::Result<u64> {
+    let metadata = fs::metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        size += disk_size(&entry?.path())?;
+    }
+    Ok(size)
+}
+
+/// Renders the reports as a Markdown table with a total row.
+pub(crate) fn render(reports: &[ModuleReport]) -> String {
+    let mut table = String::from(
+        "| Module | Lines | Functions | Types | Constants | bindgen | Artifacts |\n\
+         |---|--:|--:|--:|--:|--:|--:|\n",
+    );
+    let mut total = ModuleReport {
+        module: "total".to_owned(),
+        ..ModuleReport::default()
+    };
+    for report in reports {
+        table.push_str(&row(report));
+        total.lines += report.lines;
+        total.functions += report.functions;
+        total.types += report.types;
+        total.constants += report.constants;
+        total.bindgen_time += report.bindgen_time;
+        total.artifact_bytes += report.artifact_bytes;
+    }
+    table.push_str(&row(&total));
+    table
+}
+
+fn row(report: &ModuleReport) -> String {
+    format!(
+        "| `{}` | {} | {} | {} | {} | {:.2} s | {} |\n",
+        report.module,
+        report.lines,
+        report.functions,
+        report.types,
+        report.constants,
+        report.bindgen_time.as_secs_f64(),
+        format_bytes(report.artifact_bytes),
+    )
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
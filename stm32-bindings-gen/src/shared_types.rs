@@ -0,0 +1,259 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use proc_macro2::{TokenStream, TokenTree};
+use quote::ToTokens;
+
+/// Name of the module that receives the types shared between modules.
+pub(crate) const COMMON_MODULE: &str = "common";
+
+/// Moves type definitions that several modules emit identically into a
+/// common module and replaces them with `pub use super::common::...`.
+///
+/// A type is only shared when its definition and all of its impl blocks are
+/// token-for-token identical in every module emitting it, and every module
+/// item it refers to is shared as well. Returns the common module, or `None`
+/// when no type is shared.
+pub(crate) fn extract_shared_types(modules: &mut [(String, String)]) -> Option<String> {
+    let mut files = modules
+        .iter()
+        .map(|(module, contents)| {
+            syn::parse_file(contents).unwrap_or_else(|err| {
+                panic!("Unable to parse generated bindings for {module}: {err}")
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // Type name -> rendered definition per module, `None` once it differs.
+    let mut definitions: BTreeMap<String, Option<String>> = BTreeMap::new();
+    let mut owners: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    let mut item_names = Vec::new();
+    for (index, file) in files.iter().enumerate() {
+        let types = type_definitions(file);
+        for (name, definition) in &types {
+            owners.entry(name.clone()).or_default().push(index);
+            definitions
+                .entry(name.clone())
+                .and_modify(|existing| {
+                    if existing.as_ref() != Some(definition) {
+                        *existing = None;
+                    }
+                })
+                .or_insert_with(|| Some(definition.clone()));
+        }
+        item_names.push(item_names_of(file));
+    }
+
+    let mut shared = definitions
+        .iter()
+        .filter(|(name, definition)| definition.is_some() && owners[*name].len() > 1)
+        .map(|(name, _)| name.clone())
+        .collect::<BTreeSet<_>>();
+
+    // Drop types referring to module items that stay behind, until stable.
+    loop {
+        let before = shared.clone();
+        shared.retain(|name| {
+            let definition = definitions[name].as_deref().unwrap_or_default();
+            let tokens: TokenStream = definition.parse().unwrap_or_default();
+            let mut idents = BTreeSet::new();
+            collect_idents(tokens, &mut idents);
+            idents.iter().all(|ident| {
+                ident == name
+                    || before.contains(ident)
+                    || !item_names.iter().any(|names| names.contains(ident))
+            })
+        });
+        if shared.len() == before.len() {
+            break;
+        }
+    }
+
+    if shared.is_empty() {
+        return None;
+    }
+
+    let mut common_items = Vec::new();
+    for (index, (file, (_, contents))) in files.iter_mut().zip(modules.iter_mut()).enumerate() {
+        let mut moved = BTreeSet::new();
+        let mut kept = Vec::new();
+        for item in std::mem::take(&mut file.items) {
+            match defined_type(&item) {
+                Some(name) if shared.contains(&name) => {
+                    // Identical everywhere, so the first owner's copy is kept.
+                    if owners[&name][0] == index {
+                        common_items.push(item);
+                    }
+                    moved.insert(name);
+                }
+                _ => kept.push(item),
+            }
+        }
+
+        if !moved.is_empty() {
+            let common = syn::Ident::new(COMMON_MODULE, proc_macro2::Span::call_site());
+            let names = moved
+                .iter()
+                .map(|name| syn::Ident::new(name, proc_macro2::Span::call_site()));
+            kept.insert(
+                0,
+                syn::parse_quote! {
+                    pub use super::#common::{#(#names),*};
+                },
+            );
+        }
+        file.items = kept;
+        *contents = prettyplease::unparse(file);
+    }
+
+    let common = syn::File {
+        shebang: None,
+        attrs: Vec::new(),
+        items: common_items,
+    };
+    Some(prettyplease::unparse(&common))
+}
+
+/// Renders each type of `file` together with its impl blocks.
+fn type_definitions(file: &syn::File) -> BTreeMap<String, String> {
+    let mut definitions: BTreeMap<String, String> = BTreeMap::new();
+    for item in &file.items {
+        if let Some(name) = defined_type(item) {
+            definitions
+                .entry(name)
+                .or_default()
+                .push_str(&item.to_token_stream().to_string());
+        }
+    }
+    definitions
+}
+
+/// Returns the type an item defines, or the type an impl block belongs to.
+fn defined_type(item: &syn::Item) -> Option<String> {
+    match item {
+        syn::Item::Struct(item) => Some(item.ident.to_string()),
+        syn::Item::Union(item) => Some(item.ident.to_string()),
+        syn::Item::Enum(item) => Some(item.ident.to_string()),
+        syn::Item::Type(item) => Some(item.ident.to_string()),
+        syn::Item::Impl(item) => match item.self_ty.as_ref() {
+            syn::Type::Path(ty) if ty.qself.is_none() => {
+                Some(ty.path.segments.last()?.ident.to_string())
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Names of every module-level item, including externs.
+fn item_names_of(file: &syn::File) -> BTreeSet<String> {
+    let mut names = BTreeSet::new();
+    for item in &file.items {
+        match item {
+            syn::Item::Const(item) => {
+                names.insert(item.ident.to_string());
+            }
+            syn::Item::Static(item) => {
+                names.insert(item.ident.to_string());
+            }
+            syn::Item::Fn(item) => {
+                names.insert(item.sig.ident.to_string());
+            }
+            syn::Item::ForeignMod(foreign) => {
+                for item in &foreign.items {
+                    match item {
+                        syn::ForeignItem::Fn(func) => names.insert(func.sig.ident.to_string()),
+                        syn::ForeignItem::Static(stat) => names.insert(stat.ident.to_string()),
+                        _ => false,
+                    };
+                }
+            }
+            item => {
+                if let Some(name) = defined_type(item) {
+                    names.insert(name);
+                }
+            }
+        }
+    }
+    names
+}
+
+/// Adds every identifier of `tokens` to `idents`, including the ones in
+/// nested groups.
+pub(crate) fn collect_idents(tokens: TokenStream, idents: &mut BTreeSet<String>) {
+    for token in tokens {
+        match token {
+            TokenTree::Ident(ident) => {
+                idents.insert(ident.to_string());
+            }
+            TokenTree::Group(group) => collect_idents(group.stream(), idents),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modules(contents: &[&str]) -> Vec<(String, String)> {
+        contents
+            .iter()
+            .enumerate()
+            .map(|(index, contents)| (format!("module{index}"), contents.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn identical_types_move_to_common() {
+        let definition = r#"
+            #[repr(C)]
+            #[derive(Debug, Copy, Clone)]
+            pub struct ble_buff_hdr_t {
+                pub next: *mut ble_buff_hdr_t,
+                pub len: u16,
+            }
+        "#;
+        let mut modules = modules(&[
+            &format!(r#"{definition} unsafe extern "C" {{ pub fn ll_init(); }}"#),
+            &format!(r#"{definition} unsafe extern "C" {{ pub fn mac_init(); }}"#),
+        ]);
+
+        let common = extract_shared_types(&mut modules).expect("no shared type");
+        assert!(common.contains("pub struct ble_buff_hdr_t"));
+        for (_, contents) in &modules {
+            assert!(contents.contains("pub use super::common::ble_buff_hdr_t;"));
+            assert!(!contents.contains("pub struct ble_buff_hdr_t"));
+        }
+    }
+
+    #[test]
+    fn differing_definitions_stay_in_their_modules() {
+        let mut modules = modules(&["pub type status_t = u8;", "pub type status_t = u16;"]);
+
+        assert_eq!(extract_shared_types(&mut modules), None);
+        assert!(modules[0].1.contains("pub type status_t = u8;"));
+        assert!(modules[1].1.contains("pub type status_t = u16;"));
+    }
+
+    #[test]
+    fn types_referring_to_module_items_stay_in_their_modules() {
+        // Identical, but the array length is a constant of each module.
+        let definition = r#"
+            pub const BUF_LEN: u32 = 8;
+            #[repr(C)]
+            pub struct buffer_t { pub data: [u8; BUF_LEN as usize] }
+            pub type buffer_ptr_t = *mut buffer_t;
+            pub type handle_t = u32;
+        "#;
+        let mut modules = modules(&[definition, definition]);
+
+        let common = extract_shared_types(&mut modules).expect("no shared type");
+        assert!(common.contains("pub type handle_t = u32;"));
+        assert!(!common.contains("buffer_t"));
+        for (_, contents) in &modules {
+            assert!(contents.contains("pub use super::common::handle_t;"));
+            assert!(contents.contains("pub struct buffer_t"));
+            assert!(contents.contains("pub type buffer_ptr_t = *mut buffer_t;"));
+        }
+    }
+}
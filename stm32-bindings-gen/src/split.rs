@@ -0,0 +1,332 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use quote::ToTokens;
+
+use crate::manifest::is_layout_check;
+use crate::shared_types::collect_idents;
+
+/// Items of a module moved to the submodule `name`, compiled only with the
+/// emitted crate's `feature`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SplitArea {
+    pub name: &'static str,
+    pub feature: &'static str,
+    /// Prefixes of the item names belonging to the area. An item goes to the
+    /// first area with a matching prefix.
+    pub prefixes: &'static [&'static str],
+}
+
+/// A module split into areas: its own contents, declaring the submodules,
+/// and the contents of each submodule.
+#[derive(Debug)]
+pub(crate) struct SplitModule {
+    pub root: String,
+    pub submodules: Vec<(&'static str, String)>,
+}
+
+/// Moves the items of `contents` named with an area's prefix into the
+/// area's submodule, re-exported from the module so item paths do not
+/// change.
+///
+/// Impl blocks and layout checks follow the type they belong to. An item
+/// referred to from outside its area, e.g. a type used by functions of two
+/// areas, stays in the module, so disabling an area never breaks the
+/// others.
+pub(crate) fn split_module(module: &str, contents: &str, areas: &[SplitArea]) -> SplitModule {
+    let file = syn::parse_file(contents)
+        .unwrap_or_else(|err| panic!("Unable to parse generated bindings for {module}: {err}"));
+
+    let mut items = Vec::new();
+    for item in file.items {
+        match item {
+            // Split extern blocks into one block per item, so each item can
+            // move on its own.
+            syn::Item::ForeignMod(foreign) => {
+                for foreign_item in &foreign.items {
+                    let mut single = foreign.clone();
+                    single.items = vec![foreign_item.clone()];
+                    items.push(syn::Item::ForeignMod(single));
+                }
+            }
+            item => items.push(item),
+        }
+    }
+
+    let names = items.iter().map(item_name).collect::<Vec<_>>();
+    let defined = names.iter().flatten().cloned().collect::<BTreeSet<_>>();
+    let owners = items
+        .iter()
+        .zip(&names)
+        .map(|(item, name)| name.clone().or_else(|| owner(item, &defined)))
+        .collect::<Vec<_>>();
+    let references = items
+        .iter()
+        .map(|item| {
+            let mut idents = BTreeSet::new();
+            collect_idents(item.to_token_stream(), &mut idents);
+            idents.retain(|ident| defined.contains(ident));
+            idents
+        })
+        .collect::<Vec<_>>();
+
+    // Name -> index of its area, for the items that may move.
+    let mut area_of = defined
+        .iter()
+        .filter_map(|name| {
+            let area = areas
+                .iter()
+                .position(|area| area.prefixes.iter().any(|prefix| name.starts_with(prefix)))?;
+            Some((name.clone(), area))
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    // Keep in the module what other areas refer to, until stable.
+    loop {
+        let mut shared = BTreeSet::new();
+        for (owner, references) in owners.iter().zip(&references) {
+            let area = owner.as_ref().and_then(|owner| area_of.get(owner));
+            for reference in references {
+                if Some(reference) != owner.as_ref()
+                    && let Some(target) = area_of.get(reference)
+                    && area != Some(target)
+                {
+                    shared.insert(reference.clone());
+                }
+            }
+        }
+        if shared.is_empty() {
+            break;
+        }
+        area_of.retain(|name, _| !shared.contains(name));
+    }
+
+    let mut root_items = Vec::new();
+    let mut area_items = vec![Vec::new(); areas.len()];
+    for (item, owner) in items.into_iter().zip(&owners) {
+        match owner.as_ref().and_then(|owner| area_of.get(owner)) {
+            Some(&area) => area_items[area].push(item),
+            None => root_items.push(item),
+        }
+    }
+
+    let mut submodules = Vec::new();
+    for (area, items) in areas.iter().zip(area_items) {
+        if items.is_empty() {
+            continue;
+        }
+        let name = syn::Ident::new(area.name, proc_macro2::Span::call_site());
+        let feature = area.feature;
+        root_items.push(syn::parse_quote! {
+            #[cfg(feature = #feature)]
+            mod #name;
+        });
+        root_items.push(syn::parse_quote! {
+            #[cfg(feature = #feature)]
+            pub use self::#name::*;
+        });
+
+        let mut submodule: syn::File = syn::parse_quote! {
+            #[allow(unused_imports)]
+            use super::*;
+        };
+        submodule.items.extend(merge_extern_blocks(items));
+        submodules.push((area.name, prettyplease::unparse(&submodule)));
+    }
+
+    let root = syn::File {
+        shebang: None,
+        attrs: file.attrs,
+        items: merge_extern_blocks(root_items),
+    };
+    SplitModule {
+        root: prettyplease::unparse(&root),
+        submodules,
+    }
+}
+
+/// Joins consecutive extern blocks with the same ABI and attributes.
+fn merge_extern_blocks(items: Vec<syn::Item>) -> Vec<syn::Item> {
+    let header = |foreign: &syn::ItemForeignMod| {
+        let mut header = foreign.clone();
+        header.items.clear();
+        header.to_token_stream().to_string()
+    };
+    let mut merged: Vec<syn::Item> = Vec::new();
+    for item in items {
+        if let (Some(syn::Item::ForeignMod(last)), syn::Item::ForeignMod(foreign)) =
+            (merged.last_mut(), &item)
+            && header(last) == header(foreign)
+        {
+            last.items.extend(foreign.items.iter().cloned());
+            continue;
+        }
+        merged.push(item);
+    }
+    merged
+}
+
+/// Name an item defines. Extern blocks must hold a single item.
+fn item_name(item: &syn::Item) -> Option<String> {
+    if is_layout_check(item) {
+        return None;
+    }
+    let ident = match item {
+        syn::Item::Struct(item) => &item.ident,
+        syn::Item::Union(item) => &item.ident,
+        syn::Item::Enum(item) => &item.ident,
+        syn::Item::Type(item) => &item.ident,
+        syn::Item::Const(item) => &item.ident,
+        syn::Item::Static(item) => &item.ident,
+        syn::Item::Fn(item) => &item.sig.ident,
+        syn::Item::ForeignMod(foreign) => match foreign.items.first()? {
+            syn::ForeignItem::Fn(func) => &func.sig.ident,
+            syn::ForeignItem::Static(stat) => &stat.ident,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(ident.to_string())
+}
+
+/// Type an unnamed item belongs to: the self type of an impl block, or the
+/// only type a layout check refers to.
+fn owner(item: &syn::Item, defined: &BTreeSet<String>) -> Option<String> {
+    match item {
+        syn::Item::Impl(item) => match item.self_ty.as_ref() {
+            syn::Type::Path(ty) if ty.qself.is_none() => {
+                Some(ty.path.segments.last()?.ident.to_string())
+            }
+            _ => None,
+        },
+        _ if is_layout_check(item) => {
+            let mut idents = BTreeSet::new();
+            collect_idents(item.to_token_stream(), &mut idents);
+            idents.retain(|ident| defined.contains(ident));
+            let mut idents = idents.into_iter();
+            match (idents.next(), idents.next()) {
+                (Some(ident), None) => Some(ident),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AREAS: &[SplitArea] = &[
+        SplitArea {
+            name: "gap",
+            feature: "ble_gap",
+            prefixes: &["gap_"],
+        },
+        SplitArea {
+            name: "gatt",
+            feature: "ble_gatt",
+            prefixes: &["gatt_"],
+        },
+    ];
+
+    fn submodule<'a>(split: &'a SplitModule, name: &str) -> &'a str {
+        split
+            .submodules
+            .iter()
+            .find(|(submodule, _)| *submodule == name)
+            .map_or("", |(_, contents)| contents)
+    }
+
+    #[test]
+    fn items_move_to_their_area() {
+        let contents = r#"
+            pub type status_t = u8;
+            pub type gap_role_t = u8;
+            unsafe extern "C" {
+                pub fn gap_init(role: gap_role_t) -> status_t;
+                pub fn gatt_init() -> status_t;
+            }
+        "#;
+        let split = split_module("test", contents, AREAS);
+
+        let gap = submodule(&split, "gap");
+        assert!(gap.contains("pub type gap_role_t = u8;"), "{gap}");
+        assert!(gap.contains("pub fn gap_init("), "{gap}");
+        assert!(submodule(&split, "gatt").contains("pub fn gatt_init("));
+        assert!(split.root.contains("pub type status_t = u8;"));
+        assert!(!split.root.contains("gap_role_t"));
+        assert!(
+            split
+                .root
+                .contains("#[cfg(feature = \"ble_gap\")]\nmod gap;")
+        );
+        assert!(split.root.contains("pub use self::gap::*;"));
+    }
+
+    #[test]
+    fn impls_and_layout_checks_follow_their_type() {
+        let contents = r#"
+            #[repr(C)]
+            #[derive(Debug, Copy, Clone)]
+            pub struct gap_params_t { pub interval: u16 }
+            impl Default for gap_params_t {
+                fn default() -> Self { Self { interval: 0 } }
+            }
+            const _: () = {
+                ["Size of gap_params_t"][::core::mem::size_of::<gap_params_t>() - 2usize];
+            };
+            pub type status_t = u8;
+            #[repr(C)]
+            pub struct gatt_handle_t { pub value: u16 }
+            const _: () = {
+                ["Mixed"][::core::mem::size_of::<gatt_handle_t>()
+                    - ::core::mem::size_of::<status_t>()];
+            };
+        "#;
+        let split = split_module("test", contents, AREAS);
+
+        let gap = submodule(&split, "gap");
+        assert!(gap.contains("impl Default for gap_params_t"), "{gap}");
+        assert!(gap.contains("Size of gap_params_t"), "{gap}");
+        assert!(!split.root.contains("impl Default"), "{}", split.root);
+        assert!(
+            !split.root.contains("Size of gap_params_t"),
+            "{}",
+            split.root
+        );
+        // A check of several types stays in the module, and keeps them there.
+        assert!(split.root.contains("\"Mixed\""), "{}", split.root);
+        assert!(
+            split.root.contains("pub struct gatt_handle_t"),
+            "{}",
+            split.root
+        );
+        assert!(split.submodules.iter().all(|(name, _)| *name != "gatt"));
+    }
+
+    #[test]
+    fn shared_items_stay_in_the_module_with_what_they_refer_to() {
+        // `gatt_write` keeps `gap_conn_t` in the module, which then keeps
+        // `gap_addr_t` there too, as the module cannot see into `gap`.
+        let contents = r#"
+            pub type gap_addr_t = [u8; 6usize];
+            #[repr(C)]
+            pub struct gap_conn_t { pub peer: gap_addr_t }
+            pub type gap_tx_power_t = i8;
+            unsafe extern "C" {
+                pub fn gap_connect(conn: *mut gap_conn_t, power: gap_tx_power_t);
+                pub fn gatt_write(conn: *const gap_conn_t);
+            }
+        "#;
+        let split = split_module("test", contents, AREAS);
+
+        for shared in ["pub struct gap_conn_t", "pub type gap_addr_t"] {
+            assert!(split.root.contains(shared), "{shared}: {}", split.root);
+        }
+        let gap = submodule(&split, "gap");
+        assert!(gap.contains("pub type gap_tx_power_t = i8;"), "{gap}");
+        assert!(gap.contains("pub fn gap_connect("), "{gap}");
+        assert!(!gap.contains("gap_addr_t"), "{gap}");
+        assert!(submodule(&split, "gatt").contains("pub fn gatt_write("));
+    }
+}
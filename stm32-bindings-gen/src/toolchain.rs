@@ -0,0 +1,444 @@
+use std::collections::BTreeSet;
+use std::env;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// C library whose headers the bindings are generated against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Libc {
+    Newlib,
+    Picolibc,
+}
+
+impl FromStr for Libc {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "newlib" => Ok(Libc::Newlib),
+            "picolibc" => Ok(Libc::Picolibc),
+            _ => Err(format!(
+                "unknown C library `{s}`, expected newlib or picolibc"
+            )),
+        }
+    }
+}
+
+impl Libc {
+    /// Recognizes the C library of `sysroot` by its configuration header.
+    fn detect(sysroot: &Path) -> Option<Self> {
+        if sysroot.join("include/picolibc.h").is_file() {
+            Some(Libc::Picolibc)
+        } else if ["include/newlib.h", "usr/include/newlib.h"]
+            .iter()
+            .any(|header| sysroot.join(header).is_file())
+        {
+            Some(Libc::Newlib)
+        } else {
+            None
+        }
+    }
+
+    /// Header directories of a sysroot with this C library.
+    fn include_dirs(self) -> &'static [&'static str] {
+        match self {
+            Libc::Newlib => &[
+                "include",
+                "include-fixed",
+                "usr/include",
+                "usr/include/newlib",
+                "arm-none-eabi/include",
+            ],
+            Libc::Picolibc => &["include", "arm-none-eabi/include"],
+        }
+    }
+}
+
+/// System include directories collected for clang.
+#[derive(Default)]
+struct SystemIncludes {
+    args: Vec<String>,
+    /// Searched first, so they win over the toolchain's default C library.
+    preferred: Vec<PathBuf>,
+    paths: BTreeSet<PathBuf>,
+}
+
+impl SystemIncludes {
+    /// Adds `path` as a sysroot. Its C library is `libc`, or detected from
+    /// its headers, defaulting to newlib.
+    fn push_sysroot(&mut self, path: &Path, libc: Option<Libc>) {
+        let libc = libc.or_else(|| Libc::detect(path)).unwrap_or(Libc::Newlib);
+        for dir in libc.include_dirs() {
+            self.paths.insert(path.join(dir));
+        }
+
+        let arg = format!("--sysroot={}", path.display());
+        if !self.args.contains(&arg) {
+            self.args.push(arg);
+        }
+    }
+
+    fn into_args(self) -> Vec<String> {
+        let mut args = self.args;
+        let paths = self.preferred.into_iter().chain(self.paths);
+        for path in paths {
+            if path.exists() {
+                let flag = format!("-isystem{}", path.display());
+                if !args.contains(&flag) {
+                    args.push(flag);
+                }
+            }
+        }
+        args
+    }
+}
+
+/// Returns the clang arguments locating the C headers for `target`.
+///
+/// An explicit `sysroot` is used as is. Otherwise `ARM_NONE_EABI_SYSROOT`
+/// and the GNU Arm toolchain are probed and, without `arm-none-eabi-gcc`,
+/// the LLVM Embedded Toolchain for Arm. With picolibc, its headers are
+/// searched before the toolchain's own C library.
+pub(crate) fn sysroot_args(
+    target: &str,
+    sysroot: Option<&Path>,
+    libc: Option<Libc>,
+) -> Vec<String> {
+    let mut includes = SystemIncludes::default();
+
+    if let Some(sysroot) = sysroot {
+        includes.push_sysroot(sysroot, libc);
+    } else {
+        if let Some(sysroot_os) = env::var_os("ARM_NONE_EABI_SYSROOT") {
+            let sysroot_path = PathBuf::from(&sysroot_os);
+            if sysroot_path.exists() {
+                includes.push_sysroot(&sysroot_path, libc);
+            }
+        }
+
+        if gcc_query(&["-dumpmachine"]).is_some() {
+            push_gnu_includes(&mut includes);
+            if libc == Some(Libc::Picolibc) {
+                includes.preferred.extend(picolibc_include_dirs());
+            }
+        } else if let Some(sysroot) = llvm_embedded_sysroot(target, libc) {
+            includes.push_sysroot(&sysroot, libc);
+        }
+    }
+
+    if let Some(extra) = env::var_os("ARM_NONE_EABI_INCLUDE") {
+        for path in env::split_paths(&extra) {
+            includes.paths.insert(path);
+        }
+    }
+
+    includes.into_args()
+}
+
+fn push_gnu_includes(includes: &mut SystemIncludes) {
+    if let Some(sysroot) = gcc_query(&["-print-sysroot"]) {
+        let sysroot = sysroot.trim();
+        if !sysroot.is_empty() {
+            includes.push_sysroot(Path::new(sysroot), Some(Libc::Newlib));
+        }
+    }
+
+    if let Some(include_dir) = gcc_query(&["-print-file-name=include"]) {
+        let include_dir = include_dir.trim();
+        if !include_dir.is_empty() && include_dir != "include" {
+            includes.paths.insert(PathBuf::from(include_dir));
+        }
+    }
+
+    if let Some(libgcc) = gcc_query(&["-print-libgcc-file-name"]) {
+        let libgcc_path = Path::new(libgcc.trim());
+        if let Some(version_dir) = libgcc_path.parent() {
+            includes.paths.insert(version_dir.join("include"));
+            includes.paths.insert(version_dir.join("include-fixed"));
+
+            if let Some(toolchain_root) = version_dir.parent()
+                && let Some(version) = version_dir.file_name().and_then(|name| name.to_str())
+            {
+                includes
+                    .paths
+                    .insert(toolchain_root.join("include").join("c++").join(version));
+                includes.paths.insert(
+                    toolchain_root
+                        .join("include")
+                        .join("c++")
+                        .join(version)
+                        .join("arm-none-eabi"),
+                );
+            }
+        }
+    }
+
+    for path in gcc_include_search_paths() {
+        includes.paths.insert(path);
+    }
+}
+
+/// Header directories of picolibc installed next to the GNU Arm toolchain,
+/// as Debian's `picolibc-arm-none-eabi` package or Arm's GNU toolchain
+/// releases do, or at `PICOLIBC_SYSROOT`.
+fn picolibc_include_dirs() -> Vec<PathBuf> {
+    let mut roots = Vec::new();
+    if let Some(root) = env::var_os("PICOLIBC_SYSROOT") {
+        roots.push(PathBuf::from(root));
+    }
+    if let Some(sysroot) = gcc_query(&["-print-sysroot"]) {
+        let sysroot = Path::new(sysroot.trim());
+        roots.push(sysroot.join("picolibc"));
+        if let Some(parent) = sysroot.parent() {
+            roots.push(parent.join("picolibc").join("arm-none-eabi"));
+        }
+    }
+    roots.push(PathBuf::from("/usr/lib/picolibc/arm-none-eabi"));
+
+    roots
+        .into_iter()
+        .map(|root| root.join("include"))
+        .filter(|include| include.join("picolibc.h").is_file())
+        .take(1)
+        .collect()
+}
+
+/// Finds the library variant of the LLVM Embedded Toolchain for Arm matching
+/// `target`, below `lib/clang-runtimes` of the toolchain. The toolchain is
+/// found through `LLVM_EMBEDDED_TOOLCHAIN` or the `clang` on the `PATH`.
+fn llvm_embedded_sysroot(target: &str, libc: Option<Libc>) -> Option<PathBuf> {
+    let root = match env::var_os("LLVM_EMBEDDED_TOOLCHAIN") {
+        Some(root) => PathBuf::from(root),
+        None => {
+            let output = Command::new("clang")
+                .arg("-print-resource-dir")
+                .output()
+                .ok()?;
+            let resource_dir = PathBuf::from(String::from_utf8(output.stdout).ok()?.trim());
+            // <root>/lib/clang/<version>
+            resource_dir.parent()?.parent()?.parent()?.to_path_buf()
+        }
+    };
+    let runtimes = root.join("lib/clang-runtimes");
+    let variant = llvm_variant(target)?;
+
+    // Releases 17 and earlier put the variants directly below
+    // `clang-runtimes`, later ones below a directory per C library or triple.
+    let mut candidates = vec![runtimes.join(variant)];
+    let mut nested = std::fs::read_dir(&runtimes)
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path().join(variant))
+        .collect::<Vec<_>>();
+    nested.sort();
+    candidates.extend(nested);
+
+    candidates.into_iter().find(|candidate| {
+        candidate.join("include").is_dir()
+            && libc.is_none_or(|libc| Libc::detect(candidate) == Some(libc))
+    })
+}
+
+/// Name of the LLVM Embedded Toolchain library variant for a Rust target.
+fn llvm_variant(target: &str) -> Option<&'static str> {
+    let variant = match target.trim() {
+        "thumbv6m-none-eabi" => "armv6m_soft_nofp",
+        "thumbv7m-none-eabi" => "armv7m_soft_nofp",
+        "thumbv7em-none-eabi" => "armv7em_soft_nofp",
+        "thumbv7em-none-eabihf" => "armv7em_hard_fpv4_sp_d16",
+        "thumbv8m.main-none-eabi" => "armv8m.main_soft_nofp",
+        "thumbv8m.main-none-eabihf" => "armv8m.main_hard_fp",
+        _ => return None,
+    };
+    Some(variant)
+}
+
+/// Path of the GNU Arm toolchain program `arm-none-eabi-<tool>`: the one
+/// on the `PATH`, else the one of `ARM_NONE_EABI_BIN` or, on Windows, of a
+/// standard install.
+pub(crate) fn gnu_tool(tool: &str) -> PathBuf {
+    let name = format!("arm-none-eabi-{tool}{}", env::consts::EXE_SUFFIX);
+    match gnu_bin_dir() {
+        Some(dir) => dir.join(name),
+        None => PathBuf::from(name),
+    }
+}
+
+/// `bin` directory of the GNU Arm toolchain when it is not on the `PATH`.
+fn gnu_bin_dir() -> Option<&'static Path> {
+    static DIR: OnceLock<Option<PathBuf>> = OnceLock::new();
+    DIR.get_or_init(|| {
+        if let Some(dir) = env::var_os("ARM_NONE_EABI_BIN") {
+            return Some(PathBuf::from(dir));
+        }
+        let on_path = Command::new("arm-none-eabi-gcc")
+            .arg("-dumpmachine")
+            .output()
+            .is_ok_and(|output| output.status.success());
+        if on_path || !cfg!(windows) {
+            return None;
+        }
+        windows_install_dirs()
+            .into_iter()
+            .find(|dir| dir.join("arm-none-eabi-gcc.exe").is_file())
+    })
+    .as_deref()
+}
+
+/// `bin` directories of the GNU Arm toolchain installs Windows hosts
+/// usually have, the newest version of each kind first: Arm's installers,
+/// STM32CubeCLT and the toolchain bundled with STM32CubeIDE.
+fn windows_install_dirs() -> Vec<PathBuf> {
+    let mut dirs = registry_install_dirs();
+
+    for var in ["ProgramFiles", "ProgramFiles(x86)"] {
+        let Some(base) = env::var_os(var) else {
+            continue;
+        };
+        let base = PathBuf::from(base);
+        for vendor in [
+            "Arm GNU Toolchain arm-none-eabi",
+            "GNU Arm Embedded Toolchain",
+        ] {
+            for version in subdirs(&base.join(vendor), "") {
+                dirs.push(version.join("bin"));
+            }
+        }
+    }
+
+    let drive = env::var("SystemDrive").unwrap_or_else(|_| "C:".to_owned());
+    let st = PathBuf::from(format!("{drive}\\ST"));
+    for clt in subdirs(&st, "STM32CubeCLT") {
+        dirs.push(clt.join("GNU-tools-for-STM32").join("bin"));
+    }
+    for ide in subdirs(&st, "STM32CubeIDE") {
+        let plugins = ide.join("STM32CubeIDE").join("plugins");
+        let prefix = "com.st.stm32cube.ide.mcu.externaltools.gnu-tools-for-stm32";
+        for plugin in subdirs(&plugins, prefix) {
+            dirs.push(plugin.join("tools").join("bin"));
+        }
+    }
+
+    dirs
+}
+
+/// Install folders the GNU Arm Embedded Toolchain installer records in the
+/// registry, one per installed version.
+fn registry_install_dirs() -> Vec<PathBuf> {
+    let Ok(output) = Command::new("reg")
+        .args([
+            "query",
+            r"HKLM\SOFTWARE\WOW6432Node\ARM",
+            "/s",
+            "/v",
+            "InstallFolder",
+        ])
+        .output()
+    else {
+        return Vec::new();
+    };
+    let mut dirs = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split_once("REG_SZ"))
+        .map(|(_, folder)| PathBuf::from(folder.trim()).join("bin"))
+        .collect::<Vec<_>>();
+    dirs.sort();
+    dirs.reverse();
+    dirs
+}
+
+/// Subdirectories of `dir` whose name starts with `prefix`, in reverse name
+/// order so the newest version comes first.
+fn subdirs(dir: &Path, prefix: &str) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut dirs = entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with(prefix))
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect::<Vec<_>>();
+    dirs.sort();
+    dirs.reverse();
+    dirs
+}
+
+fn gcc_include_search_paths() -> Vec<PathBuf> {
+    let mut command = Command::new(gnu_tool("gcc"));
+    command.args(["-xc", "-E", "-Wp,-v", "-"]);
+    command.stdin(Stdio::piped());
+    command.stdout(Stdio::null());
+    command.stderr(Stdio::piped());
+
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(_) => return Vec::new(),
+    };
+
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(b"\n");
+    }
+
+    let output = match child.wait_with_output() {
+        Ok(output) => output,
+        Err(_) => return Vec::new(),
+    };
+
+    if !output.status.success() {
+        return Vec::new();
+    }
+
+    let stderr = match String::from_utf8(output.stderr) {
+        Ok(text) => text,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut paths = Vec::new();
+    let mut capture = false;
+
+    for line in stderr.lines() {
+        if line.contains("#include <...> search starts here:") {
+            capture = true;
+            continue;
+        }
+        if capture {
+            if line.contains("End of search list.") {
+                break;
+            }
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            let trimmed = trimmed.trim_start_matches("(framework directory) ");
+            let trimmed = trimmed.trim_end_matches(" (framework directory)");
+            if trimmed.is_empty() {
+                continue;
+            }
+            let candidate = PathBuf::from(trimmed);
+            if candidate.is_relative() {
+                continue;
+            }
+            paths.push(candidate);
+        }
+    }
+
+    paths
+}
+
+fn gcc_query(args: &[&str]) -> Option<String> {
+    let mut command = Command::new(gnu_tool("gcc"));
+    for arg in args {
+        command.arg(arg);
+    }
+    command.output().ok().and_then(|output| {
+        if output.status.success() {
+            String::from_utf8(output.stdout).ok()
+        } else {
+            None
+        }
+    })
+}
@@ -0,0 +1,123 @@
+// The module is built with the emitted crate's `adv_data` feature.
+#![allow(unexpected_cfgs)]
+
+#[allow(dead_code)]
+#[path = "../res/src/adv_data.rs"]
+mod adv_data;
+
+use adv_data::*;
+
+/// 128-bit UUID 6E400001-B5A3-F393-E0A9-E50E24DCCA9E, little-endian.
+const NUS: [u8; 16] = [
+    0x9E, 0xCA, 0xDC, 0x24, 0x0E, 0xE5, 0xA9, 0xE0, 0x93, 0xF3, 0xA3, 0xB5, 0x01, 0x00, 0x40, 0x6E,
+];
+
+#[test]
+fn builder_packs_length_type_and_value() {
+    let mut data = LegacyAdvData::new();
+    data.flags(Flags::LE_ONLY_GENERAL_DISCOVERABLE).unwrap();
+    data.service_uuids_16(&[0x180F, 0x181A], true).unwrap();
+    data.complete_local_name("WBA").unwrap();
+    data.tx_power_level(-4).unwrap();
+    assert_eq!(
+        data.as_bytes(),
+        [
+            0x02, 0x01, 0x06, // flags
+            0x05, 0x03, 0x0F, 0x18, 0x1A, 0x18, // UUIDs
+            0x04, 0x09, b'W', b'B', b'A', // name
+            0x02, 0x0A, 0xFC, // TX power
+        ]
+    );
+}
+
+#[test]
+fn legacy_data_is_limited_to_31_bytes() {
+    let mut data = LegacyAdvData::new();
+    data.flags(Flags::LE_GENERAL_DISCOVERABLE).unwrap();
+    data.service_uuids_128(&[NUS], true).unwrap();
+    assert_eq!(data.as_bytes().len(), 21);
+
+    // 2 + 9 bytes would make 32.
+    assert_eq!(data.complete_local_name("123456789"), Err(TooLong));
+    assert_eq!(data.as_bytes().len(), 21);
+    data.shortened_local_name("12345678").unwrap();
+    assert_eq!(data.as_bytes().len(), 31);
+    assert_eq!(data.tx_power_level(0), Err(TooLong));
+
+    let mut data = ExtendedAdvData::new();
+    data.manufacturer_data(0x0030, &[0xAA; 200]).unwrap();
+    assert_eq!(data.manufacturer_data(0x0030, &[0xAA; 44]), Err(TooLong));
+    data.manufacturer_data(0x0030, &[0xAA; 43]).unwrap();
+    assert_eq!(data.as_bytes().len(), 251);
+}
+
+#[test]
+fn parser_borrows_what_the_builder_wrote() {
+    let mut data = ExtendedAdvData::new();
+    data.flags(Flags::LE_LIMITED_DISCOVERABLE | Flags::BR_EDR_NOT_SUPPORTED)
+        .unwrap();
+    data.complete_local_name("sensor").unwrap();
+    data.service_uuids_16(&[0x1809], false).unwrap();
+    data.service_uuids_128(&[NUS], false).unwrap();
+    data.service_data_16(0x1809, &[0x12, 0x34]).unwrap();
+    data.manufacturer_data(0x0030, &[1, 2, 3]).unwrap();
+    data.tx_power_level(8).unwrap();
+    data.push(AdType::APPEARANCE, &[&0x0341u16.to_le_bytes()])
+        .unwrap();
+
+    let structures: Vec<_> = parse(data.as_bytes()).map(Result::unwrap).collect();
+    assert_eq!(structures.len(), 8);
+    let flags = structures[0].flags().unwrap();
+    assert!(flags.contains(Flags::LE_LIMITED_DISCOVERABLE));
+    assert!(!flags.contains(Flags::LE_GENERAL_DISCOVERABLE));
+    assert_eq!(structures[1].local_name(), Some("sensor"));
+    assert_eq!(
+        structures[2]
+            .service_uuids_16()
+            .unwrap()
+            .collect::<Vec<_>>(),
+        [0x1809]
+    );
+    assert_eq!(
+        structures[3]
+            .service_uuids_128()
+            .unwrap()
+            .collect::<Vec<_>>(),
+        [NUS]
+    );
+    assert_eq!(
+        structures[4].service_data_16(),
+        Some((0x1809, &[0x12, 0x34][..]))
+    );
+    assert_eq!(
+        structures[5].manufacturer_data(),
+        Some((0x0030, &[1, 2, 3][..]))
+    );
+    assert_eq!(structures[6].tx_power_level(), Some(8));
+    assert_eq!(structures[7].ad_type, AdType::APPEARANCE);
+    assert_eq!(structures[7].value, [0x41, 0x03]);
+    assert_eq!(structures[7].local_name(), None);
+
+    // The values point into the parsed data.
+    let name = structures[1].value.as_ptr();
+    assert!(data.as_bytes().as_ptr_range().contains(&name));
+}
+
+#[test]
+fn parser_stops_at_padding_and_reports_overruns() {
+    let padded = [0x02, 0x01, 0x06, 0x00, 0x00, 0x00];
+    let structures: Vec<_> = parse(&padded).collect();
+    assert_eq!(
+        structures,
+        [Ok(AdStructure {
+            ad_type: AdType::FLAGS,
+            value: &[0x06],
+        })]
+    );
+
+    let truncated = [0x02, 0x01, 0x06, 0x05, 0x09, b'a'];
+    let structures: Vec<_> = parse(&truncated).collect();
+    assert_eq!(structures.len(), 2);
+    assert_eq!(structures[1], Err(Malformed));
+    assert_eq!(parse(&[]).count(), 0);
+}
@@ -0,0 +1,75 @@
+// The accessors below are written the way bindgen emits them.
+#![allow(clippy::useless_transmute, unnecessary_transmutes)]
+
+#[allow(dead_code)]
+#[path = "../res/src/bitfield_unit.rs"]
+mod bitfield_unit;
+
+use bitfield_unit::{__BindgenBitfieldUnit, FromLowBits};
+
+/// A struct with `int wide : 32; unsigned char narrow : 3;`, with the
+/// accessors `--audit` emits.
+#[derive(Default)]
+struct Fields {
+    _bitfield_1: __BindgenBitfieldUnit<[u8; 5]>,
+}
+
+impl Fields {
+    fn wide(&self) -> i32 {
+        unsafe {
+            core::mem::transmute(<u32 as crate::bitfield_unit::FromLowBits>::from_low_bits(
+                self._bitfield_1.get(0usize, 32u8),
+            ))
+        }
+    }
+
+    fn set_wide(&mut self, val: i32) {
+        unsafe {
+            let val: u32 = core::mem::transmute(val);
+            self._bitfield_1.set(0usize, 32u8, <u64>::from(val))
+        }
+    }
+
+    fn narrow(&self) -> u8 {
+        unsafe {
+            core::mem::transmute(<u8 as crate::bitfield_unit::FromLowBits>::from_low_bits(
+                self._bitfield_1.get(32usize, 3u8),
+            ))
+        }
+    }
+
+    fn set_narrow(&mut self, val: u8) {
+        unsafe {
+            let val: u8 = core::mem::transmute(val);
+            self._bitfield_1.set(32usize, 3u8, <u64>::from(val))
+        }
+    }
+}
+
+#[test]
+fn full_width_signed_fields_keep_their_sign() {
+    let mut fields = Fields::default();
+    for value in [i32::MIN, -1, 0, 1, i32::MAX] {
+        fields.set_wide(value);
+        assert_eq!(fields.wide(), value);
+    }
+}
+
+#[test]
+fn narrow_fields_keep_only_their_bits() {
+    let mut fields = Fields::default();
+    fields.set_wide(-1);
+    fields.set_narrow(0b1101);
+    assert_eq!(fields.narrow(), 0b101);
+    assert_eq!(fields.wide(), -1);
+}
+
+#[test]
+fn low_bits_are_kept() {
+    let val = 0x1122_3344_5566_7788;
+    assert_eq!(u8::from_low_bits(val), 0x88);
+    assert_eq!(u16::from_low_bits(val), 0x7788);
+    assert_eq!(u32::from_low_bits(val), 0x5566_7788);
+    assert_eq!(u64::from_low_bits(val), val);
+    assert_eq!(u128::from_low_bits(val), 0x1122_3344_5566_7788);
+}
@@ -0,0 +1,370 @@
+// The store derives `defmt::Format` with the emitted crate's `defmt` feature.
+#![allow(unexpected_cfgs)]
+
+use embedded_storage::nor_flash::{
+    ErrorType, NorFlash, NorFlashError, NorFlashErrorKind, ReadNorFlash,
+};
+
+#[allow(dead_code)]
+#[path = "../res/src/bond_store.rs"]
+mod bond_store;
+
+use bond_store::{BondStore, Error, Key};
+
+const PAGE: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PowerLoss;
+
+impl NorFlashError for PowerLoss {
+    fn kind(&self) -> NorFlashErrorKind {
+        NorFlashErrorKind::Other
+    }
+}
+
+/// NOR flash in RAM, losing power after a given number of writes and erases.
+#[derive(Clone)]
+struct MockFlash {
+    data: Vec<u8>,
+    /// Writes and erases left; the next one is torn and the power is lost.
+    budget: Option<usize>,
+    /// Bytes at the start of the page a torn erase leaves untouched.
+    erase_keep: usize,
+    dead: bool,
+    writes: usize,
+    erases: usize,
+}
+
+impl MockFlash {
+    fn new(pages: usize) -> Self {
+        Self {
+            data: vec![0xFF; pages * PAGE],
+            budget: None,
+            erase_keep: 0,
+            dead: false,
+            writes: 0,
+            erases: 0,
+        }
+    }
+
+    fn ops(&self) -> usize {
+        self.writes + self.erases
+    }
+
+    fn lose_power_after(&mut self, ops: usize) {
+        self.budget = Some(ops);
+    }
+
+    fn power_up(&mut self) {
+        self.budget = None;
+        self.dead = false;
+    }
+
+    /// Whether the operation about to run is torn.
+    fn tear(&mut self) -> Result<bool, PowerLoss> {
+        if self.dead {
+            return Err(PowerLoss);
+        }
+        match &mut self.budget {
+            Some(0) => {
+                self.dead = true;
+                Ok(true)
+            }
+            Some(budget) => {
+                *budget -= 1;
+                Ok(false)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+impl ErrorType for MockFlash {
+    type Error = PowerLoss;
+}
+
+impl ReadNorFlash for MockFlash {
+    const READ_SIZE: usize = 1;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), PowerLoss> {
+        let offset = offset as usize;
+        bytes.copy_from_slice(&self.data[offset..offset + bytes.len()]);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.data.len()
+    }
+}
+
+impl NorFlash for MockFlash {
+    const WRITE_SIZE: usize = 8;
+    const ERASE_SIZE: usize = PAGE;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), PowerLoss> {
+        let (from, to) = (from as usize, to as usize);
+        assert!(from.is_multiple_of(PAGE) && to.is_multiple_of(PAGE) && from < to);
+        let torn = self.tear()?;
+        self.erases += 1;
+        let from = if torn { from + self.erase_keep } else { from };
+        self.data[from..to].fill(0xFF);
+        if torn { Err(PowerLoss) } else { Ok(()) }
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), PowerLoss> {
+        let offset = offset as usize;
+        assert!(offset.is_multiple_of(Self::WRITE_SIZE));
+        assert!(bytes.len().is_multiple_of(Self::WRITE_SIZE));
+        let target = offset..offset + bytes.len();
+        assert!(
+            self.data[target.clone()].iter().all(|&byte| byte == 0xFF),
+            "programming {} bytes at {offset:#x} over written flash",
+            bytes.len()
+        );
+        let torn = self.tear()?;
+        self.writes += 1;
+        let len = if torn { bytes.len() / 2 } else { bytes.len() };
+        self.data[target][..len].copy_from_slice(&bytes[..len]);
+        if torn { Err(PowerLoss) } else { Ok(()) }
+    }
+}
+
+type Store = BondStore<MockFlash>;
+
+fn key(index: u16) -> Key {
+    Key::new(0x1000 + index).unwrap()
+}
+
+fn mount(flash: MockFlash) -> Store {
+    let end = flash.data.len() as u32;
+    BondStore::mount(flash, 0..end).unwrap()
+}
+
+fn remount(store: Store) -> Store {
+    mount(store.into_inner())
+}
+
+fn read(store: &mut Store, key: Key) -> Option<Vec<u8>> {
+    let mut buf = [0; PAGE];
+    let len = store.read(key, &mut buf).unwrap()?;
+    Some(buf[..len].to_vec())
+}
+
+#[test]
+fn write_read_round_trip() {
+    let mut store = mount(MockFlash::new(4));
+    assert_eq!(read(&mut store, key(0)), None);
+
+    store.write(key(0), b"long term key").unwrap();
+    store.write(Key::peer(3), b"irk").unwrap();
+    assert_eq!(read(&mut store, key(0)).unwrap(), b"long term key");
+    assert_eq!(read(&mut store, Key::peer(3)).unwrap(), b"irk");
+    let mut short = [0; 4];
+    assert_eq!(store.read(key(0), &mut short), Err(Error::BufferTooSmall));
+
+    let mut store = remount(store);
+    assert_eq!(read(&mut store, key(0)).unwrap(), b"long term key");
+    assert_eq!(read(&mut store, Key::peer(3)).unwrap(), b"irk");
+}
+
+#[test]
+fn overwrite_keeps_the_latest_record() {
+    let mut store = mount(MockFlash::new(4));
+    store.write(key(0), &[1; 40]).unwrap();
+    store.write(key(0), &[2; 10]).unwrap();
+    assert_eq!(read(&mut store, key(0)).unwrap(), [2; 10]);
+
+    let mut flash = store.into_inner();
+    let ops = flash.ops();
+    let mut store = mount(flash);
+    store.write(key(0), &[2; 10]).unwrap();
+    flash = store.into_inner();
+    assert_eq!(
+        flash.ops(),
+        ops,
+        "rewriting the same data touched the flash"
+    );
+
+    let mut store = mount(flash);
+    assert_eq!(read(&mut store, key(0)).unwrap(), [2; 10]);
+}
+
+#[test]
+fn remove_deletes_the_record() {
+    let mut store = mount(MockFlash::new(4));
+    store.write(key(0), b"bond").unwrap();
+    store.write(key(1), b"other").unwrap();
+    store.remove(key(0)).unwrap();
+    assert_eq!(read(&mut store, key(0)), None);
+
+    let mut store = remount(store);
+    assert_eq!(read(&mut store, key(0)), None);
+    assert_eq!(read(&mut store, key(1)).unwrap(), b"other");
+    store.remove(key(2)).unwrap();
+}
+
+#[test]
+fn records_survive_many_reclaims_of_two_pages() {
+    let mut store = mount(MockFlash::new(2));
+    for round in 0..100u8 {
+        store.write(key(0), &[round; 40]).unwrap();
+        store.write(key(1), &[!round; 20]).unwrap();
+        if round % 10 == 0 {
+            store = remount(store);
+        }
+        assert_eq!(read(&mut store, key(0)).unwrap(), [round; 40]);
+        assert_eq!(read(&mut store, key(1)).unwrap(), [!round; 20]);
+    }
+}
+
+#[test]
+fn two_full_pages_report_full_without_erasing() {
+    let mut store = mount(MockFlash::new(2));
+    for index in 0..4 {
+        store.write(key(index), &[index as u8; 40]).unwrap();
+    }
+
+    let flash = store.into_inner();
+    let erases = flash.erases;
+    let mut store = mount(flash);
+    assert_eq!(store.write(key(4), &[4; 40]), Err(Error::Full));
+    assert_eq!(store.write(key(4), &[4; 40]), Err(Error::Full));
+    let flash = store.into_inner();
+    assert_eq!(flash.erases, erases);
+
+    // Removing a record makes room again.
+    let mut store = mount(flash);
+    store.remove(key(0)).unwrap();
+    store.write(key(4), &[4; 40]).unwrap();
+    let mut store = remount(store);
+    assert_eq!(read(&mut store, key(0)), None);
+    for index in 1..5 {
+        assert_eq!(read(&mut store, key(index)).unwrap(), [index as u8; 40]);
+    }
+}
+
+/// Runs `op` on a store prepared by `setup`, losing power before each of its
+/// flash operations in turn, and checks the remounted store holds either the
+/// records before `op` or after it.
+fn lose_power_during(
+    pages: usize,
+    erase_keep: usize,
+    setup: &[(u16, Vec<u8>)],
+    op: (u16, Vec<u8>),
+) {
+    let prepare = || {
+        let mut store = mount(MockFlash::new(pages));
+        for (index, data) in setup {
+            store.write(key(*index), data).unwrap();
+        }
+        let mut flash = store.into_inner();
+        flash.erase_keep = erase_keep;
+        flash
+    };
+    let expected = |index: u16| {
+        setup
+            .iter()
+            .rev()
+            .find(|(other, _)| *other == index)
+            .map(|(_, data)| data.clone())
+            .filter(|data| !data.is_empty())
+    };
+
+    let flash = prepare();
+    let before = flash.clone();
+    let mut store = mount(flash);
+    store.write(key(op.0), &op.1).unwrap();
+    let after = store.into_inner();
+    let ops = after.ops() - before.ops();
+    assert!(
+        after.erases > before.erases,
+        "the operation does not reclaim"
+    );
+
+    for step in 0..ops {
+        let mut flash = prepare();
+        flash.lose_power_after(step);
+        let mut store = mount(flash);
+        assert_eq!(
+            store.write(key(op.0), &op.1),
+            Err(Error::Flash(PowerLoss)),
+            "power lost after {step} of {ops} operations"
+        );
+
+        let mut flash = store.into_inner();
+        flash.power_up();
+        let mut store = mount(flash);
+        for _ in 0..2 {
+            for index in 0..8 {
+                let found = read(&mut store, key(index));
+                if index == op.0 {
+                    let new = Some(op.1.clone()).filter(|data| !data.is_empty());
+                    assert!(
+                        found == expected(index) || found == new,
+                        "key {index} after losing power at step {step}: {found:?}"
+                    );
+                } else {
+                    assert_eq!(found, expected(index), "key {index}, step {step}");
+                }
+            }
+            // The store stays usable.
+            store.write(key(op.0), &op.1).unwrap();
+            store = remount(store);
+        }
+    }
+}
+
+#[test]
+fn torn_record_write_keeps_the_previous_version() {
+    let setup = [(0, vec![1; 40]), (1, vec![2; 40])];
+    let mut store = mount(MockFlash::new(2));
+    for (index, data) in &setup {
+        store.write(key(*index), data).unwrap();
+    }
+    for step in 0..3 {
+        let mut flash = store.into_inner();
+        flash.lose_power_after(step);
+        store = mount(flash);
+        assert!(store.write(key(0), &[3; 40]).is_err());
+        let mut flash = store.into_inner();
+        flash.power_up();
+        store = mount(flash);
+        assert_eq!(read(&mut store, key(0)).unwrap(), [1; 40], "step {step}");
+        assert_eq!(read(&mut store, key(1)).unwrap(), [2; 40]);
+    }
+    store.write(key(0), &[4; 40]).unwrap();
+    let mut store = remount(store);
+    assert_eq!(read(&mut store, key(0)).unwrap(), [4; 40]);
+}
+
+/// Fills the first page, with a superseded record to reclaim.
+fn one_full_page() -> Vec<(u16, Vec<u8>)> {
+    vec![
+        (0, vec![1; 40]),
+        (1, vec![2; 40]),
+        (0, vec![3; 40]),
+        (2, vec![4; 40]),
+    ]
+}
+
+#[test]
+fn torn_reclaim_of_two_pages_loses_nothing() {
+    for erase_keep in [0, 8, 16, 24, PAGE / 2] {
+        lose_power_during(2, erase_keep, &one_full_page(), (3, vec![5; 40]));
+        lose_power_during(2, erase_keep, &one_full_page(), (1, vec![6; 40]));
+    }
+}
+
+#[test]
+fn torn_reclaim_of_three_pages_loses_nothing() {
+    let mut setup = one_full_page();
+    setup.extend([
+        (1, vec![7; 40]),
+        (0, vec![8; 40]),
+        (4, vec![9; 40]),
+        (1, vec![10; 40]),
+    ]);
+    for erase_keep in [0, 8, 16, 24, PAGE / 2] {
+        lose_power_during(3, erase_keep, &setup, (5, vec![11; 40]));
+    }
+}
@@ -0,0 +1,148 @@
+// The module is built with the emitted crate's `capture` feature.
+#![allow(unexpected_cfgs)]
+
+#[allow(dead_code)]
+#[path = "../res/src/capture.rs"]
+mod capture;
+
+use capture::*;
+
+#[derive(Default)]
+struct Buffer(Vec<u8>);
+
+impl Sink for Buffer {
+    type Error = core::convert::Infallible;
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.0.extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// Splits a pcapng capture into its blocks, checking that each starts and
+/// ends with the same total length, a multiple of 4.
+fn blocks(mut bytes: &[u8]) -> Vec<(u32, &[u8])> {
+    let mut blocks = Vec::new();
+    while !bytes.is_empty() {
+        let word = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        let (block_type, len) = (word(0), word(4) as usize);
+        assert_eq!(len % 4, 0);
+        assert_eq!(word(len - 4) as usize, len);
+        blocks.push((block_type, &bytes[8..len - 4]));
+        bytes = &bytes[len..];
+    }
+    blocks
+}
+
+#[test]
+fn header_blocks_are_the_expected_bytes() {
+    let mut writer = PcapngWriter::new(Buffer::default()).unwrap();
+    let hci = writer.add_interface(LinkType::BluetoothHciH4).unwrap();
+    let mac = writer.add_interface(LinkType::Ieee802154Tap).unwrap();
+    assert_ne!(hci, mac);
+    let bytes = writer.into_inner().0;
+    assert_eq!(
+        bytes,
+        [
+            // Section header block: magic, version 1.0, unknown length.
+            0x0A, 0x0D, 0x0D, 0x0A, 28, 0, 0, 0, 0x4D, 0x3C, 0x2B, 0x1A, 1, 0, 0, 0, 0xFF, 0xFF,
+            0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 28, 0, 0, 0,
+            // Interface description blocks: link type, no snapshot length.
+            1, 0, 0, 0, 20, 0, 0, 0, 187, 0, 0, 0, 0, 0, 0, 0, 20, 0, 0, 0, //
+            1, 0, 0, 0, 20, 0, 0, 0, 0x1B, 0x01, 0, 0, 0, 0, 0, 0, 20, 0, 0, 0,
+        ]
+    );
+    assert_eq!(blocks(&bytes).len(), 3);
+}
+
+#[test]
+fn hci_packets_carry_their_type_direction_and_timestamp() {
+    let mut writer = PcapngWriter::new(Buffer::default()).unwrap();
+    let hci = writer.add_interface(LinkType::BluetoothHciH4).unwrap();
+    // HCI_Reset, then its Command Complete event.
+    writer
+        .write_hci(
+            hci,
+            0x1_0000_0002,
+            Direction::Outbound,
+            HciPacketType::Command,
+            &[0x03, 0x0C, 0x00],
+        )
+        .unwrap();
+    writer
+        .write_hci(
+            hci,
+            0x1_0000_0003,
+            Direction::Inbound,
+            HciPacketType::Event,
+            &[0x0E, 0x04, 0x01, 0x03, 0x0C, 0x00],
+        )
+        .unwrap();
+    let bytes = writer.into_inner().0;
+    let blocks = blocks(&bytes);
+    assert_eq!(blocks.len(), 4);
+
+    let (block_type, body) = blocks[2];
+    assert_eq!(block_type, 6);
+    assert_eq!(
+        body,
+        [
+            0, 0, 0, 0, // interface 0
+            1, 0, 0, 0, 2, 0, 0, 0, // timestamp, high word first
+            4, 0, 0, 0, 4, 0, 0, 0, // captured and original lengths
+            0x01, 0x03, 0x0C, 0x00, // H4 command
+            2, 0, 4, 0, 2, 0, 0, 0, // epb_flags: outbound
+            0, 0, 0, 0, // end of options
+        ]
+    );
+
+    let (_, body) = blocks[3];
+    assert_eq!(body[12..16], 7u32.to_le_bytes());
+    // Seven bytes of data, padded to eight.
+    assert_eq!(
+        body[20..28],
+        [0x04, 0x0E, 0x04, 0x01, 0x03, 0x0C, 0x00, 0x00]
+    );
+    assert_eq!(body[28..36], [2, 0, 4, 0, 1, 0, 0, 0], "inbound");
+}
+
+#[test]
+fn ieee802154_frames_get_a_tap_header() {
+    let mut writer = PcapngWriter::new(Buffer::default()).unwrap();
+    writer.add_interface(LinkType::BluetoothHciH4).unwrap();
+    let mac = writer.add_interface(LinkType::Ieee802154Tap).unwrap();
+    let ack = [0x02, 0x00, 0x07];
+    writer
+        .write_802154(mac, 10, Direction::Inbound, Fcs::None, Some(15), &ack)
+        .unwrap();
+    writer
+        .write_802154(
+            mac,
+            11,
+            Direction::Outbound,
+            Fcs::Crc16,
+            None,
+            &[0x02, 0x00, 0x08, 0x12, 0x34],
+        )
+        .unwrap();
+    let bytes = writer.into_inner().0;
+    let blocks = blocks(&bytes);
+
+    let (_, body) = blocks[3];
+    assert_eq!(body[0..4], 1u32.to_le_bytes(), "interface 1");
+    assert_eq!(body[12..16], 23u32.to_le_bytes());
+    assert_eq!(
+        body[20..43],
+        [
+            0, 0, 20, 0, // TAP version 0, length 20
+            0, 0, 1, 0, 0, 0, 0, 0, // FCS type: none
+            3, 0, 3, 0, 15, 0, 0, 0, // channel 15, page 0
+            0x02, 0x00, 0x07,
+        ]
+    );
+
+    let (_, body) = blocks[4];
+    assert_eq!(body[12..16], 17u32.to_le_bytes());
+    assert_eq!(body[20..32], [0, 0, 12, 0, 0, 0, 1, 0, 1, 0, 0, 0]);
+    assert_eq!(body[32..37], [0x02, 0x00, 0x08, 0x12, 0x34]);
+}
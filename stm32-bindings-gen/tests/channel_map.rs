@@ -0,0 +1,114 @@
+// The module is built with the emitted crate's `channel_map` feature.
+#![allow(unexpected_cfgs)]
+
+#[allow(dead_code)]
+#[path = "../res/src/channel_map.rs"]
+mod channel_map;
+
+/// The HCI commands the module calls.
+mod bindings {
+    pub mod wba_ble_stack {
+        use std::cell::Cell;
+
+        thread_local! {
+            pub static CLASSIFIED: Cell<Option<[u8; 5]>> = const { Cell::new(None) };
+        }
+
+        pub unsafe fn hci_le_set_host_channel_classification(map: *const u8) -> u8 {
+            CLASSIFIED.set(Some(unsafe { *map.cast::<[u8; 5]>() }));
+            0
+        }
+
+        pub unsafe fn hci_le_read_channel_map(handle: u16, map: *mut u8) -> u8 {
+            let bytes: [u8; 5] = match handle {
+                1 => [0xFF, 0x00, 0x00, 0x00, 0x10],
+                2 => [0x00, 0x00, 0x00, 0x00, 0x20],
+                _ => return 0x02,
+            };
+            unsafe { map.cast::<[u8; 5]>().write(bytes) };
+            0
+        }
+    }
+}
+
+use channel_map::*;
+
+#[test]
+fn channels_are_little_endian_bits() {
+    assert_eq!(ChannelMap::ALL.into_bytes(), [0xFF, 0xFF, 0xFF, 0xFF, 0x1F]);
+    assert_eq!(ChannelMap::default(), ChannelMap::ALL);
+    assert_eq!(ChannelMap::ALL.len(), DATA_CHANNELS);
+
+    let map: ChannelMap = [0, 9, 36].into_iter().collect();
+    assert_eq!(map.into_bytes(), [0x01, 0x02, 0x00, 0x00, 0x10]);
+    assert_eq!(map.iter().collect::<Vec<_>>(), [0, 9, 36]);
+    assert_eq!(map.iter().len(), 3);
+    assert!(map.contains(36) && !map.contains(35) && !map.contains(37));
+
+    let mut map = ChannelMap::empty();
+    assert!(map.is_empty());
+    assert_eq!(map.insert(37), Err(Error::Invalid));
+    map.insert(20).unwrap();
+    map.insert(21).unwrap();
+    map.remove(20).unwrap();
+    assert_eq!(map.into_bytes(), [0x00, 0x00, 0x20, 0x00, 0x00]);
+
+    assert_eq!(ChannelMap::from_bytes([0xFF, 0xFF, 0xFF, 0xFF, 0x20]), None);
+    assert_eq!(
+        ChannelMap::from_bytes([0xFF, 0xFF, 0xFF, 0xFF, 0x1F]),
+        Some(ChannelMap::ALL)
+    );
+}
+
+#[test]
+fn wifi_channels_remove_the_overlapping_data_channels() {
+    // Data channels 0 to 10 lie below advertising channel 38 at 2426 MHz,
+    // 11 to 36 between it and channel 39 at 2480 MHz.
+    assert_eq!(ChannelMap::frequency_mhz(0), Some(2404));
+    assert_eq!(ChannelMap::frequency_mhz(10), Some(2424));
+    assert_eq!(ChannelMap::frequency_mhz(11), Some(2428));
+    assert_eq!(ChannelMap::frequency_mhz(36), Some(2478));
+    assert_eq!(ChannelMap::frequency_mhz(37), None);
+
+    let removed = |wifi| {
+        ChannelMap::ALL
+            .iter()
+            .filter(|&channel| !ChannelMap::ALL.without_wifi_channel(wifi).contains(channel))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(removed(1), (0..=9).collect::<Vec<_>>());
+    assert_eq!(removed(6), (11..=21).collect::<Vec<_>>());
+    assert_eq!(removed(11), (23..=33).collect::<Vec<_>>());
+    assert_eq!(removed(14), []);
+
+    let clear = ChannelMap::ALL
+        .without_wifi_channel(1)
+        .without_wifi_channel(6)
+        .without_wifi_channel(11);
+    assert_eq!(clear.iter().collect::<Vec<_>>(), [10, 22, 34, 35, 36]);
+}
+
+#[test]
+fn commands_check_and_pass_the_map() {
+    let single: ChannelMap = [5].into_iter().collect();
+    assert!(!single.is_usable());
+    assert_eq!(
+        set_host_channel_classification(single),
+        Err(Error::TooFewChannels)
+    );
+    assert_eq!(bindings::wba_ble_stack::CLASSIFIED.get(), None);
+
+    let clear = ChannelMap::ALL.without_wifi_channel(6);
+    assert_eq!(set_host_channel_classification(clear), Ok(()));
+    assert_eq!(
+        bindings::wba_ble_stack::CLASSIFIED.get(),
+        Some(clear.into_bytes())
+    );
+
+    assert_eq!(
+        read_channel_map(1).map(|map| map.iter().collect::<Vec<_>>()),
+        Ok(vec![0, 1, 2, 3, 4, 5, 6, 7, 36])
+    );
+    assert_eq!(read_channel_map(2), Err(Error::Invalid));
+    assert_eq!(read_channel_map(3), Err(Error::Rejected(0x02)));
+}
@@ -0,0 +1,133 @@
+// The module is built with the emitted crate's `event_mask` feature.
+#![allow(unexpected_cfgs)]
+
+#[allow(dead_code)]
+#[path = "../res/src/event_mask.rs"]
+mod event_mask;
+
+/// The HCI commands the module calls, recording the masks they get.
+mod bindings {
+    pub mod wba_ble_stack {
+        use std::cell::RefCell;
+
+        thread_local! {
+            pub static SENT: RefCell<Vec<(&'static str, [u8; 8])>> = const { RefCell::new(Vec::new()) };
+        }
+
+        fn record(command: &'static str, mask: *const u8) -> u8 {
+            let mask = unsafe { *mask.cast::<[u8; 8]>() };
+            SENT.with(|sent| sent.borrow_mut().push((command, mask)));
+            if command == "page_2" { 0x12 } else { 0 }
+        }
+
+        pub unsafe fn hci_set_event_mask(mask: *const u8) -> u8 {
+            record("page_1", mask)
+        }
+
+        pub unsafe fn hci_set_event_mask_page_2(mask: *const u8) -> u8 {
+            record("page_2", mask)
+        }
+
+        pub unsafe fn hci_le_set_event_mask(mask: *const u8) -> u8 {
+            record("le", mask)
+        }
+    }
+}
+
+use event_mask::*;
+
+/// Byte and bit of each event in the mask parameter, from the Core
+/// specification, Vol 4, Part E, 7.3.1, 7.3.69 and 7.8.1.
+fn assert_at<M: Copy + std::fmt::Debug>(into_bytes: fn(M) -> [u8; 8], cases: &[(M, usize, u8)]) {
+    for &(mask, byte, bit) in cases {
+        let mut expected = [0; 8];
+        expected[byte] = 1 << bit;
+        assert_eq!(into_bytes(mask), expected, "{mask:?}");
+    }
+}
+
+#[test]
+fn bits_are_at_their_spec_positions() {
+    assert_at(
+        EventMask::into_bytes,
+        &[
+            (EventMask::DISCONNECTION_COMPLETE, 0, 4),
+            (EventMask::ENCRYPTION_CHANGE, 0, 7),
+            (EventMask::READ_REMOTE_VERSION_INFORMATION_COMPLETE, 1, 3),
+            (EventMask::HARDWARE_ERROR, 1, 7),
+            (EventMask::DATA_BUFFER_OVERFLOW, 3, 1),
+            (EventMask::ENCRYPTION_KEY_REFRESH_COMPLETE, 5, 7),
+            (EventMask::LE_META, 7, 5),
+        ],
+    );
+    assert_at(
+        EventMaskPage2::into_bytes,
+        &[
+            (EventMaskPage2::AUTHENTICATED_PAYLOAD_TIMEOUT_EXPIRED, 2, 7),
+            (EventMaskPage2::ENCRYPTION_CHANGE_V2, 3, 1),
+        ],
+    );
+    assert_at(
+        LeEventMask::into_bytes,
+        &[
+            (LeEventMask::CONNECTION_COMPLETE, 0, 0),
+            (LeEventMask::LONG_TERM_KEY_REQUEST, 0, 4),
+            (LeEventMask::GENERATE_DHKEY_COMPLETE, 1, 0),
+            (LeEventMask::PHY_UPDATE_COMPLETE, 1, 3),
+            (LeEventMask::SCAN_TIMEOUT, 2, 0),
+            (LeEventMask::CHANNEL_SELECTION_ALGORITHM, 2, 3),
+            (LeEventMask::CIS_ESTABLISHED, 3, 0),
+            (LeEventMask::PATH_LOSS_THRESHOLD, 3, 7),
+            (LeEventMask::BIGINFO_ADVERTISING_REPORT, 4, 1),
+            (LeEventMask::ENHANCED_CONNECTION_COMPLETE_V2, 5, 0),
+            (LeEventMask::CIS_ESTABLISHED_V2, 5, 1),
+        ],
+    );
+}
+
+#[test]
+fn defaults_match_the_controller_reset_values() {
+    assert_eq!(EventMask::default().bits(), 0x0000_1FFF_FFFF_FFFF);
+    assert!(!EventMask::default().contains(EventMask::LE_META));
+    assert_eq!(EventMaskPage2::default().bits(), 0);
+    assert_eq!(
+        LeEventMask::default(),
+        LeEventMask::CONNECTION_COMPLETE
+            | LeEventMask::ADVERTISING_REPORT
+            | LeEventMask::CONNECTION_UPDATE_COMPLETE
+            | LeEventMask::READ_REMOTE_FEATURES_COMPLETE
+            | LeEventMask::LONG_TERM_KEY_REQUEST
+    );
+    assert_eq!(LeEventMask::ALL.bits(), (1 << 42) - 1);
+}
+
+#[test]
+fn masks_combine_and_reach_the_controller_by_value() {
+    let mut mask = EventMask::DISCONNECTION_COMPLETE | EventMask::LE_META;
+    mask |= EventMask::HARDWARE_ERROR;
+    assert!(mask.contains(EventMask::LE_META | EventMask::HARDWARE_ERROR));
+    assert_eq!(
+        mask - EventMask::LE_META,
+        EventMask::DISCONNECTION_COMPLETE | EventMask::HARDWARE_ERROR
+    );
+    assert_eq!(
+        mask & !EventMask::HARDWARE_ERROR,
+        mask.without(EventMask::HARDWARE_ERROR)
+    );
+
+    assert_eq!(set_event_mask(mask), Ok(()));
+    assert_eq!(set_le_event_mask(LeEventMask::CONNECTIONS), Ok(()));
+    assert_eq!(
+        set_event_mask_page_2(EventMaskPage2::empty()),
+        Err(Rejected(0x12))
+    );
+    let sent = bindings::wba_ble_stack::SENT.with(|sent| sent.take());
+    assert_eq!(
+        sent,
+        [
+            ("page_1", [0x10, 0x80, 0, 0, 0, 0, 0, 0x20]),
+            ("le", [0xFF, 0x0B, 0x08, 0, 0, 0, 0, 0]),
+            ("page_2", [0; 8]),
+        ]
+    );
+}
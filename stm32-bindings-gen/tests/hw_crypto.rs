@@ -0,0 +1,140 @@
+// The drivers are built with the emitted crate's `hw_crypto_*` features.
+#![allow(unexpected_cfgs)]
+
+#[allow(dead_code)]
+#[path = "../res/src/hw_crypto.rs"]
+mod hw_crypto;
+
+/// The constants of `wba_hw_if.h` the module uses.
+mod bindings {
+    pub mod wba_hw_if {
+        pub const HW_AES_DEC: u32 = 0;
+        pub const HW_AES_ENC: u32 = 1;
+        pub const HW_AES_REV: u32 = 2;
+        pub const HW_AES_SWAP: u32 = 4;
+    }
+}
+
+use bindings::wba_hw_if::*;
+use hw_crypto::Aes;
+
+/// FIPS-197 appendix C.1, in the byte order of the standard.
+const KEY: [u8; 16] = [
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f,
+];
+const PLAINTEXT: [u8; 16] = [
+    0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff,
+];
+const CIPHERTEXT: [u8; 16] = [
+    0x69, 0xc4, 0xe0, 0xd8, 0x6a, 0x7b, 0x04, 0x30, 0xd8, 0xcd, 0xb7, 0x80, 0x70, 0xb4, 0xc5, 0x5a,
+];
+
+/// Knows the single FIPS-197 C.1 block, and fails on anything else.
+struct KnownAnswerAes {
+    key: Option<[u8; 16]>,
+}
+
+impl KnownAnswerAes {
+    fn keyed(mode: u32, key: [u8; 16]) -> Self {
+        let mut aes = Self { key: None };
+        aes.set_key(&hw_crypto::aes_key(mode, key));
+        aes
+    }
+
+    fn map(&self, block: &mut [u8; 16], from: [u8; 16], to: [u8; 16]) {
+        assert_eq!(self.key, Some(KEY), "wrong key");
+        assert_eq!(*block, from, "wrong input block");
+        *block = to;
+    }
+}
+
+impl Aes for KnownAnswerAes {
+    fn set_key(&mut self, key: &[u8; 16]) {
+        self.key = Some(*key);
+    }
+
+    fn encrypt(&mut self, block: &mut [u8; 16]) {
+        self.map(block, PLAINTEXT, CIPHERTEXT);
+    }
+
+    fn decrypt(&mut self, block: &mut [u8; 16]) {
+        self.map(block, CIPHERTEXT, PLAINTEXT);
+    }
+}
+
+/// C.1 key as callers pass it with `HW_AES_REV`.
+const REVERSED_KEY: [u8; 16] = [
+    0x0f, 0x0e, 0x0d, 0x0c, 0x0b, 0x0a, 0x09, 0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01, 0x00,
+];
+
+/// C.1 blocks in the data words of `HW_AES_Crypt`: first byte most
+/// significant, as the AES data registers take them.
+const PLAINTEXT_WORDS: [u32; 4] = [0x0011_2233, 0x4455_6677, 0x8899_aabb, 0xccdd_eeff];
+const CIPHERTEXT_WORDS: [u32; 4] = [0x69c4_e0d8, 0x6a7b_0430, 0xd8cd_b780, 0x70b4_c55a];
+
+/// The same with `HW_AES_SWAP`: bytes in memory order.
+const SWAPPED_PLAINTEXT_WORDS: [u32; 4] = [0x3322_1100, 0x7766_5544, 0xbbaa_9988, 0xffee_ddcc];
+const SWAPPED_CIPHERTEXT_WORDS: [u32; 4] = [0xd8e0_c469, 0x3004_7b6a, 0x80b7_cdd8, 0x5ac5_b470];
+
+/// C.1 blocks as `HW_AES_Crypt8` takes them in memory without the byte
+/// swap: each word of four bytes reversed on this little-endian target.
+const CRYPT8_PLAINTEXT: [u8; 16] = [
+    0x33, 0x22, 0x11, 0x00, 0x77, 0x66, 0x55, 0x44, 0xbb, 0xaa, 0x99, 0x88, 0xff, 0xee, 0xdd, 0xcc,
+];
+const CRYPT8_CIPHERTEXT: [u8; 16] = [
+    0xd8, 0xe0, 0xc4, 0x69, 0x30, 0x04, 0x7b, 0x6a, 0x80, 0xb7, 0xcd, 0xd8, 0x5a, 0xc5, 0xb4, 0x70,
+];
+
+#[test]
+fn crypt_matches_fips_197_in_every_mode() {
+    let vectors = [
+        (0, KEY, PLAINTEXT_WORDS, CIPHERTEXT_WORDS),
+        (HW_AES_REV, REVERSED_KEY, PLAINTEXT_WORDS, CIPHERTEXT_WORDS),
+        (
+            HW_AES_SWAP,
+            KEY,
+            SWAPPED_PLAINTEXT_WORDS,
+            SWAPPED_CIPHERTEXT_WORDS,
+        ),
+        (
+            HW_AES_REV | HW_AES_SWAP,
+            REVERSED_KEY,
+            SWAPPED_PLAINTEXT_WORDS,
+            SWAPPED_CIPHERTEXT_WORDS,
+        ),
+    ];
+    for (mode, key, plaintext, ciphertext) in vectors {
+        let mut aes = KnownAnswerAes::keyed(mode, key);
+        let encrypted = hw_crypto::aes_crypt(&mut aes, mode | HW_AES_ENC, plaintext);
+        assert_eq!(encrypted, ciphertext, "mode {mode:#x}");
+        let decrypted = hw_crypto::aes_crypt(&mut aes, mode | HW_AES_DEC, ciphertext);
+        assert_eq!(decrypted, plaintext, "mode {mode:#x}");
+    }
+}
+
+#[test]
+fn crypt8_matches_fips_197_in_every_mode() {
+    let vectors = [
+        (0, KEY, CRYPT8_PLAINTEXT, CRYPT8_CIPHERTEXT),
+        (
+            HW_AES_REV,
+            REVERSED_KEY,
+            CRYPT8_PLAINTEXT,
+            CRYPT8_CIPHERTEXT,
+        ),
+        (HW_AES_SWAP, KEY, PLAINTEXT, CIPHERTEXT),
+        (
+            HW_AES_REV | HW_AES_SWAP,
+            REVERSED_KEY,
+            PLAINTEXT,
+            CIPHERTEXT,
+        ),
+    ];
+    for (mode, key, plaintext, ciphertext) in vectors {
+        let mut aes = KnownAnswerAes::keyed(mode, key);
+        let encrypted = hw_crypto::aes_crypt8(&mut aes, mode | HW_AES_ENC, plaintext);
+        assert_eq!(encrypted, ciphertext, "mode {mode:#x}");
+        let decrypted = hw_crypto::aes_crypt8(&mut aes, mode | HW_AES_DEC, ciphertext);
+        assert_eq!(decrypted, plaintext, "mode {mode:#x}");
+    }
+}
@@ -0,0 +1,221 @@
+// The module is built with the emitted crate's `mac_frame` feature.
+#![allow(unexpected_cfgs)]
+
+#[allow(dead_code)]
+#[path = "../res/src/mac_frame.rs"]
+mod mac_frame;
+
+use mac_frame::*;
+
+const EXT_A: u64 = 0x0011_2233_4455_6677;
+const EXT_B: u64 = 0x8899_AABB_CCDD_EEFF;
+
+fn data(version: FrameVersion) -> Header {
+    Header {
+        frame_type: FrameType::Data,
+        version,
+        frame_pending: false,
+        ack_request: false,
+        ie_present: false,
+        sequence_number: Some(0),
+        dst_pan_id: None,
+        dst_address: None,
+        src_pan_id: None,
+        src_address: None,
+        security: None,
+    }
+}
+
+fn encode(frame: &Frame) -> Vec<u8> {
+    let mut buf = [0; 127];
+    let len = frame.encode(&mut buf).unwrap();
+    assert_eq!(len, frame.encoded_len());
+    buf[..len].to_vec()
+}
+
+/// Checks that `header` and `payload` encode to `bytes` and back.
+fn assert_codes(header: Header, payload: &[u8], bytes: &[u8]) {
+    let frame = Frame { header, payload };
+    assert_eq!(encode(&frame), bytes, "{header:?}");
+    assert_eq!(Frame::parse(bytes), Ok(frame));
+}
+
+#[test]
+fn compressed_2003_data_frame() {
+    assert_codes(
+        Header {
+            sequence_number: Some(0x2A),
+            dst_pan_id: Some(0x1234),
+            dst_address: Some(Address::BROADCAST),
+            src_address: Some(Address::Short(0x0001)),
+            ..data(FrameVersion::Ieee2003)
+        },
+        &[0xAB, 0xCD],
+        &[
+            0x41, 0x88, 0x2A, 0x34, 0x12, 0xFF, 0xFF, 0x01, 0x00, 0xAB, 0xCD,
+        ],
+    );
+}
+
+#[test]
+fn ack_frame() {
+    assert_codes(
+        Header {
+            frame_type: FrameType::Ack,
+            frame_pending: true,
+            sequence_number: Some(7),
+            ..data(FrameVersion::Ieee2003)
+        },
+        &[],
+        &[0x12, 0x00, 0x07],
+    );
+}
+
+#[test]
+fn secured_2006_frame_with_extended_addresses() {
+    let mut bytes = vec![0x29, 0xDC, 0x05, 0xCD, 0xAB];
+    bytes.extend(EXT_A.to_le_bytes());
+    bytes.extend([0xCD, 0xAB]);
+    bytes.extend(EXT_B.to_le_bytes());
+    bytes.extend([0x0D, 0x01, 0x00, 0x00, 0x00, 0x01, 0xEE, 0xFF]);
+    assert_codes(
+        Header {
+            ack_request: true,
+            sequence_number: Some(5),
+            dst_pan_id: Some(0xABCD),
+            dst_address: Some(Address::Extended(EXT_A)),
+            src_pan_id: Some(0xABCD),
+            src_address: Some(Address::Extended(EXT_B)),
+            security: Some(SecurityHeader {
+                level: SecurityLevel::EncMic32,
+                key_id: KeyIdentifier::Index(1),
+                frame_counter: Some(1),
+                asn_in_nonce: false,
+            }),
+            ..data(FrameVersion::Ieee2006)
+        },
+        &[0xEE, 0xFF],
+        &bytes,
+    );
+    assert_eq!(SecurityLevel::EncMic32.mic_len(), 4);
+    assert_eq!(SecurityLevel::Mic128.mic_len(), 16);
+    assert!(!SecurityLevel::Mic128.encrypted());
+}
+
+#[test]
+fn frame_2015_without_pan_ids_or_sequence_number() {
+    let mut bytes = vec![0x49, 0xEF];
+    bytes.extend(EXT_A.to_le_bytes());
+    bytes.extend(EXT_B.to_le_bytes());
+    bytes.extend([0x78, 0x04, 0x03, 0x02, 0x01, 0x08, 0x07, 0x06, 0x05, 0x09]);
+    bytes.extend([0x00, 0x3F]);
+    assert_codes(
+        Header {
+            ie_present: true,
+            sequence_number: None,
+            dst_address: Some(Address::Extended(EXT_A)),
+            src_address: Some(Address::Extended(EXT_B)),
+            security: Some(SecurityHeader {
+                level: SecurityLevel::None,
+                key_id: KeyIdentifier::Source8 {
+                    source: 0x0506_0708_0102_0304,
+                    index: 9,
+                },
+                frame_counter: None,
+                asn_in_nonce: true,
+            }),
+            ..data(FrameVersion::Ieee2015)
+        },
+        &[0x00, 0x3F],
+        &bytes,
+    );
+}
+
+#[test]
+fn pan_ids_round_trip_in_every_2015_combination() {
+    let addresses = [
+        None,
+        Some(Address::Short(0x1234)),
+        Some(Address::Extended(EXT_A)),
+    ];
+    let pan_ids = [None, Some(0xBEEF)];
+    let mut encoded = 0;
+    for dst_address in addresses {
+        for src_address in addresses {
+            for dst_pan_id in pan_ids {
+                for src_pan_id in pan_ids {
+                    let header = Header {
+                        dst_pan_id,
+                        dst_address,
+                        src_pan_id,
+                        src_address,
+                        ..data(FrameVersion::Ieee2015)
+                    };
+                    let frame = Frame {
+                        header,
+                        payload: &[1, 2, 3],
+                    };
+                    let mut buf = [0; 127];
+                    match frame.encode(&mut buf) {
+                        Ok(len) => {
+                            encoded += 1;
+                            assert_eq!(Frame::parse(&buf[..len]), Ok(frame));
+                        }
+                        Err(error) => assert_eq!(error, Error::Inconsistent, "{header:?}"),
+                    }
+                }
+            }
+        }
+    }
+    // IEEE 802.15.4-2015 table 7-2 gives two PAN ID layouts, one per PAN ID
+    // Compression bit, for each pair of addressing modes.
+    assert_eq!(encoded, 2 * addresses.len() * addresses.len());
+}
+
+#[test]
+fn malformed_frames_are_refused() {
+    assert_eq!(Frame::parse(&[0x41]), Err(Error::Truncated));
+    assert_eq!(
+        Frame::parse(&[0x41, 0x88, 0x2A, 0x34, 0x12, 0xFF, 0xFF, 0x01]),
+        Err(Error::Truncated)
+    );
+    assert_eq!(Frame::parse(&[0x04, 0x00, 0x00]), Err(Error::Reserved));
+    assert_eq!(Frame::parse(&[0x01, 0x04, 0x00]), Err(Error::Reserved));
+    assert_eq!(Frame::parse(&[0x01, 0x30, 0x00]), Err(Error::Reserved));
+    assert_eq!(Frame::parse(&[0x05, 0x00]), Err(Error::Unsupported));
+
+    let frame = |header| Frame {
+        header,
+        payload: &[],
+    };
+    let mut buf = [0; 127];
+    for header in [
+        Header {
+            sequence_number: None,
+            ..data(FrameVersion::Ieee2006)
+        },
+        Header {
+            dst_pan_id: Some(1),
+            ..data(FrameVersion::Ieee2006)
+        },
+        Header {
+            ie_present: true,
+            ..data(FrameVersion::Ieee2003)
+        },
+    ] {
+        assert_eq!(
+            frame(header).encode(&mut buf),
+            Err(Error::Inconsistent),
+            "{header:?}"
+        );
+    }
+    let header = Header {
+        dst_pan_id: Some(1),
+        dst_address: Some(Address::Extended(EXT_A)),
+        ..data(FrameVersion::Ieee2006)
+    };
+    assert_eq!(
+        frame(header).encode(&mut buf[..12]),
+        Err(Error::BufferTooSmall)
+    );
+}
@@ -0,0 +1,64 @@
+// The module is built with the emitted crate's `sleep_clock` feature.
+#![allow(unexpected_cfgs)]
+
+#[allow(dead_code)]
+#[path = "../res/src/sleep_clock.rs"]
+mod sleep_clock;
+
+/// The link-layer functions the module calls.
+mod bindings {
+    pub mod wba_link_layer {
+        pub unsafe fn ll_sys_sleep_clock_source_selection() {}
+
+        pub unsafe fn le_set_sleep_clock_accuracy(_sca: u8) -> u8 {
+            0
+        }
+    }
+}
+
+use sleep_clock::*;
+
+#[test]
+fn ppm_maps_to_the_covering_sca() {
+    let cases = [
+        (0, Sca::Ppm0To20),
+        (20, Sca::Ppm0To20),
+        (21, Sca::Ppm21To30),
+        (50, Sca::Ppm31To50),
+        (51, Sca::Ppm51To75),
+        (100, Sca::Ppm76To100),
+        (150, Sca::Ppm101To150),
+        (250, Sca::Ppm151To250),
+        (251, Sca::Ppm251To500),
+        (500, Sca::Ppm251To500),
+    ];
+    for (ppm, sca) in cases {
+        assert_eq!(Sca::from_ppm(ppm), Some(sca), "{ppm} ppm");
+        assert!(ppm <= sca.max_ppm());
+    }
+    assert_eq!(Sca::from_ppm(501), None);
+}
+
+#[test]
+fn sca_values_follow_the_ble_encoding() {
+    for value in 0..=7u8 {
+        let sca = (0..=500)
+            .filter_map(Sca::from_ppm)
+            .find(|sca| *sca as u8 == value)
+            .unwrap();
+        // Higher values are more accurate.
+        assert_eq!(
+            sca.max_ppm(),
+            [500, 250, 150, 100, 75, 50, 30, 20][usize::from(value)]
+        );
+    }
+}
+
+#[test]
+fn inaccurate_clocks_are_refused() {
+    let config = SleepClockConfig {
+        source: SleepClockSource::Lsi,
+        accuracy_ppm: 1000,
+    };
+    assert_eq!(configure_sleep_clock(config), Err(Error::Inaccurate));
+}
@@ -12,3 +12,33 @@ fn displays_help_message() {
         .stderr(predicate::str::contains("Usage: stm32-bindings-gen"))
         .stdout(predicate::str::is_empty());
 }
+
+#[test]
+fn rejects_unknown_option() {
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("stm32-bindings-gen"));
+    cmd.arg("--frobnicate");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown option: --frobnicate"));
+}
+
+#[test]
+fn rejects_unknown_libc() {
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("stm32-bindings-gen"));
+    cmd.arg("--libc=musl");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown C library `musl`"));
+}
+
+#[test]
+fn rejects_unknown_module() {
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("stm32-bindings-gen"));
+    cmd.arg("--only=wba_nonexistent");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown module `wba_nonexistent`"));
+}